@@ -1,2 +1,23 @@
+pub mod bulk;
 pub mod database;
-pub mod handlers;
\ No newline at end of file
+pub mod duplicates;
+pub mod fulltext;
+pub mod export;
+pub mod facets;
+pub mod handlers;
+pub mod history;
+pub mod language;
+pub mod lookup;
+pub mod paging;
+pub mod patch;
+pub mod picklist;
+pub mod qa;
+pub mod query;
+pub mod recognize;
+pub mod recycle;
+pub mod relations;
+pub mod search;
+pub mod skos;
+pub mod taxonomy;
+pub mod term_sets;
+pub mod xliff;
\ No newline at end of file
@@ -0,0 +1,462 @@
+use axum::extract::State;
+use regex::{NoExpand, Regex, RegexBuilder};
+use rusqlite::{params, Connection, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::dictionary::database::{
+    apply_patch, connect_db, current_epoch, query_terms, read_term, AppState, TermsList,
+};
+use crate::dictionary::history::{last_revision_id, sign_revisions_since, FieldChange};
+use crate::dictionary::patch::{FieldUpdate, TermPatch};
+use crate::dictionary::picklist::{
+    check_patch_picklists, get_picklists, insert_picklist_value, PicklistError,
+};
+use crate::dictionary::query::{parse_query, QueryError};
+use crate::dictionary::search::SearchOptions;
+use crate::import::parse::{TermLanguageSet, TermStatus};
+
+/// The most terms one bulk edit may change.
+pub const MAX_BULK_TERMS: usize = 5000;
+
+/// The fields a bulk edit can set to one value.
+pub const SET_FIELDS: &[&str] = &["subject", "source", "status", "term_type", "attributes"];
+
+/// The fields find-and-replace works on.
+pub const REPLACE_FIELDS: &[&str] = &[
+    "term",
+    "term_type",
+    "subject",
+    "source",
+    "user",
+    "attributes",
+    "remark",
+    "url",
+    "context",
+    "definition",
+    "inflection",
+];
+
+#[derive(Debug)]
+pub enum BulkError {
+    InvalidQuery(QueryError),
+    InvalidRegex(regex::Error),
+    EmptyPattern,
+    UnknownField(String),
+    InvalidStatus(String),
+    UnknownTerm(i32),
+    /// The edit would leave a term without its term.
+    EmptyTerm(i32),
+    NoTerms,
+    TooManyTerms(usize),
+    Picklist(PicklistError),
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for BulkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BulkError::InvalidQuery(err) => write!(f, "invalid query {err}"),
+            BulkError::InvalidRegex(err) => write!(f, "invalid regular expression: {err}"),
+            BulkError::EmptyPattern => write!(f, "nothing to find"),
+            BulkError::UnknownField(field) => write!(f, "field '{field}' cannot be bulk edited"),
+            BulkError::InvalidStatus(status) => write!(f, "unknown status '{status}'"),
+            BulkError::UnknownTerm(id) => write!(f, "term {id} not found"),
+            BulkError::EmptyTerm(id) => write!(f, "the edit would leave term {id} empty"),
+            BulkError::NoTerms => write!(f, "no terms selected"),
+            BulkError::TooManyTerms(count) => write!(
+                f,
+                "{count} terms selected, a bulk edit can change at most {MAX_BULK_TERMS}"
+            ),
+            BulkError::Picklist(err) => write!(f, "{err}"),
+            BulkError::Database(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for BulkError {}
+
+impl From<rusqlite::Error> for BulkError {
+    fn from(err: rusqlite::Error) -> Self {
+        BulkError::Database(err)
+    }
+}
+
+impl From<PicklistError> for BulkError {
+    fn from(err: PicklistError) -> Self {
+        BulkError::Picklist(err)
+    }
+}
+
+/// The terms to edit: the given IDs, or else the results of a query in the
+/// search syntax.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct BulkSelection {
+    #[serde(default)]
+    pub term_ids: Vec<i32>,
+    #[serde(default)]
+    pub q: String,
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub options: SearchOptions,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FindReplace {
+    pub fields: Vec<String>,
+    pub find: String,
+    #[serde(default)]
+    pub replace: String,
+    /// Whether `find` is a regular expression, in which case `replace` can
+    /// refer to its groups as `$1` or `${name}`.
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub ignore_case: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BulkAction {
+    /// Sets a field to a value, or clears it for `null` or an empty value.
+    Set {
+        field: String,
+        value: Option<String>,
+    },
+    /// Adds tags to the `;` separated attributes.
+    AddTags {
+        tags: Vec<String>,
+    },
+    Replace(FindReplace),
+    /// Moves the terms to the recycle bin.
+    Delete,
+}
+
+/// A term a bulk edit changes, with its changes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BulkChange {
+    pub term_id: i32,
+    pub term_set_id: i32,
+    pub term: Option<String>,
+    pub language: Option<String>,
+    pub changes: Vec<FieldChange>,
+}
+
+fn field_value(set: &TermLanguageSet, field: &str) -> Option<String> {
+    let value = match field {
+        "term" => &set.term,
+        "term_type" => &set.term_type,
+        "subject" => &set.subject,
+        "source" => &set.source,
+        "user" => &set.user,
+        "attributes" => &set.attributes,
+        "remark" => &set.remark,
+        "url" => &set.url,
+        "context" => &set.context,
+        "definition" => &set.definition,
+        "inflection" => &set.inflection,
+        "status" => return set.status.map(|status| status.as_str().to_string()),
+        _ => return None,
+    };
+    value.clone()
+}
+
+fn check_field(field: &str, fields: &[&str]) -> Result<(), BulkError> {
+    if fields.contains(&field) {
+        Ok(())
+    } else {
+        Err(BulkError::UnknownField(field.to_string()))
+    }
+}
+
+/// A patch setting `field` to `value`, which must already be normalized.
+fn set_field(patch: &mut TermPatch, field: &str, value: Option<String>) {
+    if field == "status" {
+        patch.status = value
+            .as_deref()
+            .and_then(TermStatus::parse)
+            .map_or(FieldUpdate::Clear, FieldUpdate::Set);
+    } else if let Some(update) = patch.text_field_mut(field) {
+        *update = value.map_or(FieldUpdate::Clear, FieldUpdate::Set);
+    }
+}
+
+fn find_regex(replace: &FindReplace) -> Result<Regex, BulkError> {
+    if replace.find.is_empty() {
+        return Err(BulkError::EmptyPattern);
+    }
+    let pattern = if replace.regex {
+        replace.find.clone()
+    } else {
+        regex::escape(&replace.find)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(replace.ignore_case)
+        .build()
+        .map_err(BulkError::InvalidRegex)
+}
+
+fn split_tags(attributes: &str) -> impl Iterator<Item = &str> {
+    attributes
+        .split([';', ','])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+}
+
+/// The attributes with the tags that are not in them yet appended.
+fn add_tags(attributes: Option<&str>, tags: &[String]) -> Option<String> {
+    let mut result = attributes.unwrap_or("").trim().to_string();
+    for tag in tags
+        .iter()
+        .map(|tag| tag.trim())
+        .filter(|tag| !tag.is_empty())
+    {
+        let present = split_tags(&result).any(|known| known.to_lowercase() == tag.to_lowercase());
+        if !present {
+            if !result.is_empty() {
+                result.push_str("; ");
+            }
+            result.push_str(tag);
+        }
+    }
+    (!result.is_empty()).then_some(result)
+}
+
+/// The live terms of a selection.
+fn select_terms(
+    conn: &Connection,
+    table: &str,
+    selection: &BulkSelection,
+) -> Result<Vec<TermsList>, BulkError> {
+    let terms = if !selection.term_ids.is_empty() {
+        let mut term_ids = selection.term_ids.clone();
+        term_ids.sort_unstable();
+        term_ids.dedup();
+        if term_ids.len() > MAX_BULK_TERMS {
+            return Err(BulkError::TooManyTerms(term_ids.len()));
+        }
+        term_ids
+            .into_iter()
+            .map(|term_id| read_term(conn, table, term_id)?.ok_or(BulkError::UnknownTerm(term_id)))
+            .collect::<Result<Vec<_>, _>>()?
+    } else if !selection.q.trim().is_empty() {
+        let query = parse_query(&selection.q).map_err(BulkError::InvalidQuery)?;
+        query_terms(conn, table, &query, &selection.language, &selection.options)?
+    } else {
+        return Err(BulkError::NoTerms);
+    };
+
+    if terms.is_empty() {
+        return Err(BulkError::NoTerms);
+    }
+    if terms.len() > MAX_BULK_TERMS {
+        return Err(BulkError::TooManyTerms(terms.len()));
+    }
+    Ok(terms)
+}
+
+/// Works out what `action` changes in every term. Terms it leaves as they
+/// are are left out, except when deleting.
+fn plan(
+    terms: Vec<TermsList>,
+    action: &BulkAction,
+) -> Result<Vec<(BulkChange, TermPatch)>, BulkError> {
+    let mut set_value = None;
+    let mut replace_regex = None;
+    match action {
+        BulkAction::Set { field, value } => {
+            check_field(field, SET_FIELDS)?;
+            let value = value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty());
+            set_value = match value {
+                Some(status) if field == "status" => Some(
+                    TermStatus::parse(status)
+                        .ok_or_else(|| BulkError::InvalidStatus(status.to_string()))?
+                        .as_str()
+                        .to_string(),
+                ),
+                value => value.map(str::to_string),
+            };
+        }
+        BulkAction::Replace(replace) => {
+            for field in &replace.fields {
+                check_field(field, REPLACE_FIELDS)?;
+            }
+            replace_regex = Some(find_regex(replace)?);
+        }
+        BulkAction::AddTags { .. } | BulkAction::Delete => {}
+    }
+
+    let mut edits = Vec::new();
+    for entry in terms {
+        let set = &entry.term_language_set;
+        let mut patch = TermPatch::default();
+        let mut changes = Vec::new();
+        let mut change = |patch: &mut TermPatch, field: &str, old: Option<String>, new| {
+            if old != new {
+                set_field(patch, field, new.clone());
+                changes.push(FieldChange {
+                    field: field.to_string(),
+                    old,
+                    new,
+                });
+            }
+        };
+
+        match action {
+            BulkAction::Set { field, .. } => {
+                change(
+                    &mut patch,
+                    field,
+                    field_value(set, field),
+                    set_value.clone(),
+                );
+            }
+            BulkAction::AddTags { tags } => {
+                let new = add_tags(set.attributes.as_deref(), tags);
+                change(&mut patch, "attributes", set.attributes.clone(), new);
+            }
+            BulkAction::Replace(replace) => {
+                let regex = replace_regex.as_ref().expect("compiled above");
+                for field in &replace.fields {
+                    let Some(old) = field_value(set, field) else {
+                        continue;
+                    };
+                    let new = if replace.regex {
+                        regex.replace_all(&old, replace.replace.as_str())
+                    } else {
+                        regex.replace_all(&old, NoExpand(&replace.replace))
+                    };
+                    let new = Some(new.into_owned()).filter(|new| !new.trim().is_empty());
+                    change(&mut patch, field, Some(old), new);
+                }
+            }
+            BulkAction::Delete => {}
+        }
+
+        if changes.is_empty() && !matches!(action, BulkAction::Delete) {
+            continue;
+        }
+        if patch.check_required().is_err() {
+            return Err(BulkError::EmptyTerm(entry.term_id));
+        }
+        edits.push((
+            BulkChange {
+                term_id: entry.term_id,
+                term_set_id: entry.term_set_id,
+                term: set.term.clone(),
+                language: set.language.clone(),
+                changes,
+            },
+            patch,
+        ));
+    }
+    Ok(edits)
+}
+
+/// Puts the values a bulk edit writes to picklist fields through their
+/// picklists, like a single update, without changing any picklist yet. One
+/// value a closed picklist does not have rejects the whole edit. Terms the
+/// picklist spelling leaves as they are drop out. Returns the values still to
+/// be added to open picklists.
+fn check_picklists(
+    State(app_state): State<Arc<AppState>>,
+    edits: &mut Vec<(BulkChange, TermPatch)>,
+    action: &BulkAction,
+) -> Result<Vec<(String, String)>, BulkError> {
+    if matches!(action, BulkAction::Delete) {
+        return Ok(Vec::new());
+    }
+    let picklists = get_picklists(State(app_state))?;
+    let mut new_values = Vec::new();
+    for (change, patch) in edits.iter_mut() {
+        for new_value in check_patch_picklists(&picklists, patch)? {
+            if !new_values.contains(&new_value) {
+                new_values.push(new_value);
+            }
+        }
+        for field_change in &mut change.changes {
+            match patch.text_field_mut(&field_change.field) {
+                Some(FieldUpdate::Set(value)) => field_change.new = Some(value.clone()),
+                Some(FieldUpdate::Clear) => field_change.new = None,
+                _ => {}
+            }
+        }
+        change
+            .changes
+            .retain(|field_change| field_change.old != field_change.new);
+    }
+    edits.retain(|(change, _)| !change.changes.is_empty());
+    Ok(new_values)
+}
+
+/// What a bulk edit would change, without changing anything.
+pub fn preview_bulk_edit(
+    State(app_state): State<Arc<AppState>>,
+    selection: &BulkSelection,
+    action: &BulkAction,
+) -> Result<Vec<BulkChange>, BulkError> {
+    let conn = connect_db(State(app_state.clone()))?;
+    let terms = select_terms(&conn, &app_state.db_info.table_name, selection)?;
+    let mut edits = plan(terms, action)?;
+    check_picklists(State(app_state), &mut edits, action)?;
+    Ok(edits.into_iter().map(|(change, _)| change).collect())
+}
+
+/// Applies a bulk edit in one transaction, so either every term changes or
+/// none does. The terms are read in the same transaction, so no other change
+/// can come in between. Each changed term gets a revision in its history,
+/// made by `edited_by`. Returns the changes.
+pub fn bulk_edit(
+    State(app_state): State<Arc<AppState>>,
+    selection: &BulkSelection,
+    action: &BulkAction,
+    edited_by: Option<&str>,
+) -> Result<Vec<BulkChange>, BulkError> {
+    let table = &app_state.db_info.table_name;
+    let mut conn = connect_db(State(app_state.clone()))?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let last_revision = last_revision_id(&tx)?;
+    let terms = select_terms(&tx, table, selection)?;
+    let mut edits = plan(terms, action)?;
+    let new_values = check_picklists(State(app_state.clone()), &mut edits, action)?;
+
+    let now = current_epoch();
+    for (change, patch) in &edits {
+        let changed = if matches!(action, BulkAction::Delete) {
+            tx.execute(
+                &format!(
+                    "UPDATE {table} SET deleted = 1, deleted_by = ?2, deleted_at = ?3
+                    WHERE term_id = ?1 AND deleted = 0"
+                ),
+                params![change.term_id, edited_by, now],
+            )? == 1
+        } else {
+            let mut patch = patch.clone();
+            if let Some(edited_by) = edited_by {
+                patch.updater_id = FieldUpdate::Set(edited_by.to_string());
+            }
+            patch.update_timestamp = FieldUpdate::Set(now);
+            apply_patch(&tx, table, change.term_id, &patch, None)?
+        };
+        // Dropping the transaction rolls back the terms changed so far.
+        if !changed {
+            return Err(BulkError::UnknownTerm(change.term_id));
+        }
+    }
+    for (field, value) in &new_values {
+        insert_picklist_value(&tx, field, value)?;
+    }
+    if let Some(edited_by) = edited_by {
+        sign_revisions_since(&tx, last_revision, edited_by)?;
+    }
+    tx.commit()?;
+
+    info!("Bulk edit changed {} terms", edits.len());
+    Ok(edits.into_iter().map(|(change, _)| change).collect())
+}
@@ -1,8 +1,25 @@
+use crate::dictionary::facets::create_facet_index;
+use crate::dictionary::fulltext::{create_fulltext_index, search_fulltext};
 use crate::dictionary::handlers::SEARCH_CACHE;
-use crate::import::parse::TermLanguageSet;
+use crate::dictionary::history::{create_history_table, last_revision_id, sign_revisions_since};
+use crate::dictionary::patch::{FieldUpdate, TermPatch, UpdateError};
+use crate::dictionary::picklist::{create_picklist_tables, insert_picklist_value};
+use crate::dictionary::query::Query;
+use crate::dictionary::relations::create_relations_table;
+use crate::dictionary::search::{
+    fold_text, rank_hits, register_search_functions, term_matches, SearchHit, SearchMode,
+    SearchOptions,
+};
+use crate::dictionary::taxonomy::{create_subject_table, link_subjects, resolve_subject};
+use crate::import::parse::{
+    GrammaticalGender, GrammaticalNumber, PartOfSpeech, TermLanguageSet, TermStatus,
+};
 use axum::extract::State;
 use chrono::Utc;
-use rusqlite::{params, Connection, OptionalExtension, Result};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{
+    params, params_from_iter, Connection, OptionalExtension, Result, Row, TransactionBehavior,
+};
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use tracing::{debug, error, info};
@@ -125,6 +142,37 @@ impl TermsList {
             .unwrap_or("No Context")
     }
 
+    pub fn status_or_default(&self) -> &str {
+        self.term_language_set
+            .status
+            .map_or("No Status", |status| status.as_str())
+    }
+
+    pub fn part_of_speech_or_default(&self) -> &str {
+        self.term_language_set
+            .part_of_speech
+            .map_or("No Part of Speech", |pos| pos.as_str())
+    }
+
+    pub fn gender_or_default(&self) -> &str {
+        self.term_language_set
+            .gender
+            .map_or("No Gender", |gender| gender.as_str())
+    }
+
+    pub fn number_or_default(&self) -> &str {
+        self.term_language_set
+            .number
+            .map_or("No Number", |number| number.as_str())
+    }
+
+    pub fn inflection_or_default(&self) -> &str {
+        self.term_language_set
+            .inflection
+            .as_deref()
+            .unwrap_or("No Inflection")
+    }
+
     pub fn definition_or_default(&self) -> &str {
         self.term_language_set
             .definition
@@ -133,6 +181,47 @@ impl TermsList {
     }
 }
 
+pub fn terms_list_from_row(row: &Row) -> Result<TermsList, rusqlite::Error> {
+    Ok(TermsList {
+        term_id: row.get(0)?,
+        term_set_id: row.get(1)?,
+        term_language_set: TermLanguageSet {
+            term: row.get(2)?,
+            language: row.get(3)?,
+            term_type: row.get(4)?,
+            creator_id: row.get(5)?,
+            creation_timestamp: row.get(6)?,
+            updater_id: row.get(7)?,
+            update_timestamp: row.get(8)?,
+            subject: row.get(9)?,
+            source: row.get(10)?,
+            user: row.get(11)?,
+            attributes: row.get(12)?,
+            remark: row.get(13)?,
+            url: row.get(14)?,
+            context: row.get(15)?,
+            definition: row.get(16)?,
+            status: row
+                .get::<_, Option<String>>(17)?
+                .as_deref()
+                .and_then(TermStatus::parse),
+            part_of_speech: row
+                .get::<_, Option<String>>(18)?
+                .as_deref()
+                .and_then(PartOfSpeech::parse),
+            gender: row
+                .get::<_, Option<String>>(19)?
+                .as_deref()
+                .and_then(GrammaticalGender::parse),
+            number: row
+                .get::<_, Option<String>>(20)?
+                .as_deref()
+                .and_then(GrammaticalNumber::parse),
+            inflection: row.get(21)?,
+        },
+    })
+}
+
 pub fn add_term(
     State(app_state): State<Arc<AppState>>,
     term_set: &TermLanguageSet,
@@ -140,6 +229,7 @@ pub fn add_term(
     debug!("Add term: {:?}", term_set);
     let conn = connect_db(State(app_state.clone()))?;
     let term_set_id = get_max_term_set_id(State(app_state.clone()))? + 1;
+    let (subject_id, subject) = subject_columns(&conn, term_set)?;
     let insert_sql = format!(
         "INSERT INTO {} (
             term_set_id, 
@@ -157,8 +247,14 @@ pub fn add_term(
             remark, 
             url,
             context, 
-            definition
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            definition,
+            status,
+            part_of_speech,
+            gender,
+            number,
+            inflection,
+            subject_id
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         app_state.db_info.table_name
     );
     conn.execute(
@@ -172,7 +268,7 @@ pub fn add_term(
             term_set.creation_timestamp,
             term_set.updater_id,
             term_set.update_timestamp,
-            term_set.subject,
+            subject,
             term_set.source,
             term_set.user,
             term_set.attributes,
@@ -180,6 +276,12 @@ pub fn add_term(
             term_set.url,
             term_set.context,
             term_set.definition,
+            term_set.status.map(|status| status.as_str()),
+            term_set.part_of_speech.map(|pos| pos.as_str()),
+            term_set.gender.map(|gender| gender.as_str()),
+            term_set.number.map(|number| number.as_str()),
+            term_set.inflection,
+            subject_id,
         ],
     )?;
     Ok(())
@@ -194,6 +296,7 @@ pub fn add_term_to_term_set(
         term_set, existing_term_set_id
     );
     let conn = connect_db(State(app_state.clone()))?;
+    let (subject_id, subject) = subject_columns(&conn, term_set)?;
     let insert_sql = format!(
         "INSERT INTO {} (
             term_set_id, 
@@ -211,8 +314,14 @@ pub fn add_term_to_term_set(
             remark, 
             url, 
             context, 
-            definition
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            definition,
+            status,
+            part_of_speech,
+            gender,
+            number,
+            inflection,
+            subject_id
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         app_state.db_info.table_name
     );
     conn.execute(
@@ -226,7 +335,7 @@ pub fn add_term_to_term_set(
             term_set.creation_timestamp,
             term_set.updater_id,
             term_set.update_timestamp,
-            term_set.subject,
+            subject,
             term_set.source,
             term_set.user,
             term_set.attributes,
@@ -234,13 +343,32 @@ pub fn add_term_to_term_set(
             term_set.url,
             term_set.context,
             term_set.definition,
+            term_set.status.map(|status| status.as_str()),
+            term_set.part_of_speech.map(|pos| pos.as_str()),
+            term_set.gender.map(|gender| gender.as_str()),
+            term_set.number.map(|number| number.as_str()),
+            term_set.inflection,
+            subject_id,
         ],
     )?;
     Ok(())
 }
+/// The subject node and stored subject path of a term about to be inserted.
+fn subject_columns(
+    conn: &Connection,
+    term_set: &TermLanguageSet,
+) -> Result<(Option<i64>, Option<String>), rusqlite::Error> {
+    Ok(match resolve_subject(conn, term_set.subject.as_deref())? {
+        Some((subject_id, path)) => (Some(subject_id), Some(path)),
+        None => (None, term_set.subject.clone()),
+    })
+}
+
 pub fn connect_db(State(app_state): State<Arc<AppState>>) -> Result<Connection, rusqlite::Error> {
     debug!("Connect db: {:?}", app_state.db_info.table_name);
-    Connection::open(app_state.db_info.path())
+    let conn = Connection::open(app_state.db_info.path())?;
+    register_search_functions(&conn)?;
+    Ok(conn)
 }
 
 pub fn check_termset_count(
@@ -250,7 +378,7 @@ pub fn check_termset_count(
     debug!("Check termset count: {:?}", term_id);
     let conn = connect_db(State(app_state.clone()))?;
     let sql = format!(
-        "SELECT COUNT(*) FROM {} WHERE term_id = ?1",
+        "SELECT COUNT(*) FROM {} WHERE term_id = ?1 AND deleted = 0",
         app_state.db_info.table_name
     );
     let termset_count: i32 = conn.query_row(&sql, [&term_id], |row| row.get(0))?;
@@ -280,7 +408,17 @@ pub fn create_terms_table(State(app_state): State<Arc<AppState>>) -> Result<()>
             remark TEXT,
             url TEXT,
             context TEXT,
-            definition TEXT
+            definition TEXT,
+            status TEXT,
+            part_of_speech TEXT,
+            gender TEXT,
+            number TEXT,
+            inflection TEXT,
+            subject_id INTEGER REFERENCES subjects(id),
+            deleted INTEGER NOT NULL DEFAULT 0,
+            deleted_by TEXT,
+            deleted_at INTEGER,
+            version INTEGER NOT NULL DEFAULT 1
         );
         COMMIT;
         ",
@@ -289,6 +427,130 @@ pub fn create_terms_table(State(app_state): State<Arc<AppState>>) -> Result<()>
 
     conn.execute_batch(&create_table_sql)?;
 
+    if add_column_if_missing(&conn, &app_state.db_info.table_name, "status", "TEXT")? {
+        backfill_status(&conn, &app_state.db_info.table_name)?;
+    }
+    if add_column_if_missing(
+        &conn,
+        &app_state.db_info.table_name,
+        "part_of_speech",
+        "TEXT",
+    )? {
+        backfill_part_of_speech(&conn, &app_state.db_info.table_name)?;
+    }
+    for column in ["gender", "number", "inflection"] {
+        add_column_if_missing(&conn, &app_state.db_info.table_name, column, "TEXT")?;
+    }
+    create_subject_table(&conn)?;
+    if add_column_if_missing(
+        &conn,
+        &app_state.db_info.table_name,
+        "subject_id",
+        "INTEGER REFERENCES subjects(id)",
+    )? {
+        link_subjects(&conn, &app_state.db_info.table_name)?;
+    }
+    add_column_if_missing(
+        &conn,
+        &app_state.db_info.table_name,
+        "deleted",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(&conn, &app_state.db_info.table_name, "deleted_by", "TEXT")?;
+    add_column_if_missing(&conn, &app_state.db_info.table_name, "deleted_at", "INTEGER")?;
+    add_column_if_missing(
+        &conn,
+        &app_state.db_info.table_name,
+        "version",
+        "INTEGER NOT NULL DEFAULT 1",
+    )?;
+    create_version_trigger(&conn, &app_state.db_info.table_name)?;
+
+    Ok(())
+}
+
+/// Bumps the version of a term whenever it changes, so editors can detect
+/// that a term changed since they read it.
+fn create_version_trigger(conn: &Connection, table: &str) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(&format!(
+        "
+        DROP TRIGGER IF EXISTS {table}_version_after_update;
+
+        CREATE TRIGGER {table}_version_after_update AFTER UPDATE ON {table}
+        WHEN new.version = old.version BEGIN
+            UPDATE {table} SET version = old.version + 1 WHERE term_id = new.term_id;
+        END;
+        "
+    ))
+}
+
+/// Adds a column to a table created by an older version. Returns whether the
+/// column had to be added.
+pub fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    declaration: &str,
+) -> Result<bool, rusqlite::Error> {
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1)"),
+        params![column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        info!("Adding column {} to {}", column, table);
+        conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {declaration}"),
+            params![],
+        )?;
+    }
+    Ok(!exists)
+}
+
+/// Moves statuses that TermStar kept in the term type or attributes into the
+/// status column.
+fn backfill_status(conn: &Connection, table: &str) -> Result<(), rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT term_id, term_type, attributes FROM {table}
+         WHERE term_type IS NOT NULL OR attributes IS NOT NULL"
+    ))?;
+    let rows: Vec<(i32, Option<String>, Option<String>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<_, _>>()?;
+    let statuses = rows.into_iter().filter_map(|(term_id, term_type, attributes)| {
+        [term_type, attributes]
+            .into_iter()
+            .flatten()
+            .find_map(|value| TermStatus::find_in(&value))
+            .map(|status| (term_id, status))
+    });
+
+    for (term_id, status) in statuses {
+        conn.execute(
+            &format!("UPDATE {table} SET status = ?1 WHERE term_id = ?2"),
+            params![status.as_str(), term_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Fills the part of speech of terms whose term type names one, such as `noun`.
+fn backfill_part_of_speech(conn: &Connection, table: &str) -> Result<(), rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT term_id, term_type FROM {table} WHERE term_type IS NOT NULL"
+    ))?;
+    let rows: Vec<(i32, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    for (term_id, term_type) in rows {
+        if let Some(pos) = PartOfSpeech::parse(&term_type) {
+            conn.execute(
+                &format!("UPDATE {table} SET part_of_speech = ?1 WHERE term_id = ?2"),
+                params![pos.as_str(), term_id],
+            )?;
+        }
+    }
     Ok(())
 }
 
@@ -298,6 +560,17 @@ pub fn current_epoch() -> i64 {
     timestamp
 }
 
+pub const UNIQUE_VALUE_COLUMNS: &[(&str, &str)] = &[
+    ("unique_languages", "language"),
+    ("unique_term_types", "term_type"),
+    ("unique_creator_ids", "creator_id"),
+    ("unique_updater_ids", "updater_id"),
+    ("unique_subjects", "subject"),
+    ("unique_sources", "source"),
+    ("unique_users", "user"),
+    ("unique_attributes", "attributes"),
+];
+
 pub fn create_unique_values_tables(State(app_state): State<Arc<AppState>>) -> Result<()> {
     debug!(
         "Create unique values tables: {:?}",
@@ -311,42 +584,50 @@ pub fn create_unique_values_tables(State(app_state): State<Arc<AppState>>) -> Re
 
         CREATE TABLE IF NOT EXISTS unique_languages (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            language TEXT UNIQUE
+            language TEXT UNIQUE,
+            term_count INTEGER NOT NULL DEFAULT 0
         );
 
         CREATE TABLE IF NOT EXISTS unique_term_types (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            term_type TEXT UNIQUE
+            term_type TEXT UNIQUE,
+            term_count INTEGER NOT NULL DEFAULT 0
         );
 
         CREATE TABLE IF NOT EXISTS unique_creator_ids (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            creator_id TEXT UNIQUE
+            creator_id TEXT UNIQUE,
+            term_count INTEGER NOT NULL DEFAULT 0
         );
 
         CREATE TABLE IF NOT EXISTS unique_updater_ids (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            updater_id TEXT UNIQUE
+            updater_id TEXT UNIQUE,
+            term_count INTEGER NOT NULL DEFAULT 0
         );
 
         CREATE TABLE IF NOT EXISTS unique_subjects (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            subject TEXT UNIQUE
+            subject TEXT UNIQUE,
+            term_count INTEGER NOT NULL DEFAULT 0
         );
 
         CREATE TABLE IF NOT EXISTS unique_sources (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            source TEXT UNIQUE
+            source TEXT UNIQUE,
+            term_count INTEGER NOT NULL DEFAULT 0
         );
 
         CREATE TABLE IF NOT EXISTS unique_users (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            user TEXT UNIQUE
+            user TEXT UNIQUE,
+            term_count INTEGER NOT NULL DEFAULT 0
         );
 
         CREATE TABLE IF NOT EXISTS unique_attributes (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
-            attributes TEXT UNIQUE
+            attributes TEXT UNIQUE,
+            term_count INTEGER NOT NULL DEFAULT 0
         );
 
         COMMIT;
@@ -356,133 +637,72 @@ pub fn create_unique_values_tables(State(app_state): State<Arc<AppState>>) -> Re
     Ok(())
 }
 
+/// Moves a term to the recycle bin. It is hidden from searches and the
+/// caches until it is restored, and removed for good when it is purged.
 pub fn delete_term(
     State(app_state): State<Arc<AppState>>,
     term_id: i32,
+    deleted_by: Option<&str>,
 ) -> Result<(), rusqlite::Error> {
     debug!("Delete term: {:?}", term_id);
     let conn = connect_db(State(app_state.clone()))?;
-    debug!("Connecting to DB to delete term_id: {}", term_id);
-    conn.execute("BEGIN TRANSACTION", [])?;
-    println!("Executing DELETE for term_id: {}", term_id);
-    let result = conn.execute("DELETE FROM terms WHERE term_id = ?1", params![term_id])?;
-    println!("Deleted {} rows", result);
+    let sql = format!(
+        "UPDATE {} SET deleted = 1, deleted_by = ?2, deleted_at = ?3
+         WHERE term_id = ?1 AND deleted = 0",
+        app_state.db_info.table_name
+    );
+    let result = conn.execute(&sql, params![term_id, deleted_by, current_epoch()])?;
+    debug!("Deleted {} rows", result);
 
     if result == 0 {
-        conn.execute("ROLLBACK", [])?;
         return Err(rusqlite::Error::ExecuteReturnedResults);
     }
 
-    conn.execute("COMMIT", [])?;
-
     Ok(())
 }
 
+/// Moves all terms of a term set to the recycle bin.
 pub fn delete_termset(
     State(app_state): State<Arc<AppState>>,
     termset_to_delete: i32,
+    deleted_by: Option<&str>,
 ) -> Result<(), rusqlite::Error> {
     debug!("Delete termset: {:?}", termset_to_delete);
     let conn = connect_db(State(app_state.clone()))?;
 
     let sql = format!(
-        "DELETE FROM {} WHERE term_set_id = ?1",
+        "UPDATE {} SET deleted = 1, deleted_by = ?2, deleted_at = ?3
+         WHERE term_set_id = ?1 AND deleted = 0",
         app_state.db_info.table_name
     );
 
-    conn.execute(&sql, [&termset_to_delete])?;
+    conn.execute(&sql, params![termset_to_delete, deleted_by, current_epoch()])?;
 
     Ok(())
 }
 
+/// Recounts every unique value table from scratch. Day-to-day changes are
+/// tracked by the facet triggers; this is for databases that come from
+/// elsewhere.
 pub fn extract_and_insert_unique_values(State(app_state): State<Arc<AppState>>) -> Result<()> {
     debug!("Extract and insert unique values");
     let conn = connect_db(State(app_state.clone()))?;
-
-    conn.execute("BEGIN;", params![])?;
-
-    conn.execute("DELETE FROM unique_languages;", params![])?;
-    conn.execute("DELETE FROM unique_term_types;", params![])?;
-    conn.execute("DELETE FROM unique_creator_ids;", params![])?;
-    conn.execute("DELETE FROM unique_updater_ids;", params![])?;
-    conn.execute("DELETE FROM unique_subjects;", params![])?;
-    conn.execute("DELETE FROM unique_sources;", params![])?;
-    conn.execute("DELETE FROM unique_users;", params![])?;
-    conn.execute("DELETE FROM unique_attributes;", params![])?;
-
     let table = &app_state.db_info.table_name;
 
-    conn.execute(
-        &format!(
-            "INSERT OR IGNORE INTO unique_languages (language)
-             SELECT DISTINCT language FROM {};",
-            table
-        ),
-        params![],
-    )?;
-
-    conn.execute(
-        &format!(
-            "INSERT OR IGNORE INTO unique_term_types (term_type)
-             SELECT DISTINCT term_type FROM {};",
-            table
-        ),
-        params![],
-    )?;
-
-    conn.execute(
-        &format!(
-            "INSERT OR IGNORE INTO unique_creator_ids (creator_id)
-             SELECT DISTINCT creator_id FROM {};",
-            table
-        ),
-        params![],
-    )?;
-
-    conn.execute(
-        &format!(
-            "INSERT OR IGNORE INTO unique_updater_ids (updater_id)
-             SELECT DISTINCT updater_id FROM {};",
-            table
-        ),
-        params![],
-    )?;
-
-    conn.execute(
-        &format!(
-            "INSERT OR IGNORE INTO unique_subjects (subject)
-             SELECT DISTINCT subject FROM {};",
-            table
-        ),
-        params![],
-    )?;
-
-    conn.execute(
-        &format!(
-            "INSERT OR IGNORE INTO unique_sources (source)
-             SELECT DISTINCT source FROM {};",
-            table
-        ),
-        params![],
-    )?;
-
-    conn.execute(
-        &format!(
-            "INSERT OR IGNORE INTO unique_users (user)
-             SELECT DISTINCT user FROM {};",
-            table
-        ),
-        params![],
-    )?;
+    conn.execute("BEGIN;", params![])?;
 
-    conn.execute(
-        &format!(
-            "INSERT OR IGNORE INTO unique_attributes (attributes)
-             SELECT DISTINCT attributes FROM {};",
-            table
-        ),
-        params![],
-    )?;
+    for (unique_table, column) in UNIQUE_VALUE_COLUMNS {
+        conn.execute(&format!("DELETE FROM {unique_table};"), params![])?;
+        conn.execute(
+            &format!(
+                "INSERT INTO {unique_table} ({column}, term_count)
+                 SELECT {column}, COUNT(*) FROM {table}
+                 WHERE {column} IS NOT NULL AND deleted = 0
+                 GROUP BY {column};"
+            ),
+            params![],
+        )?;
+    }
 
     conn.execute("COMMIT;", params![])?;
 
@@ -508,6 +728,11 @@ pub fn init_db(State(app_state): State<Arc<AppState>>) -> Result<()> {
 
     create_terms_table(State(app_state.clone()))?;
     create_unique_values_tables(State(app_state.clone()))?;
+    create_facet_index(State(app_state.clone()))?;
+    create_fulltext_index(State(app_state.clone()))?;
+    create_picklist_tables(State(app_state.clone()))?;
+    create_relations_table(State(app_state.clone()))?;
+    create_history_table(State(app_state.clone()))?;
 
     let all_terms = get_all_terms(State(app_state.clone()))?;
 
@@ -553,40 +778,34 @@ pub fn get_all_terms(
             remark, 
             url, 
             context, 
-            definition 
-        FROM {}",
+            definition,
+            status,
+            part_of_speech,
+            gender,
+            number,
+            inflection
+        FROM {}
+        WHERE deleted = 0",
         app_state.db_info.table_name
     );
 
     let mut stmt = conn.prepare(&sql)?;
 
-    let dictionary_iter = stmt.query_map([], |row| {
-        Ok(TermsList {
-            term_id: row.get(0)?,
-            term_set_id: row.get(1)?,
-            term_language_set: TermLanguageSet {
-                term: row.get(2)?,
-                language: row.get(3)?,
-                term_type: row.get(4)?,
-                creator_id: row.get(5)?,
-                creation_timestamp: row.get(6)?,
-                updater_id: row.get(7)?,
-                update_timestamp: row.get(8)?,
-                subject: row.get(9)?,
-                source: row.get(10)?,
-                user: row.get(11)?,
-                attributes: row.get(12)?,
-                remark: row.get(13)?,
-                url: row.get(14)?,
-                context: row.get(15)?,
-                definition: row.get(16)?,
-            },
-        })
-    })?;
+    let dictionary_iter = stmt.query_map([], terms_list_from_row)?;
 
     dictionary_iter.collect()
 }
 
+pub fn count_terms(State(app_state): State<Arc<AppState>>) -> Result<usize, rusqlite::Error> {
+    let conn = connect_db(State(app_state.clone()))?;
+    let sql = format!(
+        "SELECT COUNT(*) FROM {} WHERE deleted = 0",
+        app_state.db_info.table_name
+    );
+    let count: i64 = conn.query_row(&sql, [], |row| row.get(0))?;
+    Ok(count as usize)
+}
+
 pub fn get_max_id_terms(State(app_state): State<Arc<AppState>>) -> Result<i32, String> {
     debug!("Get max id terms: {:?}", app_state.db_info.table_name);
     let hid = get_max_term_id(State(app_state));
@@ -639,7 +858,15 @@ pub fn get_term_by_id(
 ) -> Result<Option<TermsList>, rusqlite::Error> {
     debug!("Get term by id: {:?}", term_id);
     let conn = connect_db(State(app_state.clone()))?;
+    read_term(&conn, &app_state.db_info.table_name, term_id)
+}
 
+/// Like `get_term_by_id`, on an open connection or transaction.
+pub fn read_term(
+    conn: &Connection,
+    table: &str,
+    term_id: i32,
+) -> Result<Option<TermsList>, rusqlite::Error> {
     let sql = format!(
         "SELECT 
             term_id,
@@ -658,38 +885,21 @@ pub fn get_term_by_id(
             remark, 
             url, 
             context, 
-            definition 
-        FROM {} 
-        WHERE term_id = ?",
-        app_state.db_info.table_name
+            definition,
+            status,
+            part_of_speech,
+            gender,
+            number,
+            inflection
+        FROM {table}
+        WHERE term_id = ? AND deleted = 0"
     );
 
     let mut stmt = conn.prepare(&sql)?;
     let mut rows = stmt.query([term_id])?;
 
     if let Some(row) = rows.next()? {
-        let term = TermsList {
-            term_id: row.get(0)?,
-            term_set_id: row.get(1)?,
-            term_language_set: TermLanguageSet {
-                term: row.get(2)?,
-                language: row.get(3)?,
-                term_type: row.get(4)?,
-                creator_id: row.get(5)?,
-                creation_timestamp: row.get(6)?,
-                updater_id: row.get(7)?,
-                update_timestamp: row.get(8)?,
-                subject: row.get(9)?,
-                source: row.get(10)?,
-                user: row.get(11)?,
-                attributes: row.get(12)?,
-                remark: row.get(13)?,
-                url: row.get(14)?,
-                context: row.get(15)?,
-                definition: row.get(16)?,
-            },
-        };
-        Ok(Some(term))
+        Ok(Some(terms_list_from_row(row)?))
     } else {
         Ok(None)
     }
@@ -704,7 +914,7 @@ pub fn get_term_set_id(
     let conn = connect_db(State(app_state.clone()))?;
 
     let sql = format!(
-        "SELECT term_set_id FROM {} WHERE term = ? AND language = ?",
+        "SELECT term_set_id FROM {} WHERE term = ? AND language = ? AND deleted = 0",
         app_state.db_info.table_name
     );
 
@@ -724,7 +934,7 @@ pub fn get_term_set_id_by_term_id(
     let conn = connect_db(State(app_state.clone()))?;
 
     let sql = format!(
-        "SELECT term_set_id FROM {} WHERE term_id = ? LIMIT 1",
+        "SELECT term_set_id FROM {} WHERE term_id = ? AND deleted = 0 LIMIT 1",
         app_state.db_info.table_name
     );
 
@@ -740,6 +950,16 @@ pub fn search_terms(
     term: &str,
     language: &str,
 ) -> Result<Vec<TermsList>, rusqlite::Error> {
+    search_terms_with_options(State(app_state), term, language, &SearchOptions::default())
+}
+
+pub fn search_terms_with_options(
+    State(app_state): State<Arc<AppState>>,
+    term: &str,
+    language: &str,
+    options: &SearchOptions,
+) -> Result<Vec<TermsList>, rusqlite::Error> {
+    let folded_term = fold_text(term, options);
     let cache_guard = app_state.terms_cache.lock().unwrap();
 
     if let Some(cached_terms) = &*cache_guard {
@@ -747,25 +967,7 @@ pub fn search_terms(
 
         let matches: Vec<TermsList> = cached_terms
             .iter()
-            .filter(|term_entry| {
-                let term_str = term_entry.term_language_set.term.as_deref().unwrap_or("");
-                if !term_str.contains(term) {
-                    return false;
-                }
-
-                if !language.is_empty() {
-                    let lang_str = term_entry
-                        .term_language_set
-                        .language
-                        .as_deref()
-                        .unwrap_or("");
-                    if lang_str != language {
-                        return false;
-                    }
-                }
-
-                true
-            })
+            .filter(|term_entry| term_matches(term_entry, &folded_term, language, options))
             .cloned()
             .collect();
 
@@ -795,38 +997,107 @@ pub fn search_terms(
             remark, 
             url, 
             context, 
-            definition 
+            definition,
+            status,
+            part_of_speech,
+            gender,
+            number,
+            inflection
         FROM {} 
-        WHERE term LIKE ? AND (language = ? OR ? = '')",
+        WHERE instr(ts_fold(COALESCE(term, ''), ?2), ?1) > 0 AND (language = ?3 OR ?3 = '')
+            AND deleted = 0",
         app_state.db_info.table_name
     );
 
-    let term_pattern = format!("%{}%", term);
     let mut stmt = conn.prepare(&sql)?;
 
-    let terms_iter = stmt.query_map([&term_pattern, language, language], |row| {
-        Ok(TermsList {
-            term_id: row.get(0)?,
-            term_set_id: row.get(1)?,
-            term_language_set: TermLanguageSet {
-                term: row.get(2)?,
-                language: row.get(3)?,
-                term_type: row.get(4)?,
-                creator_id: row.get(5)?,
-                creation_timestamp: row.get(6)?,
-                updater_id: row.get(7)?,
-                update_timestamp: row.get(8)?,
-                subject: row.get(9)?,
-                source: row.get(10)?,
-                user: row.get(11)?,
-                attributes: row.get(12)?,
-                remark: row.get(13)?,
-                url: row.get(14)?,
-                context: row.get(15)?,
-                definition: row.get(16)?,
-            },
-        })
-    })?;
+    let terms_iter = stmt.query_map(
+        params![folded_term, options.flags(), language],
+        terms_list_from_row,
+    )?;
+
+    terms_iter.collect()
+}
+
+pub fn search_terms_ranked(
+    State(app_state): State<Arc<AppState>>,
+    term: &str,
+    language: &str,
+    options: &SearchOptions,
+    mode: SearchMode,
+    min_score: f64,
+) -> Result<Vec<SearchHit>, rusqlite::Error> {
+    let candidate_term = match mode {
+        SearchMode::Substring => term,
+        SearchMode::Fuzzy => "",
+        SearchMode::Fulltext => return search_fulltext(State(app_state), term, language),
+    };
+    let candidates =
+        search_terms_with_options(State(app_state), candidate_term, language, options)?;
+    Ok(rank_hits(&candidates, term, options, mode, min_score))
+}
+
+pub fn search_terms_by_query(
+    State(app_state): State<Arc<AppState>>,
+    query: &Query,
+    language: &str,
+    options: &SearchOptions,
+) -> Result<Vec<TermsList>, rusqlite::Error> {
+    debug!("Search terms by query: {}, language: {:?}", query, language);
+    let conn = connect_db(State(app_state.clone()))?;
+    query_terms(
+        &conn,
+        &app_state.db_info.table_name,
+        query,
+        language,
+        options,
+    )
+}
+
+/// Like `search_terms_by_query`, on an open connection or transaction.
+pub fn query_terms(
+    conn: &Connection,
+    table: &str,
+    query: &Query,
+    language: &str,
+    options: &SearchOptions,
+) -> Result<Vec<TermsList>, rusqlite::Error> {
+    let (condition, mut query_params) = query.to_sql(options);
+    query_params.push(SqlValue::Text(language.to_string()));
+    query_params.push(SqlValue::Text(language.to_string()));
+
+    let sql = format!(
+        "SELECT
+            term_id,
+            term_set_id,
+            term,
+            language,
+            term_type,
+            creator_id,
+            creation_timestamp,
+            updater_id,
+            update_timestamp,
+            subject,
+            source,
+            user,
+            attributes,
+            remark,
+            url,
+            context,
+            definition,
+            status,
+            part_of_speech,
+            gender,
+            number,
+            inflection
+        FROM {table}
+        WHERE deleted = 0 AND {condition} AND (language = ? OR ? = '')
+        ORDER BY term_set_id, term_id"
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+
+    let terms_iter = stmt.query_map(params_from_iter(query_params), terms_list_from_row)?;
 
     terms_iter.collect()
 }
@@ -856,97 +1127,271 @@ pub fn search_terms_by_term_set_id(
             remark, 
             url, 
             context, 
-            definition 
+            definition,
+            status,
+            part_of_speech,
+            gender,
+            number,
+            inflection
         FROM {} 
-        WHERE term_set_id = ?",
+        WHERE term_set_id = ? AND deleted = 0",
         app_state.db_info.table_name
     );
 
     let mut stmt = conn.prepare(&sql)?;
 
-    let terms_iter = stmt.query_map(params![term_set_id], |row| {
-        Ok(TermsList {
-            term_id: row.get(0)?,
-            term_set_id: row.get(1)?,
-            term_language_set: TermLanguageSet {
-                term: row.get(2)?,
-                language: row.get(3)?,
-                term_type: row.get(4)?,
-                creator_id: row.get(5)?,
-                creation_timestamp: row.get(6)?,
-                updater_id: row.get(7)?,
-                update_timestamp: row.get(8)?,
-                subject: row.get(9)?,
-                source: row.get(10)?,
-                user: row.get(11)?,
-                attributes: row.get(12)?,
-                remark: row.get(13)?,
-                url: row.get(14)?,
-                context: row.get(15)?,
-                definition: row.get(16)?,
-            },
-        })
-    })?;
+    let terms_iter = stmt.query_map(params![term_set_id], terms_list_from_row)?;
 
     terms_iter.collect()
 }
 
+/// The live terms whose language is exactly one of `languages`. A missing
+/// language counts as the empty string.
+pub fn search_terms_by_languages(
+    State(app_state): State<Arc<AppState>>,
+    languages: &[String],
+) -> Result<Vec<TermsList>, rusqlite::Error> {
+    debug!("Search terms by languages: {:?}", languages);
+    if languages.is_empty() {
+        return Ok(Vec::new());
+    }
+    let conn = connect_db(State(app_state.clone()))?;
+    let placeholders = vec!["?"; languages.len()].join(", ");
+
+    let sql = format!(
+        "SELECT
+            term_id,
+            term_set_id,
+            term,
+            language,
+            term_type,
+            creator_id,
+            creation_timestamp,
+            updater_id,
+            update_timestamp,
+            subject,
+            source,
+            user,
+            attributes,
+            remark,
+            url,
+            context,
+            definition,
+            status,
+            part_of_speech,
+            gender,
+            number,
+            inflection
+        FROM {}
+        WHERE deleted = 0 AND COALESCE(language, '') IN ({placeholders})
+        ORDER BY term_set_id, term_id",
+        app_state.db_info.table_name
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+
+    let terms_iter = stmt.query_map(params_from_iter(languages), terms_list_from_row)?;
+
+    terms_iter.collect()
+}
+
+/// Sets the fields of a term that have a value, leaving the others as they
+/// are. Fails like `patch_term` for a missing term or an empty term or
+/// language.
 pub fn update_term(
     State(app_state): State<Arc<AppState>>,
     term_id_to_update: i32,
     termset_update: &TermLanguageSet,
-) -> Result<(), rusqlite::Error> {
-    debug!(
-        "Update term: term_id_to_update: {:?}, termset_update: {:?}",
-        term_id_to_update, termset_update
-    );
+) -> Result<(), UpdateError> {
+    patch_term(
+        State(app_state),
+        term_id_to_update,
+        &TermPatch::from(termset_update),
+        None,
+    )
+}
+
+/// The version of a term, which changes every time the term does.
+pub fn get_term_version(
+    State(app_state): State<Arc<AppState>>,
+    term_id: i32,
+) -> Result<Option<i64>, rusqlite::Error> {
     let conn = connect_db(State(app_state.clone()))?;
+    conn.query_row(
+        &format!(
+            "SELECT version FROM {} WHERE term_id = ?1 AND deleted = 0",
+            app_state.db_info.table_name
+        ),
+        params![term_id],
+        |row| row.get(0),
+    )
+    .optional()
+}
 
-    let query = format!(
-        "
-        UPDATE {} 
-        SET 
-            term = COALESCE(?2, term),
-            language = COALESCE(?3, language),
-            term_type = COALESCE(?4, term_type),
-            creator_id = COALESCE(?5, creator_id),
-            creation_timestamp = COALESCE(?6, creation_timestamp),
-            updater_id = COALESCE(?7, updater_id),
-            update_timestamp = COALESCE(?8, update_timestamp),
-            subject = COALESCE(?9, subject),
-            source = COALESCE(?10, source),
-            user = COALESCE(?11, user),
-            attributes = COALESCE(?12, attributes),
-            remark = COALESCE(?13, remark),
-            url = COALESCE(?14, url),
-            context = COALESCE(?15, context),
-            definition = COALESCE(?16, definition)
-        WHERE term_id = ?1
-        ",
-        app_state.db_info.table_name
-    );
+fn text_update(update: &FieldUpdate<String>) -> FieldUpdate<SqlValue> {
+    match update {
+        FieldUpdate::Set(value) if !value.trim().is_empty() => {
+            FieldUpdate::Set(SqlValue::Text(value.clone()))
+        }
+        FieldUpdate::Set(_) => FieldUpdate::Clear,
+        other => other.as_ref().map(|_| SqlValue::Null),
+    }
+}
 
-    conn.execute(
-        &query,
-        params![
-            term_id_to_update,
-            termset_update.term.as_deref(),
-            termset_update.language.as_deref(),
-            termset_update.term_type.as_deref(),
-            termset_update.creator_id.as_deref(),
-            termset_update.creation_timestamp,
-            termset_update.updater_id.as_deref(),
-            termset_update.update_timestamp,
-            termset_update.subject.as_deref(),
-            termset_update.source.as_deref(),
-            termset_update.user.as_deref(),
-            termset_update.attributes.as_deref(),
-            termset_update.remark.as_deref(),
-            termset_update.url.as_deref(),
-            termset_update.context.as_deref(),
-            termset_update.definition.as_deref(),
-        ],
-    )?;
+fn list_update(update: FieldUpdate<&'static str>) -> FieldUpdate<SqlValue> {
+    update.map(|value| SqlValue::Text(value.to_string()))
+}
 
-    Ok(())
+/// Applies a partial update to a term: unchanged fields are kept, cleared
+/// fields are set to NULL and set fields are overwritten. Setting the subject
+/// links it to the taxonomy like an insert does. With a `version`, the update
+/// is rejected when the term has changed since that version. The term and its
+/// language cannot be cleared. A patch that sets `updater_id` is recorded in
+/// the history as made by that editor.
+pub fn patch_term(
+    State(app_state): State<Arc<AppState>>,
+    term_id: i32,
+    patch: &TermPatch,
+    version: Option<i64>,
+) -> Result<(), UpdateError> {
+    patch_term_with_picklists(State(app_state), term_id, patch, version, &[])
+}
+
+/// Runs `patch_term` and adds the new picklist values the patch uses, as
+/// returned by `apply_picklists_to_patch`, in the same transaction: they are
+/// only added when the update succeeds.
+pub fn patch_term_with_picklists(
+    State(app_state): State<Arc<AppState>>,
+    term_id: i32,
+    patch: &TermPatch,
+    version: Option<i64>,
+    new_values: &[(String, String)],
+) -> Result<(), UpdateError> {
+    debug!("Patch term: term_id: {:?}, patch: {:?}", term_id, patch);
+    patch.check_required()?;
+    let mut conn = connect_db(State(app_state.clone()))?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let last_revision = last_revision_id(&tx)?;
+    if apply_patch(&tx, &app_state.db_info.table_name, term_id, patch, version)? {
+        for (field, value) in new_values {
+            insert_picklist_value(&tx, field, value)?;
+        }
+        if let FieldUpdate::Set(editor) = &patch.updater_id {
+            if !editor.trim().is_empty() {
+                sign_revisions_since(&tx, last_revision, editor)?;
+            }
+        }
+        tx.commit()?;
+        return Ok(());
+    }
+    drop(tx);
+
+    let current = get_term_by_id(State(app_state.clone()), term_id)?;
+    match (current, get_term_version(State(app_state), term_id)?) {
+        (Some(term), Some(version)) => Err(UpdateError::Conflict {
+            version,
+            term: Box::new(term),
+        }),
+        _ => Err(UpdateError::NotFound(term_id)),
+    }
+}
+
+/// Runs `patch_term` on a connection the caller owns, so several terms can be
+/// updated in one transaction. Returns `false` when the term does not exist,
+/// is deleted or is not at `version`.
+pub fn apply_patch(
+    conn: &Connection,
+    table: &str,
+    term_id: i32,
+    patch: &TermPatch,
+    version: Option<i64>,
+) -> Result<bool, rusqlite::Error> {
+    let (subject, subject_id) = match text_update(&patch.subject) {
+        FieldUpdate::Set(SqlValue::Text(subject)) => {
+            match resolve_subject(conn, Some(&subject))? {
+                Some((subject_id, path)) => (
+                    FieldUpdate::Set(SqlValue::Text(path)),
+                    FieldUpdate::Set(SqlValue::Integer(subject_id)),
+                ),
+                None => (
+                    FieldUpdate::Set(SqlValue::Text(subject)),
+                    FieldUpdate::Clear,
+                ),
+            }
+        }
+        FieldUpdate::Unchanged => (FieldUpdate::Unchanged, FieldUpdate::Unchanged),
+        _ => (FieldUpdate::Clear, FieldUpdate::Clear),
+    };
+
+    let columns = [
+        ("term", text_update(&patch.term)),
+        ("language", text_update(&patch.language)),
+        ("term_type", text_update(&patch.term_type)),
+        ("creator_id", text_update(&patch.creator_id)),
+        (
+            "creation_timestamp",
+            patch.creation_timestamp.clone().map(SqlValue::Integer),
+        ),
+        ("updater_id", text_update(&patch.updater_id)),
+        (
+            "update_timestamp",
+            patch.update_timestamp.clone().map(SqlValue::Integer),
+        ),
+        ("subject", subject),
+        ("subject_id", subject_id),
+        ("source", text_update(&patch.source)),
+        ("user", text_update(&patch.user)),
+        ("attributes", text_update(&patch.attributes)),
+        ("remark", text_update(&patch.remark)),
+        ("url", text_update(&patch.url)),
+        ("context", text_update(&patch.context)),
+        ("definition", text_update(&patch.definition)),
+        (
+            "status",
+            list_update(patch.status.as_ref().map(|status| status.as_str())),
+        ),
+        (
+            "part_of_speech",
+            list_update(patch.part_of_speech.as_ref().map(|pos| pos.as_str())),
+        ),
+        (
+            "gender",
+            list_update(patch.gender.as_ref().map(|gender| gender.as_str())),
+        ),
+        (
+            "number",
+            list_update(patch.number.as_ref().map(|number| number.as_str())),
+        ),
+        ("inflection", text_update(&patch.inflection)),
+    ];
+
+    let mut assignments = Vec::new();
+    let mut values = vec![
+        SqlValue::Integer(term_id as i64),
+        version.map_or(SqlValue::Null, SqlValue::Integer),
+    ];
+    for (column, update) in columns {
+        let value = match update {
+            FieldUpdate::Unchanged => continue,
+            FieldUpdate::Clear => SqlValue::Null,
+            FieldUpdate::Set(value) => value,
+        };
+        values.push(value);
+        assignments.push(format!("{column} = ?{}", values.len()));
+    }
+    let condition = "term_id = ?1 AND deleted = 0 AND (?2 IS NULL OR version = ?2)";
+    if assignments.is_empty() {
+        // Nothing to write, but the term still has to be there at the version.
+        let query = format!("SELECT 1 FROM {table} WHERE {condition}");
+        let found = conn
+            .query_row(&query, params_from_iter(values), |_| Ok(()))
+            .optional()?;
+        return Ok(found.is_some());
+    }
+
+    let query = format!(
+        "UPDATE {table} SET {} WHERE {condition}",
+        assignments.join(", ")
+    );
+    Ok(conn.execute(&query, params_from_iter(values))? > 0)
 }
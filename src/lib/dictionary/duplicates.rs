@@ -0,0 +1,236 @@
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use strsim::normalized_damerau_levenshtein;
+
+use crate::dictionary::database::{get_all_terms, AppState, TermsList};
+use crate::dictionary::search::{fold_text, SearchOptions};
+
+/// How similar two spellings have to be to count as near-duplicates.
+pub const DEFAULT_MIN_SIMILARITY: f64 = 0.85;
+
+/// How alike the terms of a group are, from the closest to the loosest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateKind {
+    /// The same spelling.
+    Exact,
+    /// Spellings that only differ in case, character width or whitespace.
+    Variant,
+    /// Spellings at least as similar as the minimum similarity.
+    Fuzzy,
+}
+
+impl DuplicateKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DuplicateKind::Exact => "Exact duplicates",
+            DuplicateKind::Variant => "Case or whitespace variants",
+            DuplicateKind::Fuzzy => "Near-duplicates",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct DuplicateOptions {
+    /// Only look at this language, or at all languages when empty.
+    pub language: String,
+    /// Whether to look for near-duplicates as well. This compares the
+    /// spellings of a language pairwise, so it is off by default.
+    pub fuzzy: bool,
+    pub min_similarity: f64,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        DuplicateOptions {
+            language: String::new(),
+            fuzzy: false,
+            min_similarity: DEFAULT_MIN_SIMILARITY,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateTermSet {
+    pub term_set_id: i32,
+    pub terms: Vec<TermsList>,
+}
+
+/// Terms in one language that look like the same term, spread over more than
+/// one term set, together with all terms of those term sets.
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub language: String,
+    pub kind: DuplicateKind,
+    pub term_ids: Vec<i32>,
+    pub term_sets: Vec<DuplicateTermSet>,
+}
+
+impl DuplicateGroup {
+    pub fn contains(&self, term_id: &i32) -> bool {
+        self.term_ids.contains(term_id)
+    }
+
+    /// Whether merging discards `term` unless the user says otherwise: the
+    /// duplicates outside the first term set, which is kept by default.
+    pub fn discarded_by_default(&self, term: &TermsList) -> bool {
+        self.contains(&term.term_id) && term.term_set_id != self.term_sets[0].term_set_id
+    }
+}
+
+/// Case, width and whitespace insensitive form of a term.
+fn variant_key(term: &str) -> String {
+    fold_text(term, &SearchOptions::default())
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    let mut index = index;
+    while parents[index] != root {
+        let next = parents[index];
+        parents[index] = root;
+        index = next;
+    }
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    if a != b {
+        parents[a.max(b)] = a.min(b);
+    }
+}
+
+/// Groups the distinct spellings of one language: spellings with the same
+/// variant key always end up together, similar keys only when fuzzy.
+fn group_keys(keys: &[String], options: &DuplicateOptions) -> Vec<usize> {
+    let mut parents: Vec<usize> = (0..keys.len()).collect();
+    if options.fuzzy {
+        let min_similarity = options.min_similarity.clamp(0.0, 1.0);
+        let mut by_length: Vec<(usize, usize)> = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (key.chars().count(), index))
+            .collect();
+        by_length.sort_unstable();
+        // The edit distance is at least the difference in length, so only
+        // keys of about the same length can be similar enough.
+        for (i, &(length, a)) in by_length.iter().enumerate() {
+            for &(other_length, b) in &by_length[i + 1..] {
+                let max_edits = (1.0 - min_similarity) * other_length as f64;
+                if (other_length - length) as f64 > max_edits {
+                    break;
+                }
+                if normalized_damerau_levenshtein(&keys[a], &keys[b]) >= min_similarity {
+                    union(&mut parents, a, b);
+                }
+            }
+        }
+    }
+    (0..keys.len())
+        .map(|index| find(&mut parents, index))
+        .collect()
+}
+
+/// Finds groups of duplicate terms per language. Only groups spanning
+/// several term sets are returned, since terms in one term set are synonyms.
+pub fn find_duplicates(
+    State(app_state): State<Arc<AppState>>,
+    options: &DuplicateOptions,
+) -> Result<Vec<DuplicateGroup>, rusqlite::Error> {
+    let all_terms = get_all_terms(State(app_state))?;
+    let language_filter = options.language.trim().to_lowercase();
+
+    let mut term_sets: HashMap<i32, Vec<TermsList>> = HashMap::new();
+    let mut languages: BTreeMap<String, Vec<&TermsList>> = BTreeMap::new();
+    for entry in &all_terms {
+        term_sets
+            .entry(entry.term_set_id)
+            .or_default()
+            .push(entry.clone());
+        let set = &entry.term_language_set;
+        let language = set.language.as_deref().unwrap_or("").trim().to_lowercase();
+        let has_term = set
+            .term
+            .as_deref()
+            .is_some_and(|term| !term.trim().is_empty());
+        if has_term && (language_filter.is_empty() || language == language_filter) {
+            languages.entry(language).or_default().push(entry);
+        }
+    }
+    for terms in term_sets.values_mut() {
+        terms.sort_by_key(|entry| entry.term_id);
+    }
+
+    let mut groups = Vec::new();
+    for terms in languages.into_values() {
+        let mut keys: Vec<String> = Vec::new();
+        let mut key_indexes: HashMap<String, usize> = HashMap::new();
+        let term_keys: Vec<usize> = terms
+            .iter()
+            .map(|entry| {
+                let key = variant_key(entry.term_language_set.term.as_deref().unwrap_or(""));
+                *key_indexes.entry(key.clone()).or_insert_with(|| {
+                    keys.push(key);
+                    keys.len() - 1
+                })
+            })
+            .collect();
+        let roots = group_keys(&keys, options);
+        let mut language_groups = Vec::new();
+
+        let mut members: BTreeMap<usize, Vec<&TermsList>> = BTreeMap::new();
+        for (entry, key) in terms.iter().zip(&term_keys) {
+            members.entry(roots[*key]).or_default().push(entry);
+        }
+        for mut members in members.into_values() {
+            let mut term_set_ids: Vec<i32> =
+                members.iter().map(|entry| entry.term_set_id).collect();
+            term_set_ids.sort_unstable();
+            term_set_ids.dedup();
+            if term_set_ids.len() < 2 {
+                continue;
+            }
+            members.sort_by_key(|entry| (entry.term_set_id, entry.term_id));
+
+            let spelling = |entry: &TermsList| entry.term_language_set.term.clone();
+            let kind = if members
+                .iter()
+                .all(|entry| spelling(entry) == spelling(members[0]))
+            {
+                DuplicateKind::Exact
+            } else if members.iter().all(|entry| {
+                variant_key(entry.term_or_default()) == variant_key(members[0].term_or_default())
+            }) {
+                DuplicateKind::Variant
+            } else {
+                DuplicateKind::Fuzzy
+            };
+
+            language_groups.push(DuplicateGroup {
+                language: members[0].language_or_default().to_string(),
+                kind,
+                term_ids: members.iter().map(|entry| entry.term_id).collect(),
+                term_sets: term_set_ids
+                    .into_iter()
+                    .map(|term_set_id| DuplicateTermSet {
+                        term_set_id,
+                        terms: term_sets.get(&term_set_id).cloned().unwrap_or_default(),
+                    })
+                    .collect(),
+            });
+        }
+        language_groups.sort_by_key(|group| (group.kind, group.term_ids[0]));
+        groups.extend(language_groups);
+    }
+    Ok(groups)
+}
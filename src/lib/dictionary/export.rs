@@ -0,0 +1,218 @@
+use chrono::DateTime;
+use elementtree::Element;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::dictionary::database::TermsList;
+use crate::dictionary::relations::{RelationType, TermSetRelation};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Tbx,
+    /// SKOS in Turtle.
+    #[serde(alias = "ttl")]
+    Turtle,
+    /// SKOS in RDF/XML.
+    #[serde(alias = "rdf")]
+    RdfXml,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Tbx => "application/x-tbx+xml",
+            ExportFormat::Turtle => "text/turtle",
+            ExportFormat::RdfXml => "application/rdf+xml",
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "terms.json",
+            ExportFormat::Tbx => "terms.tbx",
+            ExportFormat::Turtle => "terms.ttl",
+            ExportFormat::RdfXml => "terms.rdf",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedRelation {
+    pub relation: RelationType,
+    pub term_set_id: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExportedTermSet {
+    pub term_set_id: i32,
+    pub terms: Vec<TermsList>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relations: Vec<ExportedRelation>,
+}
+
+/// Groups terms into their term sets, with the relations starting from each
+/// term set. Term sets appear in the order of their first term, so a sorted
+/// term list gives sorted term sets.
+pub fn group_term_sets(terms: &[TermsList], relations: &[TermSetRelation]) -> Vec<ExportedTermSet> {
+    let mut term_sets: Vec<ExportedTermSet> = Vec::new();
+    let mut positions: HashMap<i32, usize> = HashMap::new();
+
+    for entry in terms {
+        match positions.get(&entry.term_set_id) {
+            Some(&position) => term_sets[position].terms.push(entry.clone()),
+            None => {
+                positions.insert(entry.term_set_id, term_sets.len());
+                term_sets.push(ExportedTermSet {
+                    term_set_id: entry.term_set_id,
+                    terms: vec![entry.clone()],
+                    relations: Vec::new(),
+                });
+            }
+        }
+    }
+
+    for relation in relations {
+        if let Some(&position) = positions.get(&relation.source_set_id) {
+            term_sets[position].relations.push(ExportedRelation {
+                relation: relation.relation,
+                term_set_id: relation.target_set_id,
+            });
+        }
+    }
+
+    term_sets
+}
+
+pub fn export_json(
+    terms: &[TermsList],
+    relations: &[TermSetRelation],
+) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&group_term_sets(terms, relations))
+}
+
+fn format_tbx_date(timestamp: i64) -> Option<String> {
+    DateTime::from_timestamp(timestamp, 0).map(|dt| dt.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+fn append_text(parent: &mut Element, tag: &str, note_type: &str, value: &Option<String>) {
+    if let Some(value) = value.as_deref().filter(|value| !value.is_empty()) {
+        parent
+            .append_new_child(tag)
+            .set_attr("type", note_type)
+            .set_text(value);
+    }
+}
+
+fn append_date(parent: &mut Element, date_type: &str, timestamp: Option<i64>) {
+    if let Some(date) = timestamp.and_then(format_tbx_date) {
+        parent
+            .append_new_child("date")
+            .set_attr("type", date_type)
+            .set_text(date);
+    }
+}
+
+/// Writes terms as a MARTIF/TBX document in the same layout the dictionary
+/// import reads, so an export can be imported again. Relations are written
+/// as concept-level `descrip`s, or a `ref` for see-also, pointing to the
+/// `termEntry` of the related term set.
+pub fn export_tbx(
+    terms: &[TermsList],
+    relations: &[TermSetRelation],
+) -> Result<String, elementtree::Error> {
+    let mut root = Element::new("martif");
+    root.set_attr("type", "TBX").set_attr("lang", "en");
+
+    root.append_new_child("martifHeader")
+        .append_new_child("fileDesc")
+        .append_new_child("sourceDesc")
+        .append_new_child("p")
+        .set_text("term-squire export");
+
+    let body = root.append_new_child("text").append_new_child("body");
+
+    let term_sets = group_term_sets(terms, relations);
+    let first_terms: HashMap<i32, String> = term_sets
+        .iter()
+        .map(|term_set| {
+            let term = term_set.terms[0].term_language_set.term.clone();
+            (term_set.term_set_id, term.unwrap_or_default())
+        })
+        .collect();
+
+    for term_set in &term_sets {
+        let term_entry = body.append_new_child("termEntry");
+        term_entry.set_attr("id", term_set.term_set_id.to_string());
+
+        for relation in &term_set.relations {
+            let tag = match relation.relation {
+                RelationType::SeeAlso => "ref",
+                _ => "descrip",
+            };
+            let element = term_entry
+                .append_new_child(tag)
+                .set_attr("type", relation.relation.tbx_type())
+                .set_attr("target", relation.term_set_id.to_string());
+            if let Some(term) = first_terms.get(&relation.term_set_id) {
+                element.set_text(term.clone());
+            }
+        }
+
+        for entry in &term_set.terms {
+            let set = &entry.term_language_set;
+            let lang_set = term_entry.append_new_child("langSet");
+            lang_set.set_attr("lang", set.language.clone().unwrap_or_default());
+
+            let term_group = lang_set
+                .append_new_child("ntig")
+                .append_new_child("termGrp");
+            term_group
+                .append_new_child("term")
+                .set_text(set.term.clone().unwrap_or_default());
+            append_text(term_group, "termNote", "termType", &set.term_type);
+            append_text(term_group, "termNote", "TS_CreateId", &set.creator_id);
+            append_date(term_group, "origination", set.creation_timestamp);
+            append_text(term_group, "termNote", "TS_UpdateId", &set.updater_id);
+            append_date(term_group, "modification", set.update_timestamp);
+            append_text(term_group, "termNote", "TS_Subject", &set.subject);
+            append_text(term_group, "termNote", "TS_Source", &set.source);
+            append_text(term_group, "termNote", "TS_User1", &set.user);
+            append_text(term_group, "termNote", "TS_Attributes", &set.attributes);
+            append_text(term_group, "termNote", "TS_Remark", &set.remark);
+            append_text(term_group, "termNote", "TS_Hyperlink", &set.url);
+            append_text(
+                term_group,
+                "termNote",
+                "normativeAuthorization",
+                &set.status.map(|status| format!("{}Term", status.as_str())),
+            );
+            append_text(
+                term_group,
+                "termNote",
+                "partOfSpeech",
+                &set.part_of_speech.map(|pos| pos.as_str().to_string()),
+            );
+            append_text(
+                term_group,
+                "termNote",
+                "grammaticalGender",
+                &set.gender.map(|gender| gender.as_str().to_string()),
+            );
+            append_text(
+                term_group,
+                "termNote",
+                "grammaticalNumber",
+                &set.number.map(|number| number.as_str().to_string()),
+            );
+            append_text(term_group, "termNote", "inflection", &set.inflection);
+            append_text(term_group, "descrip", "context", &set.context);
+            append_text(term_group, "descrip", "definition", &set.definition);
+        }
+    }
+
+    root.to_string()
+}
@@ -0,0 +1,182 @@
+use axum::extract::State;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::dictionary::database::{
+    add_column_if_missing, connect_db, extract_and_insert_unique_values, AppState, TermsList,
+    UNIQUE_VALUE_COLUMNS,
+};
+
+pub const DEFAULT_FACET_LIMIT: usize = 25;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Facets {
+    pub language: Vec<FacetCount>,
+    pub subject: Vec<FacetCount>,
+    pub term_type: Vec<FacetCount>,
+    pub source: Vec<FacetCount>,
+    pub creator_id: Vec<FacetCount>,
+}
+
+fn unique_values_table(column: &str) -> &'static str {
+    UNIQUE_VALUE_COLUMNS
+        .iter()
+        .find(|(_, candidate)| *candidate == column)
+        .map(|(table, _)| *table)
+        .unwrap()
+}
+
+/// Adds the `term_count` column to unique value tables created before facets
+/// existed and (re)creates the triggers that keep the counts in step with the
+/// terms table. Counts are rebuilt when the triggers weren't there before.
+pub fn create_facet_index(State(app_state): State<Arc<AppState>>) -> Result<(), rusqlite::Error> {
+    let table = &app_state.db_info.table_name;
+    debug!("Create facet triggers on: {}", table);
+    let conn = connect_db(State(app_state.clone()))?;
+
+    let had_triggers: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'trigger' AND name = ?1)",
+        params![format!("{table}_facets_after_insert")],
+        |row| row.get(0),
+    )?;
+
+    for (unique_table, _) in UNIQUE_VALUE_COLUMNS {
+        add_column_if_missing(
+            &conn,
+            unique_table,
+            "term_count",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+    }
+
+    let mut on_insert = String::new();
+    let mut on_delete = String::new();
+    for (unique_table, column) in UNIQUE_VALUE_COLUMNS {
+        on_insert.push_str(&format!(
+            "INSERT INTO {unique_table} ({column}, term_count) SELECT new.{column}, 1
+                WHERE new.{column} IS NOT NULL AND new.deleted = 0
+                ON CONFLICT({column}) DO UPDATE SET term_count = term_count + 1;\n"
+        ));
+        on_delete.push_str(&format!(
+            "UPDATE {unique_table} SET term_count = term_count - 1
+                WHERE {column} = old.{column} AND old.deleted = 0;
+            DELETE FROM {unique_table} WHERE {column} = old.{column} AND term_count <= 0;\n"
+        ));
+    }
+
+    conn.execute_batch(&format!(
+        "
+        BEGIN;
+
+        DROP TRIGGER IF EXISTS {table}_facets_after_insert;
+        DROP TRIGGER IF EXISTS {table}_facets_after_delete;
+        DROP TRIGGER IF EXISTS {table}_facets_after_update;
+
+        CREATE TRIGGER {table}_facets_after_insert AFTER INSERT ON {table} BEGIN
+            {on_insert}
+        END;
+
+        CREATE TRIGGER {table}_facets_after_delete AFTER DELETE ON {table} BEGIN
+            {on_delete}
+        END;
+
+        CREATE TRIGGER {table}_facets_after_update AFTER UPDATE ON {table} BEGIN
+            {on_delete}
+            {on_insert}
+        END;
+
+        COMMIT;
+        "
+    ))?;
+
+    if !had_triggers {
+        debug!("Rebuilding facet counts for {}", table);
+        extract_and_insert_unique_values(State(app_state))?;
+    }
+
+    Ok(())
+}
+
+fn facet_from_table(
+    conn: &rusqlite::Connection,
+    column: &str,
+    limit: usize,
+) -> Result<Vec<FacetCount>, rusqlite::Error> {
+    let sql = format!(
+        "SELECT {column}, term_count FROM {}
+        WHERE {column} IS NOT NULL AND term_count > 0
+        ORDER BY term_count DESC, {column}
+        LIMIT ?1",
+        unique_values_table(column)
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let facet_iter = stmt.query_map(params![limit as i64], |row| {
+        Ok(FacetCount {
+            value: row.get(0)?,
+            count: row.get(1)?,
+        })
+    })?;
+    facet_iter.collect()
+}
+
+/// Facet counts over the whole dictionary, read from the unique value tables.
+pub fn get_facets(
+    State(app_state): State<Arc<AppState>>,
+    limit: usize,
+) -> Result<Facets, rusqlite::Error> {
+    let conn = connect_db(State(app_state))?;
+
+    Ok(Facets {
+        language: facet_from_table(&conn, "language", limit)?,
+        subject: facet_from_table(&conn, "subject", limit)?,
+        term_type: facet_from_table(&conn, "term_type", limit)?,
+        source: facet_from_table(&conn, "source", limit)?,
+        creator_id: facet_from_table(&conn, "creator_id", limit)?,
+    })
+}
+
+fn count_values<'a>(
+    values: impl Iterator<Item = Option<&'a str>>,
+    limit: usize,
+) -> Vec<FacetCount> {
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for value in values.flatten() {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+
+    let mut facet: Vec<FacetCount> = counts
+        .into_iter()
+        .map(|(value, count)| FacetCount {
+            value: value.to_string(),
+            count,
+        })
+        .collect();
+    facet.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    facet.truncate(limit);
+    facet
+}
+
+/// Facet counts over a search result.
+pub fn facets_from_terms<'a>(
+    terms: impl Iterator<Item = &'a TermsList> + Clone,
+    limit: usize,
+) -> Facets {
+    let sets = terms.map(|entry| &entry.term_language_set);
+
+    Facets {
+        language: count_values(sets.clone().map(|set| set.language.as_deref()), limit),
+        subject: count_values(sets.clone().map(|set| set.subject.as_deref()), limit),
+        term_type: count_values(sets.clone().map(|set| set.term_type.as_deref()), limit),
+        source: count_values(sets.clone().map(|set| set.source.as_deref()), limit),
+        creator_id: count_values(sets.map(|set| set.creator_id.as_deref()), limit),
+    }
+}
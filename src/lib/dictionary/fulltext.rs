@@ -0,0 +1,165 @@
+use axum::extract::State;
+use rusqlite::params;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::dictionary::database::{connect_db, terms_list_from_row, AppState};
+use crate::dictionary::search::{MatchKind, SearchHit};
+
+const SNIPPET_START: &str = "\u{2}";
+const SNIPPET_END: &str = "\u{3}";
+
+pub fn fts_table_name(table_name: &str) -> String {
+    format!("{table_name}_fts")
+}
+
+pub fn create_fulltext_index(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(), rusqlite::Error> {
+    let table = &app_state.db_info.table_name;
+    let fts_table = fts_table_name(table);
+    debug!("Create full-text index: {}", fts_table);
+    let conn = connect_db(State(app_state.clone()))?;
+
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        params![fts_table],
+        |row| row.get(0),
+    )?;
+
+    conn.execute_batch(&format!(
+        "
+        BEGIN;
+
+        CREATE VIRTUAL TABLE IF NOT EXISTS {fts_table} USING fts5(
+            term,
+            definition,
+            context,
+            remark,
+            subject,
+            source,
+            content = '{table}',
+            content_rowid = 'term_id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS {fts_table}_after_insert AFTER INSERT ON {table} BEGIN
+            INSERT INTO {fts_table} (rowid, term, definition, context, remark, subject, source)
+            VALUES (new.term_id, new.term, new.definition, new.context, new.remark, new.subject, new.source);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS {fts_table}_after_delete AFTER DELETE ON {table} BEGIN
+            INSERT INTO {fts_table} ({fts_table}, rowid, term, definition, context, remark, subject, source)
+            VALUES ('delete', old.term_id, old.term, old.definition, old.context, old.remark, old.subject, old.source);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS {fts_table}_after_update AFTER UPDATE ON {table} BEGIN
+            INSERT INTO {fts_table} ({fts_table}, rowid, term, definition, context, remark, subject, source)
+            VALUES ('delete', old.term_id, old.term, old.definition, old.context, old.remark, old.subject, old.source);
+            INSERT INTO {fts_table} (rowid, term, definition, context, remark, subject, source)
+            VALUES (new.term_id, new.term, new.definition, new.context, new.remark, new.subject, new.source);
+        END;
+
+        COMMIT;
+        "
+    ))?;
+
+    if !exists {
+        debug!(
+            "Populating new full-text index {} from {}",
+            fts_table, table
+        );
+        conn.execute(
+            &format!("INSERT INTO {fts_table} ({fts_table}) VALUES ('rebuild')"),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Runs an FTS5 query (phrases, `AND`/`OR`/`NOT`, `prefix*` and
+/// `column:` filters) against term, definition, context, remark, subject and
+/// source. Hits are ordered by bm25 relevance and carry an HTML-safe snippet.
+pub fn search_fulltext(
+    State(app_state): State<Arc<AppState>>,
+    query: &str,
+    language: &str,
+) -> Result<Vec<SearchHit>, rusqlite::Error> {
+    debug!("Full-text search: {:?}, language: {:?}", query, language);
+    let conn = connect_db(State(app_state.clone()))?;
+    let table = &app_state.db_info.table_name;
+    let fts_table = fts_table_name(table);
+
+    let sql = format!(
+        "SELECT
+            t.term_id,
+            t.term_set_id,
+            t.term,
+            t.language,
+            t.term_type,
+            t.creator_id,
+            t.creation_timestamp,
+            t.updater_id,
+            t.update_timestamp,
+            t.subject,
+            t.source,
+            t.user,
+            t.attributes,
+            t.remark,
+            t.url,
+            t.context,
+            t.definition,
+            t.status,
+            t.part_of_speech,
+            t.gender,
+            t.number,
+            t.inflection,
+            snippet({fts_table}, -1, ?3, ?4, '…', 12),
+            bm25({fts_table})
+        FROM {fts_table}
+        JOIN {table} t ON t.term_id = {fts_table}.rowid
+        WHERE {fts_table} MATCH ?1 AND (t.language = ?2 OR ?2 = '') AND t.deleted = 0
+        ORDER BY bm25({fts_table}), t.term_id"
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let hits_iter = stmt.query_map(
+        params![query, language, SNIPPET_START, SNIPPET_END],
+        |row| {
+            let rank: f64 = row.get(23)?;
+            let relevance = -rank;
+            Ok(SearchHit {
+                entry: terms_list_from_row(row)?,
+                match_kind: MatchKind::Fulltext,
+                score: relevance.max(0.0) / (1.0 + relevance.max(0.0)),
+                snippet: row
+                    .get::<_, Option<String>>(22)?
+                    .map(|raw| highlight_snippet(&raw)),
+            })
+        },
+    )?;
+
+    hits_iter.collect()
+}
+
+pub fn highlight_snippet(raw: &str) -> String {
+    escape_html(raw)
+        .replace(SNIPPET_START, "<mark>")
+        .replace(SNIPPET_END, "</mark>")
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -1,7 +1,7 @@
 use askama::Template;
 use axum::{
     debug_handler,
-    extract::{Json, State},
+    extract::{Json, OriginalUri, State},
     http::{HeaderValue, StatusCode},
     response::{Html, IntoResponse, Response},
 };
@@ -10,7 +10,7 @@ use axum::{
     Form,
 };
 use lazy_static::lazy_static;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::{
     collections::HashMap,
@@ -24,12 +24,65 @@ use tokio::{
 use tracing::{error, info};
 
 use crate::{
+    dictionary::bulk::{bulk_edit, preview_bulk_edit, BulkAction, BulkError, BulkSelection},
     dictionary::database::{
-        add_term, add_term_to_term_set, current_epoch, delete_term,
-        extract_and_insert_unique_values, get_all_terms, get_term_by_id, search_terms,
-        search_terms_by_term_set_id, update_term, AppState, TermsList,
+        add_term, add_term_to_term_set, count_terms, create_terms_table,
+        create_unique_values_tables, current_epoch, delete_term, delete_termset,
+        extract_and_insert_unique_values, get_all_terms, get_term_by_id, get_term_version,
+        patch_term_with_picklists, search_terms_by_query, search_terms_by_term_set_id,
+        search_terms_with_options, AppState, TermsList,
+    },
+    dictionary::duplicates::{find_duplicates, DuplicateGroup, DuplicateOptions},
+    dictionary::export::{export_json, export_tbx, ExportFormat},
+    dictionary::facets::{create_facet_index, facets_from_terms, get_facets, DEFAULT_FACET_LIMIT},
+    dictionary::fulltext::{create_fulltext_index, search_fulltext},
+    dictionary::history::{
+        create_history_table, get_term_history, revert_term, HistoryError, Revision,
+    },
+    dictionary::lookup::{
+        batch_lookup, lookup_terms, BatchLookupEntry, BatchLookupOptions, LookupOptions,
+        DEFAULT_BATCH_MAX_RESULTS, DEFAULT_LOOKUP_LIMIT, MAX_BATCH_SIZE,
+    },
+    dictionary::paging::{
+        clamp_limit, page_links, paginate, sort_page_for_language, SortField, SortOrder,
+        DEFAULT_PAGE_SIZE,
+    },
+    dictionary::patch::{FieldUpdate, TermPatch, UpdateError},
+    dictionary::picklist::{
+        add_picklist_value, apply_picklists, apply_picklists_to_patch, create_picklist_tables,
+        get_picklists, merge_values, remove_picklist_value, seed_picklist, set_allow_new,
+        PicklistError,
+    },
+    dictionary::qa::{check_segments, Segment, MAX_QA_SEGMENTS},
+    dictionary::query::{parse_query, Query as SearchQuery},
+    dictionary::recognize::{recognize_terms, terms_changed},
+    dictionary::recycle::{
+        get_deleted_terms, purge_expired, purge_term, restore_term, DeletedTerm,
+    },
+    dictionary::relations::{
+        add_relation, create_relations_table, get_related_term_sets, get_relations,
+        remove_relation, RelatedTermSet, RelationError, RelationType, TermSetRelation,
+    },
+    dictionary::search::{
+        fold_text, rank_hits, rank_query_hits, term_matches, SearchHit, SearchMode, SearchOptions,
+        DEFAULT_MIN_SCORE,
+    },
+    dictionary::skos::{export_rdf_xml, export_turtle, skos_graph, DEFAULT_BASE_URI},
+    dictionary::taxonomy::{
+        add_subject, delete_subject, get_subject_tree, get_term_subject_path, update_subject,
+        SubjectNode, TaxonomyError, SUBJECT_SEPARATOR,
+    },
+    dictionary::term_sets::{
+        merge_term_sets_into, move_term, split_term_set, DuplicateLanguages, TermSetError,
+    },
+    dictionary::xliff::{check_xliff, read_xliff, XliffQaReport},
+    import::{
+        parse::{
+            deserialize_gender, deserialize_number, deserialize_part_of_speech, deserialize_status,
+            GrammaticalGender, GrammaticalNumber, PartOfSpeech, TermLanguageSet, TermStatus,
+        },
+        process::import_dictionary_data,
     },
-    import::{parse::TermLanguageSet, process::import_dictionary_data},
 };
 
 lazy_static! {
@@ -40,6 +93,7 @@ lazy_static! {
 pub fn clear_cache() {
     info!("Clearing cache");
     SEARCH_CACHE.write().unwrap().clear();
+    terms_changed();
 }
 
 #[derive(Debug, Deserialize)]
@@ -56,6 +110,10 @@ pub async fn handle_add_term_set(
     let existing_term_set_id = payload.existing_term_set_id;
     let mut term_set = payload.term_language_set;
 
+    if let Err(err) = apply_picklists(State(app_state.clone()), &mut term_set) {
+        return picklist_error_response(err);
+    }
+
     let now = current_epoch();
     term_set.creation_timestamp = Some(now);
     term_set.update_timestamp = Some(now);
@@ -69,7 +127,6 @@ pub async fn handle_add_term_set(
         Ok(_) => {
             info!("Term set added successfully.");
             clear_cache();
-            let _unique_values_result = extract_and_insert_unique_values(State(app_state.clone()));
             (
                 StatusCode::OK,
                 "Term set added to existing term successfully",
@@ -90,6 +147,7 @@ pub async fn handle_add_term_set(
 #[derive(Debug, Deserialize)]
 pub struct DeleteTermRequest {
     term_id: i32,
+    user: Option<String>,
 }
 
 #[debug_handler]
@@ -101,9 +159,10 @@ pub async fn handle_delete_term(
 
     info!("Deleting term with ID: {}", term_id);
 
-    match delete_term(State(app_state.clone()), term_id) {
+    let user = params.user.as_deref().filter(|user| !user.is_empty());
+    match delete_term(State(app_state.clone()), term_id, user) {
         Ok(_) => {
-            info!("Term deleted successfully.");
+            info!("Term moved to the recycle bin.");
             clear_cache();
             match get_all_terms(State(app_state.clone())) {
                 Ok(all_terms) => {
@@ -118,8 +177,6 @@ pub async fn handle_delete_term(
                         search_cache.insert("*:*".to_string(), all_terms);
                     }
 
-                    let _ = extract_and_insert_unique_values(State(app_state.clone()));
-
                     (
                         StatusCode::OK,
                         "Term deleted and cache refreshed successfully",
@@ -166,6 +223,10 @@ pub async fn handle_insert_term(
 ) -> impl IntoResponse {
     let mut term_set = payload.term_language_set;
 
+    if let Err(err) = apply_picklists(State(app_state.clone()), &mut term_set) {
+        return picklist_error_response(err);
+    }
+
     let now = current_epoch();
     term_set.creation_timestamp = Some(now);
     term_set.update_timestamp = Some(now);
@@ -190,8 +251,6 @@ pub async fn handle_insert_term(
                         search_cache.insert("*:*".to_string(), all_terms);
                     }
 
-                    let _ = extract_and_insert_unique_values(State(app_state.clone()));
-
                     (
                         StatusCode::OK,
                         "Term inserted and cache refreshed successfully",
@@ -223,6 +282,8 @@ pub async fn handle_import_dictionary_data(
     State(app_state): State<Arc<AppState>>,
     mut multipart: Multipart,
 ) -> impl IntoResponse {
+    let mut subject_separator = SUBJECT_SEPARATOR.to_string();
+
     while let Some(mut field) = match multipart.next_field().await {
         Ok(Some(field)) => Some(field),
         Ok(None) => None,
@@ -249,6 +310,21 @@ pub async fn handle_import_dictionary_data(
 
         info!("Processing field: {}", name);
 
+        if name == "subjectSeparator" {
+            match field.text().await {
+                Ok(separator) => subject_separator = separator.trim().to_string(),
+                Err(err) => {
+                    error!("Failed to read subject separator: {}", err);
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        format!("Failed to read subject separator: {err}"),
+                    )
+                        .into_response();
+                }
+            }
+            continue;
+        }
+
         if name == "dictionaryFile" {
             info!("Receiving dictionary file {}", name);
 
@@ -305,6 +381,7 @@ pub async fn handle_import_dictionary_data(
             if let Err(err) = import_dictionary_data(
                 State(app_state.clone()),
                 file_path.to_string_lossy().as_ref(),
+                &subject_separator,
             )
             .await
             {
@@ -330,8 +407,6 @@ pub async fn handle_import_dictionary_data(
                         search_cache.insert("*:*".to_string(), all_terms);
                     }
 
-                    let _ = extract_and_insert_unique_values(State(app_state.clone()));
-
                     info!("Cache repopulated after dictionary import.");
                     return (
                         StatusCode::OK,
@@ -430,36 +505,37 @@ pub async fn handle_get_settings() -> impl IntoResponse {
 #[derive(Template)]
 #[template(path = "terms.html")]
 pub struct TermsTemplate {
-    pub terms: Vec<TermsList>,
+    pub total_terms: usize,
+    pub page_size: usize,
 }
 pub async fn handle_terms(State(app_state): State<Arc<AppState>>) -> Html<String> {
-    if let Some(cached_terms) = app_state.terms_cache.lock().unwrap().as_ref() {
-        let template = TermsTemplate {
-            terms: cached_terms.clone(),
-        };
-        return Html(
-            template
-                .render()
-                .unwrap_or_else(|_| "Template rendering error".to_string()),
-        );
-    }
-
-    match get_all_terms(State(app_state.clone())) {
-        Ok(terms) => {
-            *app_state.terms_cache.lock().unwrap() = Some(terms.clone());
+    let cached_count = app_state
+        .terms_cache
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|terms| terms.len());
+
+    let total_terms = match cached_count {
+        Some(count) => count,
+        None => match count_terms(State(app_state.clone())) {
+            Ok(count) => count,
+            Err(err) => {
+                error!("Failed to count terms: {}", err);
+                return Html(format!("<h1>Failed to get data: {err}</h1>"));
+            }
+        },
+    };
 
-            let template = TermsTemplate { terms };
-            Html(
-                template
-                    .render()
-                    .unwrap_or_else(|_| "Template rendering error".to_string()),
-            )
-        }
-        Err(err) => {
-            error!("Failed to get terms: {}", err);
-            Html(format!("<h1>Failed to get data: {err}</h1>"))
-        }
-    }
+    let template = TermsTemplate {
+        total_terms,
+        page_size: DEFAULT_PAGE_SIZE,
+    };
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|_| "Template rendering error".to_string()),
+    )
 }
 
 #[derive(Debug, Deserialize)]
@@ -471,6 +547,12 @@ pub struct TermDetailRequest {
 #[template(path = "term_detail.html")]
 pub struct TermDetailTemplate {
     pub term: TermsList,
+    pub subject_path: Vec<SubjectNode>,
+    pub relations: Vec<RelatedTermSet>,
+    pub relation_types: [RelationType; 5],
+    pub history: Vec<Revision>,
+    pub version: i64,
+    pub term_set: Vec<TermsList>,
 }
 
 pub async fn handle_get_term_details(
@@ -484,7 +566,41 @@ pub async fn handle_get_term_details(
     match get_term_by_id(State(app_state.clone()), term_id) {
         Ok(Some(term)) => {
             info!("Term details fetched successfully.");
-            let template = TermDetailTemplate { term };
+            let subject_path = get_term_subject_path(State(app_state.clone()), term_id)
+                .unwrap_or_else(|err| {
+                    error!("Failed to get subject path: {}", err);
+                    Vec::new()
+                });
+            let relations = get_related_term_sets(State(app_state.clone()), term.term_set_id)
+                .unwrap_or_else(|err| {
+                    error!("Failed to get relations: {}", err);
+                    Vec::new()
+                });
+            let history =
+                get_term_history(State(app_state.clone()), term_id).unwrap_or_else(|err| {
+                    error!("Failed to get term history: {}", err);
+                    Vec::new()
+                });
+            let version = get_term_version(State(app_state.clone()), term_id)
+                .unwrap_or_else(|err| {
+                    error!("Failed to get term version: {}", err);
+                    None
+                })
+                .unwrap_or(1);
+            let term_set = search_terms_by_term_set_id(State(app_state.clone()), term.term_set_id)
+                .unwrap_or_else(|err| {
+                    error!("Failed to get term set: {}", err);
+                    Vec::new()
+                });
+            let template = TermDetailTemplate {
+                term,
+                subject_path,
+                relations,
+                relation_types: RelationType::ALL,
+                history,
+                version,
+                term_set,
+            };
             Html(
                 template
                     .render()
@@ -502,10 +618,72 @@ pub async fn handle_get_term_details(
     }
 }
 
+fn default_true() -> bool {
+    true
+}
+
+fn default_min_score() -> f64 {
+    DEFAULT_MIN_SCORE
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SearchRequest {
+    #[serde(default)]
     term: String,
+    #[serde(default)]
     language: String,
+    #[serde(default)]
+    q: String,
+    #[serde(default = "default_true")]
+    ignore_case: bool,
+    #[serde(default = "default_true")]
+    ignore_width: bool,
+    #[serde(default)]
+    ignore_accents: bool,
+    #[serde(default)]
+    mode: SearchMode,
+    #[serde(default = "default_min_score")]
+    min_score: f64,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+    sort: Option<SortField>,
+    #[serde(default)]
+    order: SortOrder,
+    #[serde(default, deserialize_with = "deserialize_status")]
+    status: Option<TermStatus>,
+    #[serde(default, deserialize_with = "deserialize_part_of_speech")]
+    part_of_speech: Option<PartOfSpeech>,
+    #[serde(default, deserialize_with = "deserialize_gender")]
+    gender: Option<GrammaticalGender>,
+    #[serde(default, deserialize_with = "deserialize_number")]
+    number: Option<GrammaticalNumber>,
+}
+
+impl SearchRequest {
+    fn options(&self) -> SearchOptions {
+        SearchOptions {
+            ignore_case: self.ignore_case,
+            ignore_width: self.ignore_width,
+            ignore_accents: self.ignore_accents,
+        }
+    }
+
+    fn has_filters(&self) -> bool {
+        self.status.is_some()
+            || self.part_of_speech.is_some()
+            || self.gender.is_some()
+            || self.number.is_some()
+    }
+
+    fn matches_filters(&self, set: &TermLanguageSet) -> bool {
+        self.status.is_none_or(|status| set.status == Some(status))
+            && self
+                .part_of_speech
+                .is_none_or(|pos| set.part_of_speech == Some(pos))
+            && self.gender.is_none_or(|gender| set.gender == Some(gender))
+            && self.number.is_none_or(|number| set.number == Some(number))
+    }
 }
 
 #[derive(Template)]
@@ -514,13 +692,77 @@ pub struct SearchResultsTemplate {
     pub terms: Vec<TermsList>,
     pub count: usize,
 }
-pub async fn handle_search_terms(
+fn find_hits(
     State(app_state): State<Arc<AppState>>,
-    Query(params): Query<SearchRequest>,
-) -> impl IntoResponse {
+    params: &SearchRequest,
+) -> Result<Vec<SearchHit>, (StatusCode, String)> {
+    let mut hits = find_matching_hits(State(app_state), params)?;
+    if params.has_filters() {
+        hits.retain(|hit| params.matches_filters(&hit.entry.term_language_set));
+    }
+    Ok(hits)
+}
+
+fn find_matching_hits(
+    State(app_state): State<Arc<AppState>>,
+    params: &SearchRequest,
+) -> Result<Vec<SearchHit>, (StatusCode, String)> {
     let term_select = params.term.clone();
     let language_select = params.language.clone();
-    let cache_key = format!("{term_select}:{language_select}");
+    let options = params.options();
+
+    if !params.q.trim().is_empty() {
+        let (query, terms) = run_query(
+            State(app_state.clone()),
+            &params.q,
+            &language_select,
+            &options,
+        )?;
+        info!(
+            "Query search returned {} results for query: '{}'",
+            terms.len(),
+            params.q
+        );
+        return Ok(rank_query_hits(&terms, &query.free_text(), &options));
+    }
+
+    if params.mode == SearchMode::Fulltext {
+        return match search_fulltext(State(app_state.clone()), &term_select, &language_select) {
+            Ok(hits) => {
+                info!(
+                    "Full-text search returned {} results for query: '{}'",
+                    hits.len(),
+                    term_select
+                );
+                Ok(hits)
+            }
+            Err(err) => {
+                error!("Invalid full-text query '{}': {}", term_select, err);
+                Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid full-text query: {err}"),
+                ))
+            }
+        };
+    }
+
+    // Fuzzy matches can't be narrowed down by substring, so fuzzy searches rank
+    // every term of the language; the candidate list is cached either way.
+    let candidate_term = match params.mode {
+        SearchMode::Fuzzy => String::new(),
+        SearchMode::Substring | SearchMode::Fulltext => term_select.clone(),
+    };
+    let cache_key = format!("{candidate_term}:{language_select}:{}", options.flags());
+
+    let rank = |candidates: &[TermsList]| {
+        rank_hits(
+            candidates,
+            &term_select,
+            &options,
+            params.mode,
+            params.min_score,
+        )
+    };
 
     let search_cache_read = SEARCH_CACHE.read().unwrap();
 
@@ -529,33 +771,30 @@ pub async fn handle_search_terms(
             "Request cache hit for term: '{}' and language: '{}'",
             term_select, language_select
         );
-        return Json(cached_results.clone());
+        return Ok(rank(cached_results));
     }
 
-    if term_select.is_empty() || language_select.is_empty() {
+    if candidate_term.is_empty() || language_select.is_empty() {
         if let Some(all_terms) = search_cache_read.get("*:*") {
             info!("Using wildcard '*:*' cache to filter results in-memory.");
 
+            let folded_term = fold_text(&candidate_term, &options);
             let filtered: Vec<TermsList> = all_terms
                 .iter()
-                .filter(|t| {
-                    let term_str = t.term_language_set.term.as_deref().unwrap_or("");
-                    let lang_str = t.term_language_set.language.as_deref().unwrap_or("");
-
-                    (term_select.is_empty() || term_str.contains(&term_select))
-                        && (language_select.is_empty() || lang_str == language_select)
-                })
+                .filter(|t| term_matches(t, &folded_term, &language_select, &options))
                 .cloned()
                 .collect();
 
             drop(search_cache_read);
 
+            let hits = rank(&filtered);
+
             SEARCH_CACHE
                 .write()
                 .unwrap()
-                .insert(cache_key.clone(), filtered.clone());
+                .insert(cache_key.clone(), filtered);
 
-            return Json(filtered);
+            return Ok(hits);
         }
     }
 
@@ -566,195 +805,1567 @@ pub async fn handle_search_terms(
         term_select, language_select
     );
 
-    match search_terms(State(app_state.clone()), &term_select, &language_select) {
+    match search_terms_with_options(
+        State(app_state.clone()),
+        &candidate_term,
+        &language_select,
+        &options,
+    ) {
         Ok(terms) => {
+            let hits = rank(&terms);
             info!(
                 "Search returned {} results for term: '{}' and language: '{}'",
-                terms.len(),
+                hits.len(),
                 term_select,
                 language_select
             );
 
-            SEARCH_CACHE
-                .write()
-                .unwrap()
-                .insert(cache_key, terms.clone());
+            SEARCH_CACHE.write().unwrap().insert(cache_key, terms);
 
-            Json(terms)
+            Ok(hits)
         }
         Err(err) => {
             error!(
                 "Error searching for term: '{}' and language: '{}': {}",
                 term_select, language_select, err
             );
-            Json(vec![])
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to search terms: {err}"),
+            ))
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct SearchByTermSetIdRequest {
-    term_set_id: i32,
-}
-
-pub async fn handle_search_terms_by_term_set_id(
+/// Returns one page of hits, in relevance order unless `sort` is given. The
+/// total number of hits is sent in `X-Total-Count` and, when `limit` is set,
+/// the neighbouring pages in a `Link` header. Every hit is fetched and ranked
+/// before the page is cut out; only the sort stops at the end of the page.
+pub async fn handle_search_terms(
     State(app_state): State<Arc<AppState>>,
-    Query(params): Query<SearchByTermSetIdRequest>,
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<SearchRequest>,
 ) -> impl IntoResponse {
-    let term_set_id = params.term_set_id;
-
-    info!("Searching for terms with term_set_id: {}", term_set_id);
+    let mut hits = match find_hits(State(app_state), &params) {
+        Ok(hits) => hits,
+        Err(response) => return response.into_response(),
+    };
 
-    match search_terms_by_term_set_id(State(app_state.clone()), term_set_id) {
-        Ok(terms) => {
-            info!("Search completed successfully.");
-            Json(terms)
-        }
-        Err(err) => {
-            error!("Failed to search terms: {}", err);
-            Json(vec![])
+    let total = hits.len();
+    let limit = clamp_limit(params.limit);
+    if let Some(sort) = params.sort {
+        let end = limit.map(|limit| params.offset.saturating_add(limit));
+        sort_page_for_language(
+            &mut hits,
+            |hit| &hit.entry,
+            sort,
+            params.order,
+            &params.language,
+            end,
+        );
+    }
+    let page = paginate(hits, params.offset, limit);
+
+    let mut response = Json(page).into_response();
+    response
+        .headers_mut()
+        .insert("X-Total-Count", HeaderValue::from(total));
+    if let Some(limit) = limit {
+        let links = page_links(uri.path(), uri.query(), params.offset, limit, total);
+        if let Ok(links) = HeaderValue::from_str(&links) {
+            response
+                .headers_mut()
+                .insert(axum::http::header::LINK, links);
         }
     }
+    response
 }
 
-#[derive(Debug, Deserialize)]
-pub struct UpdateTermRequest {
-    term_id: i32,
-    term_language_set: TermLanguageSet,
-}
-
-pub async fn handle_update_term(
+/// Facet counts for the same parameters as `/search`. Without any filter the
+/// counts come straight from the unique value tables.
+pub async fn handle_search_facets(
     State(app_state): State<Arc<AppState>>,
-    Json(payload): Json<UpdateTermRequest>,
+    Query(params): Query<SearchRequest>,
 ) -> impl IntoResponse {
-    let term_id = payload.term_id;
-    let mut term_set = payload.term_language_set;
-
-    let now = current_epoch();
-    term_set.update_timestamp = Some(now);
-
-    info!("Updating term ID: {}", term_id);
-
-    match update_term(State(app_state.clone()), term_id, &term_set) {
-        Ok(_) => {
-            info!("Term updated successfully.");
-            clear_cache();
-
-            match get_all_terms(State(app_state.clone())) {
-                Ok(all_terms) => {
-                    {
-                        let mut cache = app_state.terms_cache.lock().unwrap();
-                        *cache = Some(all_terms.clone());
-                    }
-
-                    {
-                        let mut search_cache = SEARCH_CACHE.write().unwrap();
-                        search_cache.insert("*:*".to_string(), all_terms);
-                    }
+    let unfiltered = params.q.trim().is_empty()
+        && params.term.is_empty()
+        && params.language.is_empty()
+        && !params.has_filters()
+        && params.mode != SearchMode::Fulltext;
+
+    if unfiltered {
+        return match get_facets(State(app_state), DEFAULT_FACET_LIMIT) {
+            Ok(facets) => Json(facets).into_response(),
+            Err(err) => {
+                error!("Failed to read facets: {}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to read facets: {err}"),
+                )
+                    .into_response()
+            }
+        };
+    }
 
-                    let _unique_values_result =
-                        extract_and_insert_unique_values(State(app_state.clone()));
+    match find_hits(State(app_state), &params) {
+        Ok(hits) => Json(facets_from_terms(
+            hits.iter().map(|hit| &hit.entry),
+            DEFAULT_FACET_LIMIT,
+        ))
+        .into_response(),
+        Err(response) => response.into_response(),
+    }
+}
 
-                    info!("Cache repopulated after term update.");
-                    (
-                        StatusCode::OK,
-                        "Term updated successfully and cache refreshed",
-                    )
-                        .into_response()
-                }
-                Err(e) => {
-                    error!("Failed to reload terms after update: {}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Term updated but failed to reload cache".to_string(),
-                    )
-                        .into_response()
-                }
-            }
-        }
-        Err(err) => {
-            error!("Failed to update term: {}", err);
+fn run_query(
+    State(app_state): State<Arc<AppState>>,
+    q: &str,
+    language: &str,
+    options: &SearchOptions,
+) -> Result<(SearchQuery, Vec<TermsList>), (StatusCode, String)> {
+    let query = parse_query(q).map_err(|err| {
+        error!("Invalid query '{}': {}", q, err);
+        (StatusCode::BAD_REQUEST, format!("Invalid query {err}"))
+    })?;
+
+    let terms =
+        search_terms_by_query(State(app_state), &query, language, options).map_err(|err| {
+            error!("Error running query '{}': {}", q, err);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to update term: {err}"),
+                format!("Failed to run query: {err}"),
             )
-                .into_response()
-        }
-    }
+        })?;
+
+    Ok((query, terms))
 }
 
-#[derive(Template)]
-#[template(path = "database_management.html")]
-struct DatabaseManagementTemplate;
+#[derive(Debug, Deserialize)]
+pub struct ExportRequest {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    language: String,
+    #[serde(default)]
+    format: ExportFormat,
+    sort: Option<SortField>,
+    #[serde(default)]
+    order: SortOrder,
+    #[serde(default = "default_base_uri")]
+    base_uri: String,
+}
 
-pub async fn handle_database_management() -> Html<String> {
-    info!("Serving database management form.");
-    let template = DatabaseManagementTemplate;
-    Html(
-        template
-            .render()
-            .unwrap_or_else(|_| "Template rendering error".to_string()),
-    )
+fn default_base_uri() -> String {
+    DEFAULT_BASE_URI.to_string()
 }
 
-pub async fn handle_download_db_file(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
-    let path = &app_state.db_info.path();
+pub async fn handle_export(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<ExportRequest>,
+) -> impl IntoResponse {
+    let mut terms = match run_query(
+        State(app_state.clone()),
+        &params.q,
+        &params.language,
+        &SearchOptions::default(),
+    ) {
+        Ok((_, terms)) => terms,
+        Err(response) => return response.into_response(),
+    };
 
-    if !Path::new(path).exists() {
-        error!("Database file not found: {}", path);
-        return (StatusCode::NOT_FOUND, "Database file not found").into_response();
+    if let Some(sort) = params.sort {
+        sort_page_for_language(
+            &mut terms,
+            |entry| entry,
+            sort,
+            params.order,
+            &params.language,
+            None,
+        );
     }
 
-    info!("Sending database file: {}", path);
-
-    match File::open(path).await {
-        Ok(mut file) => {
-            let mut buffer = Vec::new();
-            if let Err(err) = file.read_to_end(&mut buffer).await {
-                error!("Failed to read the database file: {}", err);
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Failed to read the database file: {err}"),
-                )
-                    .into_response();
-            }
-
-            let file_name = Path::new(path)
-                .file_name()
-                .and_then(|name| name.to_str())
-                .unwrap_or("database.db");
+    let mut term_set_ids: Vec<i32> = terms.iter().map(|entry| entry.term_set_id).collect();
+    term_set_ids.sort_unstable();
+    term_set_ids.dedup();
+    let relations = match get_relations(State(app_state.clone()), Some(&term_set_ids)) {
+        Ok(relations) => relations,
+        Err(err) => {
+            error!("Failed to get relations for export: {}", err);
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to export terms: {err}"),
+            )
+                .into_response();
+        }
+    };
 
-            let mut response = Response::new(buffer.into());
+    let exported = match params.format {
+        ExportFormat::Json => export_json(&terms, &relations).map_err(|err| err.to_string()),
+        ExportFormat::Tbx => export_tbx(&terms, &relations).map_err(|err| err.to_string()),
+        ExportFormat::Turtle | ExportFormat::RdfXml => get_subject_tree(State(app_state.clone()))
+            .map(|subjects| skos_graph(&terms, &relations, &subjects, &params.base_uri))
+            .map(|graph| match params.format {
+                ExportFormat::Turtle => export_turtle(&graph),
+                _ => export_rdf_xml(&graph),
+            })
+            .map_err(|err| err.to_string()),
+    };
 
+    match exported {
+        Ok(body) => {
+            info!(
+                "Exporting {} terms as {:?} for query: '{}'",
+                terms.len(),
+                params.format,
+                params.q
+            );
+            let mut response = Response::new(body.into());
             response.headers_mut().insert(
                 axum::http::header::CONTENT_TYPE,
-                HeaderValue::from_static("application/octet-stream"),
+                HeaderValue::from_static(params.format.content_type()),
             );
-
             response.headers_mut().insert(
                 axum::http::header::CONTENT_DISPOSITION,
-                HeaderValue::from_str(&format!("attachment; filename=\"{file_name}\"")).unwrap(),
+                HeaderValue::from_str(&format!(
+                    "attachment; filename=\"{}\"",
+                    params.format.file_name()
+                ))
+                .unwrap(),
             );
-
-            info!("Database file sent successfully.");
             response
         }
         Err(err) => {
-            error!("Failed to open the database file: {}", err);
+            error!("Failed to export terms: {}", err);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to open the database file: {err}"),
+                format!("Failed to export terms: {err}"),
             )
                 .into_response()
         }
     }
 }
 
-pub async fn handle_upload_db_file(
+fn default_lookup_limit() -> usize {
+    DEFAULT_LOOKUP_LIMIT
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LookupRequest {
+    term: String,
+    #[serde(default)]
+    from: String,
+    to: String,
+    #[serde(default = "default_true")]
+    ignore_case: bool,
+    #[serde(default = "default_true")]
+    ignore_width: bool,
+    #[serde(default)]
+    ignore_accents: bool,
+    #[serde(default)]
+    mode: SearchMode,
+    #[serde(default = "default_min_score")]
+    min_score: f64,
+    #[serde(default = "default_lookup_limit")]
+    limit: usize,
+}
+
+pub async fn handle_lookup(
     State(app_state): State<Arc<AppState>>,
-    mut multipart: Multipart,
+    Query(params): Query<LookupRequest>,
 ) -> impl IntoResponse {
-    let db_file_path = &app_state.db_info.path();
+    if params.term.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "Missing term to look up").into_response();
+    }
+
+    let options = LookupOptions {
+        search: SearchOptions {
+            ignore_case: params.ignore_case,
+            ignore_width: params.ignore_width,
+            ignore_accents: params.ignore_accents,
+        },
+        mode: params.mode,
+        min_score: params.min_score,
+        limit: clamp_limit(Some(params.limit)).unwrap_or(DEFAULT_LOOKUP_LIMIT),
+    };
+
+    match lookup_terms(
+        State(app_state.clone()),
+        &params.term,
+        &params.from,
+        &params.to,
+        &options,
+    ) {
+        Ok(results) => {
+            info!(
+                "Lookup of '{}' from '{}' to '{}' found {} concepts",
+                params.term,
+                params.from,
+                params.to,
+                results.len()
+            );
+            Json(results).into_response()
+        }
+        Err(err) => {
+            error!("Failed to look up '{}': {}", params.term, err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to look up term: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+fn default_batch_max_results() -> usize {
+    DEFAULT_BATCH_MAX_RESULTS
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchLookupRequest {
+    items: Vec<BatchLookupEntry>,
+    #[serde(default)]
+    from: String,
+    #[serde(default)]
+    to: String,
+    #[serde(default = "default_true")]
+    ignore_case: bool,
+    #[serde(default = "default_true")]
+    ignore_width: bool,
+    #[serde(default)]
+    ignore_accents: bool,
+    #[serde(default = "default_true")]
+    fuzzy: bool,
+    #[serde(default = "default_min_score")]
+    min_score: f64,
+    #[serde(default = "default_batch_max_results")]
+    max_results: usize,
+}
+
+pub async fn handle_batch_lookup(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<BatchLookupRequest>,
+) -> impl IntoResponse {
+    if payload.items.len() > MAX_BATCH_SIZE {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("A batch may contain at most {MAX_BATCH_SIZE} items"),
+        )
+            .into_response();
+    }
+    if let Some(position) = payload
+        .items
+        .iter()
+        .position(|item| item.to.as_deref().unwrap_or(&payload.to).trim().is_empty())
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Item {position} has no target language"),
+        )
+            .into_response();
+    }
+
+    let options = BatchLookupOptions {
+        search: SearchOptions {
+            ignore_case: payload.ignore_case,
+            ignore_width: payload.ignore_width,
+            ignore_accents: payload.ignore_accents,
+        },
+        fuzzy: payload.fuzzy,
+        min_score: payload.min_score,
+        max_results: clamp_limit(Some(payload.max_results)).unwrap_or(DEFAULT_BATCH_MAX_RESULTS),
+    };
+
+    match batch_lookup(
+        State(app_state.clone()),
+        &payload.items,
+        &payload.from,
+        &payload.to,
+        &options,
+    ) {
+        Ok(results) => {
+            info!("Batch lookup of {} terms", results.len());
+            Json(results).into_response()
+        }
+        Err(err) => {
+            error!("Failed to run batch lookup: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to look up terms: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecognizeRequest {
+    text: String,
+    from: String,
+    #[serde(default)]
+    to: String,
+    #[serde(default = "default_true")]
+    ignore_case: bool,
+    #[serde(default = "default_true")]
+    ignore_width: bool,
+    #[serde(default)]
+    ignore_accents: bool,
+}
+
+pub async fn handle_recognize(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<RecognizeRequest>,
+) -> impl IntoResponse {
+    if payload.from.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "Missing source language").into_response();
+    }
+
+    let options = SearchOptions {
+        ignore_case: payload.ignore_case,
+        ignore_width: payload.ignore_width,
+        ignore_accents: payload.ignore_accents,
+    };
+    match recognize_terms(
+        State(app_state.clone()),
+        &payload.text,
+        &payload.from,
+        &payload.to,
+        &options,
+    ) {
+        Ok(terms) => {
+            info!("Recognized {} terms in the text", terms.len());
+            Json(terms).into_response()
+        }
+        Err(err) => {
+            error!("Failed to recognize terms: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to recognize terms: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QaRequest {
+    segments: Vec<Segment>,
+    from: String,
+    to: String,
+    #[serde(default = "default_true")]
+    ignore_case: bool,
+    #[serde(default = "default_true")]
+    ignore_width: bool,
+    #[serde(default)]
+    ignore_accents: bool,
+}
+
+pub async fn handle_qa(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<QaRequest>,
+) -> impl IntoResponse {
+    if payload.from.trim().is_empty() || payload.to.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "Missing source or target language").into_response();
+    }
+    if payload.segments.len() > MAX_QA_SEGMENTS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_QA_SEGMENTS} segments can be checked at once"),
+        )
+            .into_response();
+    }
+
+    let options = SearchOptions {
+        ignore_case: payload.ignore_case,
+        ignore_width: payload.ignore_width,
+        ignore_accents: payload.ignore_accents,
+    };
+    match check_segments(
+        State(app_state.clone()),
+        &payload.segments,
+        &payload.from,
+        &payload.to,
+        &options,
+    ) {
+        Ok(report) => {
+            info!(
+                "Checked {} segments, found {} issues",
+                report.checked_segments,
+                report.issues.len()
+            );
+            Json(report).into_response()
+        }
+        Err(err) => {
+            error!("Failed to check segments: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to check segments: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Html,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct XliffQaRequest {
+    #[serde(default)]
+    format: ReportFormat,
+    #[serde(default = "default_true")]
+    ignore_case: bool,
+    #[serde(default = "default_true")]
+    ignore_width: bool,
+    #[serde(default)]
+    ignore_accents: bool,
+}
+
+#[derive(Template)]
+#[template(path = "qa_xliff.html")]
+pub struct XliffQaTemplate {
+    pub report: Option<XliffQaReport>,
+}
+
+fn render_xliff_qa(report: Option<XliffQaReport>) -> Html<String> {
+    let template = XliffQaTemplate { report };
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|_| "Template rendering error".to_string()),
+    )
+}
+
+pub async fn handle_qa_xliff_form() -> Html<String> {
+    info!("Serving XLIFF QA form.");
+    render_xliff_qa(None)
+}
+
+pub async fn handle_qa_xliff(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<XliffQaRequest>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let mut data = None;
+    loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("xliffFile") => match field.bytes().await {
+                Ok(bytes) => data = Some(bytes),
+                Err(err) => {
+                    error!("Failed to read uploaded XLIFF file: {}", err);
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        format!("Failed to read uploaded file: {err}"),
+                    )
+                        .into_response();
+                }
+            },
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(err) => {
+                error!("Failed to read field: {}", err);
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to read field: {err}"),
+                )
+                    .into_response();
+            }
+        }
+    }
+    let Some(data) = data else {
+        return (StatusCode::BAD_REQUEST, "Missing xliffFile field").into_response();
+    };
+
+    let document = match read_xliff(&data) {
+        Ok(document) => document,
+        Err(err) => {
+            error!("Failed to read XLIFF file: {}", err);
+            return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+    };
+    if let Some(unit) = document
+        .units
+        .iter()
+        .find(|unit| unit.source_language.is_empty() || unit.target_language.is_empty())
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "File '{}' does not name its source and target languages",
+                unit.file
+            ),
+        )
+            .into_response();
+    }
+
+    let options = SearchOptions {
+        ignore_case: params.ignore_case,
+        ignore_width: params.ignore_width,
+        ignore_accents: params.ignore_accents,
+    };
+    match check_xliff(State(app_state.clone()), &document, &options) {
+        Ok(report) => {
+            info!(
+                "Checked {} XLIFF units, {} with issues",
+                report.checked_units,
+                report.units.len()
+            );
+            match params.format {
+                ReportFormat::Json => Json(report).into_response(),
+                ReportFormat::Html => render_xliff_qa(Some(report)).into_response(),
+            }
+        }
+        Err(err) => {
+            error!("Failed to check XLIFF file: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to check XLIFF file: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "recognize.html")]
+pub struct RecognizeTemplate;
+
+pub async fn handle_recognize_form() -> Html<String> {
+    info!("Serving recognize form.");
+    let template = RecognizeTemplate;
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|_| "Template rendering error".to_string()),
+    )
+}
+
+#[derive(Template)]
+#[template(path = "lookup.html")]
+pub struct LookupTemplate;
+
+pub async fn handle_lookup_form() -> Html<String> {
+    info!("Serving lookup form.");
+    let template = LookupTemplate;
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|_| "Template rendering error".to_string()),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchByTermSetIdRequest {
+    term_set_id: i32,
+}
+
+pub async fn handle_search_terms_by_term_set_id(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<SearchByTermSetIdRequest>,
+) -> impl IntoResponse {
+    let term_set_id = params.term_set_id;
+
+    info!("Searching for terms with term_set_id: {}", term_set_id);
+
+    match search_terms_by_term_set_id(State(app_state.clone()), term_set_id) {
+        Ok(terms) => {
+            info!("Search completed successfully.");
+            Json(terms)
+        }
+        Err(err) => {
+            error!("Failed to search terms: {}", err);
+            Json(vec![])
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateTermRequest {
+    term_id: i32,
+    /// The version the edits are based on. Without it the update is applied
+    /// whatever the current version.
+    #[serde(default)]
+    version: Option<i64>,
+    term_language_set: TermPatch,
+}
+
+pub async fn handle_update_term(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<UpdateTermRequest>,
+) -> impl IntoResponse {
+    let term_id = payload.term_id;
+    let mut patch = payload.term_language_set;
+
+    let new_values = match apply_picklists_to_patch(State(app_state.clone()), &mut patch) {
+        Ok(new_values) => new_values,
+        Err(err) => return picklist_error_response(err),
+    };
+
+    let now = current_epoch();
+    patch.update_timestamp = FieldUpdate::Set(now);
+
+    info!("Updating term ID: {}", term_id);
+
+    match patch_term_with_picklists(
+        State(app_state.clone()),
+        term_id,
+        &patch,
+        payload.version,
+        &new_values,
+    ) {
+        Ok(_) => {
+            info!("Term updated successfully.");
+            clear_cache();
+
+            match get_all_terms(State(app_state.clone())) {
+                Ok(all_terms) => {
+                    {
+                        let mut cache = app_state.terms_cache.lock().unwrap();
+                        *cache = Some(all_terms.clone());
+                    }
+
+                    {
+                        let mut search_cache = SEARCH_CACHE.write().unwrap();
+                        search_cache.insert("*:*".to_string(), all_terms);
+                    }
+
+                    info!("Cache repopulated after term update.");
+                    (
+                        StatusCode::OK,
+                        "Term updated successfully and cache refreshed",
+                    )
+                        .into_response()
+                }
+                Err(e) => {
+                    error!("Failed to reload terms after update: {}", e);
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Term updated but failed to reload cache".to_string(),
+                    )
+                        .into_response()
+                }
+            }
+        }
+        Err(err) => update_error_response(err),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpdateConflict {
+    pub error: String,
+    pub version: i64,
+    pub term: TermsList,
+}
+
+fn update_error_response(err: UpdateError) -> Response {
+    let message = err.to_string();
+    match err {
+        UpdateError::NotFound(_) => (StatusCode::NOT_FOUND, message).into_response(),
+        UpdateError::Required(_) => (StatusCode::BAD_REQUEST, message).into_response(),
+        UpdateError::Conflict { version, term } => {
+            info!("Rejected stale update of term {}", term.term_id);
+            let conflict = UpdateConflict {
+                error: message,
+                version,
+                term: *term,
+            };
+            (StatusCode::CONFLICT, Json(conflict)).into_response()
+        }
+        UpdateError::Database(err) => {
+            error!("Failed to update term: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to update term: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TermVersion {
+    pub term_id: i32,
+    pub version: i64,
+}
+
+pub async fn handle_get_term_version(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<TermDetailRequest>,
+) -> impl IntoResponse {
+    match get_term_version(State(app_state), params.term_id) {
+        Ok(Some(version)) => Json(TermVersion {
+            term_id: params.term_id,
+            version,
+        })
+        .into_response(),
+        Ok(None) => update_error_response(UpdateError::NotFound(params.term_id)),
+        Err(err) => update_error_response(err.into()),
+    }
+}
+
+fn picklist_error_response(err: PicklistError) -> Response {
+    match err {
+        PicklistError::Database(err) => {
+            error!("Failed to use picklists: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to use picklists: {err}"),
+            )
+                .into_response()
+        }
+        err => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    }
+}
+
+pub async fn handle_get_picklists(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    match get_picklists(State(app_state)) {
+        Ok(picklists) => Json(picklists).into_response(),
+        Err(err) => picklist_error_response(err.into()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PicklistValueRequest {
+    field: String,
+    value: String,
+}
+
+pub async fn handle_add_picklist_value(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<PicklistValueRequest>,
+) -> impl IntoResponse {
+    match add_picklist_value(State(app_state), &payload.field, &payload.value) {
+        Ok(true) => (StatusCode::OK, "Value added to picklist").into_response(),
+        Ok(false) => (StatusCode::OK, "Value already in picklist").into_response(),
+        Err(err) => picklist_error_response(err),
+    }
+}
+
+pub async fn handle_remove_picklist_value(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<PicklistValueRequest>,
+) -> impl IntoResponse {
+    match remove_picklist_value(State(app_state), &params.field, &params.value) {
+        Ok(true) => (StatusCode::OK, "Value removed from picklist").into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Value not in picklist").into_response(),
+        Err(err) => picklist_error_response(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PicklistSettingsRequest {
+    field: String,
+    allow_new: bool,
+}
+
+pub async fn handle_picklist_settings(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<PicklistSettingsRequest>,
+) -> impl IntoResponse {
+    match set_allow_new(State(app_state), &payload.field, payload.allow_new) {
+        Ok(()) => (StatusCode::OK, "Picklist settings saved").into_response(),
+        Err(err) => picklist_error_response(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedPicklistRequest {
+    field: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeedPicklistResponse {
+    added: usize,
+}
+
+pub async fn handle_seed_picklist(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<SeedPicklistRequest>,
+) -> impl IntoResponse {
+    match seed_picklist(State(app_state), &payload.field) {
+        Ok(added) => Json(SeedPicklistResponse { added }).into_response(),
+        Err(err) => picklist_error_response(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeValuesRequest {
+    field: String,
+    from: Vec<String>,
+    to: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MergeValuesResponse {
+    changed: usize,
+}
+
+pub async fn handle_merge_values(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<MergeValuesRequest>,
+) -> impl IntoResponse {
+    if payload.to.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "Missing value to merge into").into_response();
+    }
+
+    match merge_values(
+        State(app_state.clone()),
+        &payload.field,
+        &payload.from,
+        &payload.to,
+    ) {
+        Ok(changed) => {
+            reload_caches(&app_state, "merge");
+            Json(MergeValuesResponse { changed }).into_response()
+        }
+        Err(err) => picklist_error_response(err),
+    }
+}
+
+/// Clears the caches and reloads all terms after a change that rewrote
+/// stored terms.
+fn reload_caches(app_state: &Arc<AppState>, change: &str) {
+    clear_cache();
+    match get_all_terms(State(app_state.clone())) {
+        Ok(all_terms) => {
+            *app_state.terms_cache.lock().unwrap() = Some(all_terms.clone());
+            SEARCH_CACHE
+                .write()
+                .unwrap()
+                .insert("*:*".to_string(), all_terms);
+        }
+        Err(err) => error!("Failed to reload terms after {}: {}", change, err),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "picklists.html")]
+pub struct PicklistsTemplate;
+
+pub async fn handle_picklists_form() -> Html<String> {
+    info!("Serving picklists form.");
+    let template = PicklistsTemplate;
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|_| "Template rendering error".to_string()),
+    )
+}
+
+fn taxonomy_error_response(err: TaxonomyError) -> Response {
+    match err {
+        TaxonomyError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()).into_response(),
+        TaxonomyError::InvalidName(_) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+        TaxonomyError::Conflict(_) => (StatusCode::CONFLICT, err.to_string()).into_response(),
+        TaxonomyError::Database(err) => {
+            error!("Failed to use subjects: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to use subjects: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn handle_get_subjects(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    match get_subject_tree(State(app_state)) {
+        Ok(tree) => Json(tree).into_response(),
+        Err(err) => taxonomy_error_response(err.into()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertSubjectRequest {
+    name: String,
+    parent_id: Option<i64>,
+}
+
+pub async fn handle_insert_subject(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<InsertSubjectRequest>,
+) -> impl IntoResponse {
+    match add_subject(State(app_state), &payload.name, payload.parent_id) {
+        Ok(node) => Json(node).into_response(),
+        Err(err) => taxonomy_error_response(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSubjectRequest {
+    subject_id: i64,
+    name: String,
+    parent_id: Option<i64>,
+}
+
+pub async fn handle_update_subject(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<UpdateSubjectRequest>,
+) -> impl IntoResponse {
+    match update_subject(
+        State(app_state.clone()),
+        payload.subject_id,
+        &payload.name,
+        payload.parent_id,
+    ) {
+        Ok(node) => {
+            reload_caches(&app_state, "subject update");
+            Json(node).into_response()
+        }
+        Err(err) => taxonomy_error_response(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteSubjectRequest {
+    subject_id: i64,
+}
+
+pub async fn handle_delete_subject(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<DeleteSubjectRequest>,
+) -> impl IntoResponse {
+    match delete_subject(State(app_state), params.subject_id) {
+        Ok(()) => (StatusCode::OK, "Subject deleted").into_response(),
+        Err(err) => taxonomy_error_response(err),
+    }
+}
+
+fn relation_error_response(err: RelationError) -> Response {
+    match err {
+        RelationError::SameTermSet(_) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+        RelationError::UnknownTermSet(_) => {
+            (StatusCode::NOT_FOUND, err.to_string()).into_response()
+        }
+        RelationError::Database(err) => {
+            error!("Failed to use relations: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to use relations: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+fn term_set_error_response(err: TermSetError) -> Response {
+    match err {
+        TermSetError::UnknownTermSet(_) | TermSetError::UnknownTerm(_) => {
+            (StatusCode::NOT_FOUND, err.to_string()).into_response()
+        }
+        TermSetError::DuplicateLanguages(_) => {
+            (StatusCode::CONFLICT, err.to_string()).into_response()
+        }
+        TermSetError::SameTermSet(_)
+        | TermSetError::MixedTermSets
+        | TermSetError::WholeTermSet(_)
+        | TermSetError::NoTerms
+        | TermSetError::NoTermSets
+        | TermSetError::NotMerged(_) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+        TermSetError::Database(err) => {
+            error!("Failed to change term sets: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to change term sets: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MoveTermRequest {
+    term_id: i32,
+    term_set_id: i32,
+    user: Option<String>,
+}
+
+pub async fn handle_move_term(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<MoveTermRequest>,
+) -> impl IntoResponse {
+    let user = payload.user.as_deref().filter(|user| !user.is_empty());
+    match move_term(
+        State(app_state.clone()),
+        payload.term_id,
+        payload.term_set_id,
+        user,
+    ) {
+        Ok(()) => {
+            reload_caches(&app_state, "move");
+            (StatusCode::OK, "Term moved").into_response()
+        }
+        Err(err) => term_set_error_response(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeTermSetsRequest {
+    term_set_id: i32,
+    merged_set_id: Option<i32>,
+    #[serde(default)]
+    merged_set_ids: Vec<i32>,
+    #[serde(default)]
+    duplicate_languages: DuplicateLanguages,
+    #[serde(default)]
+    discarded_term_ids: Vec<i32>,
+    user: Option<String>,
+}
+
+pub async fn handle_merge_term_sets(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<MergeTermSetsRequest>,
+) -> impl IntoResponse {
+    let user = payload.user.as_deref().filter(|user| !user.is_empty());
+    let mut merged_set_ids = payload.merged_set_ids;
+    merged_set_ids.extend(payload.merged_set_id);
+    match merge_term_sets_into(
+        State(app_state.clone()),
+        payload.term_set_id,
+        &merged_set_ids,
+        payload.duplicate_languages,
+        &payload.discarded_term_ids,
+        user,
+    ) {
+        Ok(result) => {
+            reload_caches(&app_state, "merge");
+            Json(result).into_response()
+        }
+        Err(err) => term_set_error_response(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SplitTermSetRequest {
+    term_ids: Vec<i32>,
+    user: Option<String>,
+}
+
+pub async fn handle_split_term_set(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<SplitTermSetRequest>,
+) -> impl IntoResponse {
+    let user = payload.user.as_deref().filter(|user| !user.is_empty());
+    match split_term_set(State(app_state.clone()), &payload.term_ids, user) {
+        Ok(term_set_id) => {
+            reload_caches(&app_state, "split");
+            Json(term_set_id).into_response()
+        }
+        Err(err) => term_set_error_response(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteTermSetRequest {
+    term_set_id: i32,
+    user: Option<String>,
+}
+
+pub async fn handle_delete_term_set(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<DeleteTermSetRequest>,
+) -> impl IntoResponse {
+    let user = params.user.as_deref().filter(|user| !user.is_empty());
+    match search_terms_by_term_set_id(State(app_state.clone()), params.term_set_id) {
+        Ok(terms) if terms.is_empty() => {
+            return term_set_error_response(TermSetError::UnknownTermSet(params.term_set_id))
+        }
+        Ok(_) => {}
+        Err(err) => return term_set_error_response(err.into()),
+    }
+    match delete_termset(State(app_state.clone()), params.term_set_id, user) {
+        Ok(()) => {
+            info!("Term set {} moved to the recycle bin", params.term_set_id);
+            reload_caches(&app_state, "delete");
+            (StatusCode::OK, "Term set deleted").into_response()
+        }
+        Err(err) => term_set_error_response(err.into()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkEditRequest {
+    selection: BulkSelection,
+    action: BulkAction,
+    user: Option<String>,
+}
+
+fn bulk_error_response(err: BulkError) -> Response {
+    match err {
+        BulkError::UnknownTerm(_) => (StatusCode::NOT_FOUND, err.to_string()).into_response(),
+        BulkError::Picklist(err) => picklist_error_response(err),
+        BulkError::Database(err) => {
+            error!("Failed to bulk edit terms: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to bulk edit terms: {err}"),
+            )
+                .into_response()
+        }
+        err => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    }
+}
+
+pub async fn handle_bulk_preview(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<BulkEditRequest>,
+) -> impl IntoResponse {
+    match preview_bulk_edit(State(app_state), &payload.selection, &payload.action) {
+        Ok(changes) => Json(changes).into_response(),
+        Err(err) => bulk_error_response(err),
+    }
+}
+
+pub async fn handle_bulk_edit(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<BulkEditRequest>,
+) -> impl IntoResponse {
+    let user = payload.user.as_deref().filter(|user| !user.is_empty());
+    match bulk_edit(
+        State(app_state.clone()),
+        &payload.selection,
+        &payload.action,
+        user,
+    ) {
+        Ok(changes) => {
+            if !changes.is_empty() {
+                reload_caches(&app_state, "bulk edit");
+            }
+            Json(changes).into_response()
+        }
+        Err(err) => bulk_error_response(err),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelationsRequest {
+    term_set_id: i32,
+}
+
+pub async fn handle_get_relations(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<RelationsRequest>,
+) -> impl IntoResponse {
+    match get_related_term_sets(State(app_state), params.term_set_id) {
+        Ok(related) => Json(related).into_response(),
+        Err(err) => relation_error_response(err.into()),
+    }
+}
+
+pub async fn handle_add_relation(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<TermSetRelation>,
+) -> impl IntoResponse {
+    match add_relation(State(app_state), &payload) {
+        Ok(true) => (StatusCode::OK, "Relation added").into_response(),
+        Ok(false) => (StatusCode::OK, "Relation already exists").into_response(),
+        Err(err) => relation_error_response(err),
+    }
+}
+
+pub async fn handle_remove_relation(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<TermSetRelation>,
+) -> impl IntoResponse {
+    match remove_relation(State(app_state), &params) {
+        Ok(true) => (StatusCode::OK, "Relation removed").into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Relation not found").into_response(),
+        Err(err) => relation_error_response(err.into()),
+    }
+}
+
+fn history_error_response(err: HistoryError) -> Response {
+    match err {
+        HistoryError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()).into_response(),
+        HistoryError::DeletedInRevision(_) => {
+            (StatusCode::CONFLICT, err.to_string()).into_response()
+        }
+        HistoryError::Database(err) => {
+            error!("Failed to use term history: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to use term history: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TermHistoryRequest {
+    term_id: i32,
+}
+
+pub async fn handle_get_term_history(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<TermHistoryRequest>,
+) -> impl IntoResponse {
+    match get_term_history(State(app_state), params.term_id) {
+        Ok(history) => Json(history).into_response(),
+        Err(err) => history_error_response(err.into()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevertTermRequest {
+    revision_id: i64,
+    user: Option<String>,
+}
+
+pub async fn handle_revert_term(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<RevertTermRequest>,
+) -> impl IntoResponse {
+    let user = payload.user.as_deref().filter(|user| !user.is_empty());
+    match revert_term(State(app_state.clone()), payload.revision_id, user) {
+        Ok(term_id) => {
+            reload_caches(&app_state, "revert");
+            Json(term_id).into_response()
+        }
+        Err(err) => history_error_response(err),
+    }
+}
+
+#[derive(Template)]
+#[template(path = "duplicates.html")]
+pub struct DuplicatesTemplate {
+    pub groups: Vec<DuplicateGroup>,
+    pub options: DuplicateOptions,
+}
+
+/// Runs the duplicate finder off the async runtime, since the fuzzy pass
+/// compares every pair of similar-length spellings of a language.
+async fn find_duplicates_blocking(
+    app_state: Arc<AppState>,
+    options: DuplicateOptions,
+) -> Result<Vec<DuplicateGroup>, String> {
+    tokio::task::spawn_blocking(move || {
+        find_duplicates(State(app_state), &options).map_err(|err| err.to_string())
+    })
+    .await
+    .map_err(|err| err.to_string())?
+}
+
+pub async fn handle_duplicates(
+    State(app_state): State<Arc<AppState>>,
+    Query(options): Query<DuplicateOptions>,
+) -> Html<String> {
+    info!("Serving duplicate finder.");
+    match find_duplicates_blocking(app_state, options.clone()).await {
+        Ok(groups) => Html(
+            DuplicatesTemplate { groups, options }
+                .render()
+                .unwrap_or_else(|_| "Template rendering error".to_string()),
+        ),
+        Err(err) => {
+            error!("Failed to find duplicates: {}", err);
+            Html(format!("<h1>Failed to find duplicates: {err}</h1>"))
+        }
+    }
+}
+
+pub async fn handle_get_duplicate_terms(
+    State(app_state): State<Arc<AppState>>,
+    Query(options): Query<DuplicateOptions>,
+) -> impl IntoResponse {
+    match find_duplicates_blocking(app_state, options).await {
+        Ok(groups) => Json(groups).into_response(),
+        Err(err) => {
+            error!("Failed to find duplicates: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to find duplicates: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "recycle_bin.html")]
+pub struct RecycleBinTemplate {
+    pub terms: Vec<DeletedTerm>,
+}
+
+pub async fn handle_recycle_bin(State(app_state): State<Arc<AppState>>) -> Html<String> {
+    info!("Serving recycle bin.");
+    match get_deleted_terms(State(app_state)) {
+        Ok(terms) => Html(
+            RecycleBinTemplate { terms }
+                .render()
+                .unwrap_or_else(|_| "Template rendering error".to_string()),
+        ),
+        Err(err) => {
+            error!("Failed to get deleted terms: {}", err);
+            Html(format!("<h1>Failed to get deleted terms: {err}</h1>"))
+        }
+    }
+}
+
+pub async fn handle_get_deleted_terms(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    match get_deleted_terms(State(app_state)) {
+        Ok(terms) => Json(terms).into_response(),
+        Err(err) => {
+            error!("Failed to get deleted terms: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to get deleted terms: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreTermRequest {
+    term_id: i32,
+    user: Option<String>,
+}
+
+pub async fn handle_restore_term(
+    State(app_state): State<Arc<AppState>>,
+    Json(payload): Json<RestoreTermRequest>,
+) -> impl IntoResponse {
+    let user = payload.user.as_deref().filter(|user| !user.is_empty());
+    match restore_term(State(app_state.clone()), payload.term_id, user) {
+        Ok(true) => {
+            reload_caches(&app_state, "restore");
+            (StatusCode::OK, "Term restored").into_response()
+        }
+        Ok(false) => (StatusCode::NOT_FOUND, "Term not in the recycle bin").into_response(),
+        Err(err) => {
+            error!("Failed to restore term: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to restore term: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PurgeTermRequest {
+    term_id: Option<i32>,
+    #[serde(default)]
+    all: bool,
+}
+
+/// Purges one term from the recycle bin, or empties it with `all=true`.
+pub async fn handle_purge_term(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<PurgeTermRequest>,
+) -> impl IntoResponse {
+    let purged = match (params.term_id, params.all) {
+        (Some(term_id), false) => purge_term(State(app_state), term_id).map(usize::from),
+        (None, true) => purge_expired(State(app_state), 0),
+        (Some(_), true) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Pass either term_id or all=true, not both",
+            )
+                .into_response()
+        }
+        (None, false) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                "Missing term_id, pass all=true to empty the recycle bin",
+            )
+                .into_response()
+        }
+    };
+    match purged {
+        Ok(0) if params.term_id.is_some() => {
+            (StatusCode::NOT_FOUND, "Term not in the recycle bin").into_response()
+        }
+        Ok(purged) => Json(purged).into_response(),
+        Err(err) => {
+            error!("Failed to purge terms: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to purge terms: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "database_management.html")]
+struct DatabaseManagementTemplate;
+
+pub async fn handle_database_management() -> Html<String> {
+    info!("Serving database management form.");
+    let template = DatabaseManagementTemplate;
+    Html(
+        template
+            .render()
+            .unwrap_or_else(|_| "Template rendering error".to_string()),
+    )
+}
+
+pub async fn handle_download_db_file(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    let path = &app_state.db_info.path();
+
+    if !Path::new(path).exists() {
+        error!("Database file not found: {}", path);
+        return (StatusCode::NOT_FOUND, "Database file not found").into_response();
+    }
+
+    info!("Sending database file: {}", path);
+
+    match File::open(path).await {
+        Ok(mut file) => {
+            let mut buffer = Vec::new();
+            if let Err(err) = file.read_to_end(&mut buffer).await {
+                error!("Failed to read the database file: {}", err);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to read the database file: {err}"),
+                )
+                    .into_response();
+            }
+
+            let file_name = Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("database.db");
+
+            let mut response = Response::new(buffer.into());
+
+            response.headers_mut().insert(
+                axum::http::header::CONTENT_TYPE,
+                HeaderValue::from_static("application/octet-stream"),
+            );
+
+            response.headers_mut().insert(
+                axum::http::header::CONTENT_DISPOSITION,
+                HeaderValue::from_str(&format!("attachment; filename=\"{file_name}\"")).unwrap(),
+            );
+
+            info!("Database file sent successfully.");
+            response
+        }
+        Err(err) => {
+            error!("Failed to open the database file: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to open the database file: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn handle_upload_db_file(
+    State(app_state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let db_file_path = &app_state.db_info.path();
     info!("Starting database file upload.");
 
     if let Some(field) = multipart.next_field().await.unwrap_or(None) {
@@ -810,6 +2421,33 @@ pub async fn handle_upload_db_file(
 
         info!("Database file uploaded successfully: {}", db_file_path);
 
+        if let Err(err) = create_terms_table(State(app_state.clone())) {
+            error!("Failed to update terms table of uploaded DB: {}", err);
+        }
+
+        if let Err(err) = create_fulltext_index(State(app_state.clone())) {
+            error!("Failed to build full-text index for uploaded DB: {}", err);
+        }
+
+        if let Err(err) = create_picklist_tables(State(app_state.clone())) {
+            error!("Failed to create picklist tables for uploaded DB: {}", err);
+        }
+
+        if let Err(err) = create_relations_table(State(app_state.clone())) {
+            error!("Failed to create relations table for uploaded DB: {}", err);
+        }
+
+        if let Err(err) = create_history_table(State(app_state.clone())) {
+            error!("Failed to create history table for uploaded DB: {}", err);
+        }
+
+        if let Err(err) = create_unique_values_tables(State(app_state.clone()))
+            .and_then(|_| create_facet_index(State(app_state.clone())))
+            .and_then(|_| extract_and_insert_unique_values(State(app_state.clone())))
+        {
+            error!("Failed to rebuild facet counts for uploaded DB: {}", err);
+        }
+
         clear_cache();
 
         match get_all_terms(State(app_state.clone())) {
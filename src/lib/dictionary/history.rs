@@ -0,0 +1,437 @@
+use axum::extract::State;
+use chrono::DateTime;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+use crate::dictionary::database::{connect_db, current_epoch, AppState};
+use crate::dictionary::taxonomy::resolve_subject;
+
+/// The columns of a term that are kept in its revisions.
+pub const REVISION_FIELDS: [&str; 22] = [
+    "term_set_id",
+    "term",
+    "language",
+    "term_type",
+    "creator_id",
+    "creation_timestamp",
+    "updater_id",
+    "update_timestamp",
+    "subject",
+    "source",
+    "user",
+    "attributes",
+    "remark",
+    "url",
+    "context",
+    "definition",
+    "status",
+    "part_of_speech",
+    "gender",
+    "number",
+    "inflection",
+    "subject_id",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RevisionAction {
+    Insert,
+    Update,
+    Delete,
+    Restore,
+    Purge,
+}
+
+impl RevisionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevisionAction::Insert => "insert",
+            RevisionAction::Update => "update",
+            RevisionAction::Delete => "delete",
+            RevisionAction::Restore => "restore",
+            RevisionAction::Purge => "purge",
+        }
+    }
+
+    fn parse(value: &str) -> Option<RevisionAction> {
+        match value {
+            "insert" => Some(RevisionAction::Insert),
+            "update" => Some(RevisionAction::Update),
+            "delete" => Some(RevisionAction::Delete),
+            "restore" => Some(RevisionAction::Restore),
+            "purge" => Some(RevisionAction::Purge),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HistoryError {
+    NotFound(i64),
+    DeletedInRevision(i64),
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::NotFound(id) => write!(f, "revision {id} not found"),
+            HistoryError::DeletedInRevision(id) => {
+                write!(f, "revision {id} deletes the term, pick an earlier one")
+            }
+            HistoryError::Database(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+impl From<rusqlite::Error> for HistoryError {
+    fn from(err: rusqlite::Error) -> Self {
+        HistoryError::Database(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl FieldChange {
+    pub fn old_or_default(&self) -> &str {
+        self.old.as_deref().unwrap_or("")
+    }
+
+    pub fn new_or_default(&self) -> &str {
+        self.new.as_deref().unwrap_or("")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    pub revision_id: i64,
+    pub term_id: i32,
+    pub action: RevisionAction,
+    pub changed_by: Option<String>,
+    pub changed_at: i64,
+    pub old_values: Option<Map<String, Value>>,
+    pub new_values: Option<Map<String, Value>>,
+    pub changes: Vec<FieldChange>,
+}
+
+impl Revision {
+    pub fn changed_by_or_default(&self) -> &str {
+        self.changed_by.as_deref().unwrap_or("Unknown")
+    }
+
+    pub fn changed_date(&self) -> String {
+        DateTime::from_timestamp(self.changed_at, 0).map_or(self.changed_at.to_string(), |date| {
+            date.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+        })
+    }
+}
+
+/// Whether a field counts as a change of the term. `update_timestamp` does
+/// not, since saving a term sets it even when nothing else changes.
+fn is_tracked(field: &str) -> bool {
+    field != "update_timestamp"
+}
+
+fn json_object<'a>(row: &str, fields: impl Iterator<Item = &'a &'a str>) -> String {
+    let pairs: Vec<String> = fields
+        .map(|field| format!("'{field}', {row}.{field}"))
+        .collect();
+    format!("json_object({})", pairs.join(", "))
+}
+
+/// Creates the revisions table and (re)creates the triggers that record every
+/// insert, update and delete on the terms table, so changes made by imports,
+/// merges and subject renames are kept as well. Moving a term to the recycle
+/// bin and back is recorded as a delete and a restore, removing it for good as
+/// a purge. Updates that change nothing but `update_timestamp` are not
+/// recorded. The triggers credit an update to its `updater_id` only when the
+/// update set a different one, never to the previous editor; writers that know
+/// who is editing sign their revisions with `sign_revisions_since`.
+pub fn create_history_table(State(app_state): State<Arc<AppState>>) -> Result<(), rusqlite::Error> {
+    let table = &app_state.db_info.table_name;
+    debug!("Create history triggers on: {}", table);
+    let conn = connect_db(State(app_state.clone()))?;
+    let old_values = json_object("old", REVISION_FIELDS.iter());
+    let new_values = json_object("new", REVISION_FIELDS.iter());
+    let old_tracked = json_object("old", REVISION_FIELDS.iter().filter(|f| is_tracked(f)));
+    let new_tracked = json_object("new", REVISION_FIELDS.iter().filter(|f| is_tracked(f)));
+
+    conn.execute_batch(&format!(
+        "
+        BEGIN;
+
+        CREATE TABLE IF NOT EXISTS term_revisions (
+            revision_id INTEGER PRIMARY KEY AUTOINCREMENT,
+            term_id INTEGER NOT NULL,
+            action TEXT NOT NULL,
+            changed_by TEXT,
+            changed_at INTEGER NOT NULL,
+            old_values TEXT,
+            new_values TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS term_revisions_term_id ON term_revisions (term_id);
+
+        DROP TRIGGER IF EXISTS {table}_history_after_insert;
+        DROP TRIGGER IF EXISTS {table}_history_after_update;
+        DROP TRIGGER IF EXISTS {table}_history_after_delete;
+
+        CREATE TRIGGER {table}_history_after_insert AFTER INSERT ON {table} BEGIN
+            INSERT INTO term_revisions (term_id, action, changed_by, changed_at, new_values)
+            VALUES (new.term_id, 'insert', COALESCE(new.creator_id, new.updater_id),
+                CAST(strftime('%s', 'now') AS INTEGER), {new_values});
+        END;
+
+        CREATE TRIGGER {table}_history_after_update AFTER UPDATE ON {table}
+        WHEN {old_tracked} IS NOT {new_tracked} OR old.deleted IS NOT new.deleted BEGIN
+            INSERT INTO term_revisions (term_id, action, changed_by, changed_at, old_values, new_values)
+            VALUES (
+                new.term_id,
+                CASE WHEN new.deleted > old.deleted THEN 'delete'
+                    WHEN new.deleted < old.deleted THEN 'restore'
+                    ELSE 'update' END,
+                CASE WHEN new.deleted > old.deleted THEN new.deleted_by
+                    WHEN new.updater_id IS NOT old.updater_id THEN new.updater_id
+                    ELSE NULL END,
+                CAST(strftime('%s', 'now') AS INTEGER),
+                CASE WHEN new.deleted < old.deleted THEN NULL ELSE {old_values} END,
+                CASE WHEN new.deleted > old.deleted THEN NULL ELSE {new_values} END
+            );
+        END;
+
+        CREATE TRIGGER {table}_history_after_delete AFTER DELETE ON {table} BEGIN
+            INSERT INTO term_revisions (term_id, action, changed_at, old_values)
+            VALUES (old.term_id, 'purge', CAST(strftime('%s', 'now') AS INTEGER), {old_values});
+        END;
+
+        COMMIT;
+        "
+    ))?;
+    Ok(())
+}
+
+fn json_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn parse_values(values: Option<String>) -> Option<Map<String, Value>> {
+    values.and_then(|values| serde_json::from_str(&values).ok())
+}
+
+/// The fields that differ between two revisions of a term. `subject_id` is
+/// left out as it follows `subject`, `update_timestamp` as it is not tracked.
+pub fn field_changes(
+    old_values: Option<&Map<String, Value>>,
+    new_values: Option<&Map<String, Value>>,
+) -> Vec<FieldChange> {
+    REVISION_FIELDS
+        .iter()
+        .filter(|field| **field != "subject_id" && is_tracked(field))
+        .filter_map(|field| {
+            let old = old_values
+                .and_then(|values| values.get(*field))
+                .and_then(json_text);
+            let new = new_values
+                .and_then(|values| values.get(*field))
+                .and_then(json_text);
+            (old != new).then(|| FieldChange {
+                field: field.to_string(),
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+fn revision_from_row(row: &rusqlite::Row) -> Result<Option<Revision>, rusqlite::Error> {
+    let action: String = row.get(2)?;
+    let Some(action) = RevisionAction::parse(&action) else {
+        return Ok(None);
+    };
+    let old_values = parse_values(row.get(5)?);
+    let new_values = parse_values(row.get(6)?);
+    Ok(Some(Revision {
+        revision_id: row.get(0)?,
+        term_id: row.get(1)?,
+        action,
+        changed_by: row.get(3)?,
+        changed_at: row.get(4)?,
+        changes: field_changes(old_values.as_ref(), new_values.as_ref()),
+        old_values,
+        new_values,
+    }))
+}
+
+const REVISION_COLUMNS: &str =
+    "revision_id, term_id, action, changed_by, changed_at, old_values, new_values";
+
+/// The revisions of a term, newest first. Deleted terms keep their history.
+pub fn get_term_history(
+    State(app_state): State<Arc<AppState>>,
+    term_id: i32,
+) -> Result<Vec<Revision>, rusqlite::Error> {
+    let conn = connect_db(State(app_state))?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {REVISION_COLUMNS} FROM term_revisions
+         WHERE term_id = ?1 ORDER BY revision_id DESC"
+    ))?;
+    let revisions = stmt
+        .query_map(params![term_id], revision_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(revisions.into_iter().flatten().collect())
+}
+
+pub fn get_revision(
+    State(app_state): State<Arc<AppState>>,
+    revision_id: i64,
+) -> Result<Option<Revision>, rusqlite::Error> {
+    let conn = connect_db(State(app_state))?;
+    let revision = conn
+        .query_row(
+            &format!("SELECT {REVISION_COLUMNS} FROM term_revisions WHERE revision_id = ?1"),
+            params![revision_id],
+            revision_from_row,
+        )
+        .optional()?;
+    Ok(revision.flatten())
+}
+
+/// The newest revision, to hand to `sign_revisions_since` once the changes
+/// are written, or `None` when history is not kept.
+pub fn last_revision_id(conn: &Connection) -> Result<Option<i64>, rusqlite::Error> {
+    let kept: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'term_revisions')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !kept {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT COALESCE(MAX(revision_id), 0) FROM term_revisions",
+        [],
+        |row| row.get(0),
+    )
+    .map(Some)
+}
+
+/// Credits the revisions recorded after `since` to `changed_by`. The triggers
+/// cannot tell who made a change, so writers that know call this inside the
+/// write transaction that made the changes, before committing: the
+/// revisions after `since` are then exactly the ones it recorded.
+pub fn sign_revisions_since(
+    conn: &Connection,
+    since: Option<i64>,
+    changed_by: &str,
+) -> Result<(), rusqlite::Error> {
+    if let Some(since) = since {
+        conn.execute(
+            "UPDATE term_revisions SET changed_by = ?2 WHERE revision_id > ?1",
+            params![since, changed_by],
+        )?;
+    }
+    Ok(())
+}
+
+fn sql_value(value: Option<&Value>) -> SqlValue {
+    match value {
+        None | Some(Value::Null) => SqlValue::Null,
+        Some(Value::Bool(value)) => SqlValue::Integer(*value as i64),
+        Some(Value::Number(number)) => match number.as_i64() {
+            Some(number) => SqlValue::Integer(number),
+            None => SqlValue::Real(number.as_f64().unwrap_or_default()),
+        },
+        Some(Value::String(text)) => SqlValue::Text(text.clone()),
+        Some(other) => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// Restores a term to the values it had after `revision_id`, taking it out of
+/// the recycle bin or recreating it when it has been purged since. The revert
+/// itself is recorded as a new revision by `changed_by`, unless it changes
+/// nothing. Returns the term id.
+pub fn revert_term(
+    State(app_state): State<Arc<AppState>>,
+    revision_id: i64,
+    changed_by: Option<&str>,
+) -> Result<i32, HistoryError> {
+    let revision = get_revision(State(app_state.clone()), revision_id)?
+        .ok_or(HistoryError::NotFound(revision_id))?;
+    let values = revision
+        .new_values
+        .ok_or(HistoryError::DeletedInRevision(revision_id))?;
+    let table = &app_state.db_info.table_name;
+    let mut conn = connect_db(State(app_state.clone()))?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let last_revision = last_revision_id(&tx)?;
+
+    let subject = values.get("subject").and_then(json_text);
+    let subject_id = resolve_subject(&tx, subject.as_deref())?.map(|(subject_id, _)| subject_id);
+    let mut row: Vec<SqlValue> = REVISION_FIELDS
+        .iter()
+        .map(|field| match *field {
+            "updater_id" => changed_by.map_or_else(
+                || sql_value(values.get("updater_id")),
+                |changed_by| SqlValue::Text(changed_by.to_string()),
+            ),
+            "update_timestamp" => SqlValue::Integer(current_epoch()),
+            "subject_id" => subject_id.map_or(SqlValue::Null, SqlValue::Integer),
+            field => sql_value(values.get(field)),
+        })
+        .collect();
+    row.push(SqlValue::Integer(revision.term_id.into()));
+
+    let exists: bool = tx.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {table} WHERE term_id = ?1)"),
+        params![revision.term_id],
+        |row| row.get(0),
+    )?;
+    let sql = if exists {
+        let assignments: Vec<String> = REVISION_FIELDS
+            .iter()
+            .enumerate()
+            .map(|(index, field)| format!("{field} = ?{}", index + 1))
+            .collect();
+        format!(
+            "UPDATE {table} SET {}, deleted = 0, deleted_by = NULL, deleted_at = NULL
+             WHERE term_id = ?{}",
+            assignments.join(", "),
+            REVISION_FIELDS.len() + 1
+        )
+    } else {
+        let placeholders: Vec<String> = (1..=REVISION_FIELDS.len() + 1)
+            .map(|index| format!("?{index}"))
+            .collect();
+        format!(
+            "INSERT INTO {table} ({}, term_id) VALUES ({})",
+            REVISION_FIELDS.join(", "),
+            placeholders.join(", ")
+        )
+    };
+    tx.execute(&sql, params_from_iter(row))?;
+    if let Some(changed_by) = changed_by {
+        sign_revisions_since(&tx, last_revision, changed_by)?;
+    }
+    tx.commit()?;
+    info!(
+        "Reverted term {} to revision {}",
+        revision.term_id, revision_id
+    );
+    Ok(revision.term_id)
+}
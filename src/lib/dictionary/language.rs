@@ -0,0 +1,126 @@
+use icu_collator::{Collator, CollatorOptions};
+use icu_locid::Locale;
+
+/// ISO 639-2 (bibliographic and terminology) codes, as used by TermStar
+/// exports such as `nld-nl`, mapped to the ISO 639-1 codes ICU expects.
+const ISO_639_2_TO_1: &[(&str, &str)] = &[
+    ("ara", "ar"),
+    ("bul", "bg"),
+    ("cat", "ca"),
+    ("ces", "cs"),
+    ("chi", "zh"),
+    ("cze", "cs"),
+    ("dan", "da"),
+    ("deu", "de"),
+    ("dut", "nl"),
+    ("ell", "el"),
+    ("eng", "en"),
+    ("est", "et"),
+    ("fin", "fi"),
+    ("fra", "fr"),
+    ("fre", "fr"),
+    ("ger", "de"),
+    ("gre", "el"),
+    ("heb", "he"),
+    ("hrv", "hr"),
+    ("hun", "hu"),
+    ("isl", "is"),
+    ("ita", "it"),
+    ("jpn", "ja"),
+    ("kor", "ko"),
+    ("lav", "lv"),
+    ("lit", "lt"),
+    ("nld", "nl"),
+    ("nno", "nn"),
+    ("nob", "nb"),
+    ("nor", "no"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("ron", "ro"),
+    ("rum", "ro"),
+    ("rus", "ru"),
+    ("slk", "sk"),
+    ("slo", "sk"),
+    ("slv", "sl"),
+    ("spa", "es"),
+    ("srp", "sr"),
+    ("swe", "sv"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+    ("zho", "zh"),
+];
+
+/// The ISO 639-1 language of a code such as `nld-nl`, `de-DE`, `sv_SE` or
+/// `ENG`, or `None` when the code is empty.
+pub fn primary_language(code: &str) -> Option<String> {
+    let primary = code
+        .split(['-', '_'])
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    if primary.is_empty() {
+        return None;
+    }
+
+    let mapped = ISO_639_2_TO_1
+        .iter()
+        .find(|(three, _)| *three == primary)
+        .map(|(_, two)| two.to_string());
+    Some(mapped.unwrap_or(primary))
+}
+
+/// Whether two language codes name the same language, ignoring region and
+/// whether two- or three-letter codes are used.
+pub fn same_language(a: &str, b: &str) -> bool {
+    match (primary_language(a), primary_language(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Whether a stored language code satisfies a requested one. A request with a
+/// region (`eng-gb`) must match that region; a bare language (`eng`, `en`)
+/// matches every region. An empty request matches everything.
+pub fn language_matches(stored: &str, requested: &str) -> bool {
+    if requested.trim().is_empty() {
+        return true;
+    }
+    let requested_locale = locale_for_language(requested);
+    if requested_locale.id.region.is_none() {
+        same_language(stored, requested)
+    } else {
+        locale_for_language(stored) == requested_locale
+    }
+}
+
+pub fn locale_for_language(code: &str) -> Locale {
+    let Some(language) = primary_language(code) else {
+        return Locale::UND;
+    };
+    let region = code
+        .split(['-', '_'])
+        .nth(1)
+        .map(str::trim)
+        .filter(|region| region.len() == 2 || region.len() == 3)
+        .map(str::to_ascii_uppercase);
+
+    let tag = match region {
+        Some(region) => format!("{language}-{region}"),
+        None => language.clone(),
+    };
+
+    tag.parse()
+        .or_else(|_| language.parse())
+        .unwrap_or(Locale::UND)
+}
+
+/// A CLDR collator for the language, falling back to root collation for
+/// unknown or empty language codes.
+pub fn collator_for_language(code: &str) -> Collator {
+    let locale = locale_for_language(code);
+    Collator::try_new(&(&locale).into(), CollatorOptions::new()).unwrap_or_else(|_| {
+        Collator::try_new(&(&Locale::UND).into(), CollatorOptions::new())
+            .expect("root collation data is compiled in")
+    })
+}
@@ -0,0 +1,345 @@
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::debug;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::dictionary::database::{
+    search_terms_by_term_set_id, search_terms_with_options, AppState, TermsList,
+};
+use crate::dictionary::language::{collator_for_language, language_matches};
+use crate::dictionary::paging::{sort_by_field, SortField, SortOrder};
+use crate::dictionary::search::{
+    compare_hits, fold_text, rank_hits, similarity, MatchKind, SearchHit, SearchMode, SearchOptions,
+};
+
+pub const DEFAULT_LOOKUP_LIMIT: usize = 20;
+
+/// One concept matching the looked up term: the source language terms that
+/// matched and every term of the concept in the target language.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LookupResult {
+    pub term_set_id: i32,
+    pub source: Vec<SearchHit>,
+    pub targets: Vec<TermsList>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LookupOptions {
+    pub search: SearchOptions,
+    pub mode: SearchMode,
+    pub min_score: f64,
+    /// Maximum number of concepts returned.
+    pub limit: usize,
+}
+
+fn language_of(entry: &TermsList) -> &str {
+    entry.term_language_set.language.as_deref().unwrap_or("")
+}
+
+/// Ranked source-language hits for `term`, best first.
+pub fn lookup_source_hits(
+    State(app_state): State<Arc<AppState>>,
+    term: &str,
+    from: &str,
+    options: &LookupOptions,
+) -> Result<Vec<SearchHit>, rusqlite::Error> {
+    let candidate_term = match options.mode {
+        SearchMode::Fuzzy => "",
+        SearchMode::Substring | SearchMode::Fulltext => term,
+    };
+    let candidates: Vec<TermsList> =
+        search_terms_with_options(State(app_state), candidate_term, "", &options.search)?
+            .into_iter()
+            .filter(|entry| language_matches(language_of(entry), from))
+            .collect();
+
+    let mode = match options.mode {
+        SearchMode::Fuzzy => SearchMode::Fuzzy,
+        SearchMode::Substring | SearchMode::Fulltext => SearchMode::Substring,
+    };
+    Ok(rank_hits(
+        &candidates,
+        term,
+        &options.search,
+        mode,
+        options.min_score,
+    ))
+}
+
+/// Groups ranked source hits by concept, keeping the rank of each concept's
+/// best hit, and keeps at most `limit` concepts.
+pub fn group_hits_by_term_set(hits: Vec<SearchHit>, limit: usize) -> Vec<(i32, Vec<SearchHit>)> {
+    let mut groups: Vec<(i32, Vec<SearchHit>)> = Vec::new();
+    let mut positions: HashMap<i32, usize> = HashMap::new();
+
+    for hit in hits {
+        let term_set_id = hit.entry.term_set_id;
+        match positions.get(&term_set_id) {
+            Some(&position) => groups[position].1.push(hit),
+            None if groups.len() < limit => {
+                positions.insert(term_set_id, groups.len());
+                groups.push((term_set_id, vec![hit]));
+            }
+            None => {}
+        }
+    }
+
+    groups
+}
+
+pub fn lookup_terms(
+    State(app_state): State<Arc<AppState>>,
+    term: &str,
+    from: &str,
+    to: &str,
+    options: &LookupOptions,
+) -> Result<Vec<LookupResult>, rusqlite::Error> {
+    debug!("Lookup {:?} from {:?} to {:?}", term, from, to);
+    let hits = lookup_source_hits(State(app_state.clone()), term, from, options)?;
+    let collator = collator_for_language(to);
+
+    group_hits_by_term_set(hits, options.limit)
+        .into_iter()
+        .map(|(term_set_id, source)| {
+            let mut targets: Vec<TermsList> =
+                search_terms_by_term_set_id(State(app_state.clone()), term_set_id)?
+                    .into_iter()
+                    .filter(|entry| language_matches(language_of(entry), to))
+                    .collect();
+            sort_by_field(
+                &mut targets,
+                |entry| entry,
+                SortField::Term,
+                SortOrder::Asc,
+                &collator,
+            );
+            Ok(LookupResult {
+                term_set_id,
+                source,
+                targets,
+            })
+        })
+        .collect()
+}
+
+pub const DEFAULT_BATCH_MAX_RESULTS: usize = 5;
+pub const MAX_BATCH_SIZE: usize = 1000;
+
+/// How well the best match of a batch item matched. The variant order is the
+/// ranking order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMatchType {
+    Exact,
+    CaseInsensitive,
+    /// Matches once character width or accents are ignored.
+    Variant,
+    Fuzzy,
+    None,
+}
+
+/// One term of a batch. `from` and `to` default to the languages of the
+/// whole batch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchLookupEntry {
+    pub term: String,
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchLookupItem {
+    pub term: String,
+    pub from: String,
+    pub to: String,
+    pub match_type: BatchMatchType,
+    pub matches: Vec<LookupResult>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BatchLookupOptions {
+    pub search: SearchOptions,
+    /// Fall back to fuzzy matching for items without an exact or
+    /// case-insensitive match.
+    pub fuzzy: bool,
+    pub min_score: f64,
+    /// Maximum number of concepts per item.
+    pub max_results: usize,
+}
+
+struct BatchIndex {
+    sources: Vec<(TermsList, String)>,
+    by_folded_term: HashMap<String, Vec<usize>>,
+    targets: HashMap<i32, Vec<TermsList>>,
+}
+
+impl BatchIndex {
+    fn build(terms: &[TermsList], from: &str, to: &str, options: &BatchLookupOptions) -> Self {
+        let mut sources = Vec::new();
+        let mut by_folded_term: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut targets: HashMap<i32, Vec<TermsList>> = HashMap::new();
+        let collator = collator_for_language(to);
+
+        for entry in terms {
+            if language_matches(language_of(entry), to) {
+                targets
+                    .entry(entry.term_set_id)
+                    .or_default()
+                    .push(entry.clone());
+            }
+            if language_matches(language_of(entry), from) {
+                let term = entry.term_language_set.term.as_deref().unwrap_or("");
+                let folded = fold_text(term, &options.search);
+                by_folded_term
+                    .entry(folded.clone())
+                    .or_default()
+                    .push(sources.len());
+                sources.push((entry.clone(), folded));
+            }
+        }
+
+        for entries in targets.values_mut() {
+            sort_by_field(
+                entries,
+                |entry| entry,
+                SortField::Term,
+                SortOrder::Asc,
+                &collator,
+            );
+        }
+
+        BatchIndex {
+            sources,
+            by_folded_term,
+            targets,
+        }
+    }
+
+    fn lookup(
+        &self,
+        term: &str,
+        options: &BatchLookupOptions,
+    ) -> (BatchMatchType, Vec<LookupResult>) {
+        let folded_query = fold_text(term, &options.search);
+        let normalized_query: String = term.nfc().collect();
+        let case_only = SearchOptions {
+            ignore_case: true,
+            ignore_width: false,
+            ignore_accents: false,
+        };
+        let case_folded_query = fold_text(term, &case_only);
+
+        let mut hits: Vec<(BatchMatchType, SearchHit)> = self
+            .by_folded_term
+            .get(&folded_query)
+            .into_iter()
+            .flatten()
+            .map(|&index| {
+                let entry = &self.sources[index].0;
+                let source_term: String = entry
+                    .term_language_set
+                    .term
+                    .as_deref()
+                    .unwrap_or("")
+                    .nfc()
+                    .collect();
+                let match_type = if source_term == normalized_query {
+                    BatchMatchType::Exact
+                } else if options.search.ignore_case
+                    && fold_text(&source_term, &case_only) == case_folded_query
+                {
+                    BatchMatchType::CaseInsensitive
+                } else {
+                    BatchMatchType::Variant
+                };
+                (match_type, hit(entry, MatchKind::Exact, 1.0))
+            })
+            .collect();
+
+        if hits.is_empty() && options.fuzzy && !folded_query.is_empty() {
+            hits = self
+                .sources
+                .iter()
+                .filter_map(|(entry, folded_term)| {
+                    let score = similarity(&folded_query, folded_term);
+                    (score >= options.min_score)
+                        .then(|| (BatchMatchType::Fuzzy, hit(entry, MatchKind::Fuzzy, score)))
+                })
+                .collect();
+        }
+
+        hits.sort_by(|(type_a, hit_a), (type_b, hit_b)| {
+            type_a.cmp(type_b).then_with(|| compare_hits(hit_a, hit_b))
+        });
+        let match_type = hits
+            .first()
+            .map(|(match_type, _)| *match_type)
+            .unwrap_or(BatchMatchType::None);
+
+        let matches = group_hits_by_term_set(
+            hits.into_iter().map(|(_, hit)| hit).collect(),
+            options.max_results,
+        )
+        .into_iter()
+        .map(|(term_set_id, source)| LookupResult {
+            term_set_id,
+            source,
+            targets: self.targets.get(&term_set_id).cloned().unwrap_or_default(),
+        })
+        .collect();
+
+        (match_type, matches)
+    }
+}
+
+fn hit(entry: &TermsList, match_kind: MatchKind, score: f64) -> SearchHit {
+    SearchHit {
+        entry: entry.clone(),
+        match_kind,
+        score,
+        snippet: None,
+    }
+}
+
+/// Looks up many terms at once. The dictionary is read once for the whole
+/// batch and indexed once per language pair; the results keep the order of
+/// `entries`.
+pub fn batch_lookup(
+    State(app_state): State<Arc<AppState>>,
+    entries: &[BatchLookupEntry],
+    from: &str,
+    to: &str,
+    options: &BatchLookupOptions,
+) -> Result<Vec<BatchLookupItem>, rusqlite::Error> {
+    debug!(
+        "Batch lookup of {} terms from {:?} to {:?}",
+        entries.len(),
+        from,
+        to
+    );
+    let all_terms = search_terms_with_options(State(app_state), "", "", &options.search)?;
+    let mut indexes: HashMap<(String, String), BatchIndex> = HashMap::new();
+
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            let from = entry.from.as_deref().unwrap_or(from).to_string();
+            let to = entry.to.as_deref().unwrap_or(to).to_string();
+            let index = indexes
+                .entry((from.clone(), to.clone()))
+                .or_insert_with(|| BatchIndex::build(&all_terms, &from, &to, options));
+            let (match_type, matches) = index.lookup(&entry.term, options);
+            BatchLookupItem {
+                term: entry.term.clone(),
+                from,
+                to,
+                match_type,
+                matches,
+            }
+        })
+        .collect())
+}
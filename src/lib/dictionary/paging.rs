@@ -0,0 +1,242 @@
+use icu_collator::Collator;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::dictionary::database::TermsList;
+use crate::dictionary::language::collator_for_language;
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    TermId,
+    TermSetId,
+    Term,
+    Language,
+    TermType,
+    CreatorId,
+    CreationTimestamp,
+    UpdaterId,
+    UpdateTimestamp,
+    Subject,
+    Source,
+    User,
+    Attributes,
+    Remark,
+    Url,
+    Context,
+    Definition,
+    Status,
+    PartOfSpeech,
+    Gender,
+    Number,
+    Inflection,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+enum SortKey<'a> {
+    Number(i64),
+    Text(&'a str),
+}
+
+fn text_key(value: &Option<String>) -> Option<SortKey<'_>> {
+    value
+        .as_deref()
+        .filter(|value| !value.is_empty())
+        .map(SortKey::Text)
+}
+
+fn sort_key(entry: &TermsList, field: SortField) -> Option<SortKey<'_>> {
+    let set = &entry.term_language_set;
+    match field {
+        SortField::TermId => Some(SortKey::Number(entry.term_id.into())),
+        SortField::TermSetId => Some(SortKey::Number(entry.term_set_id.into())),
+        SortField::CreationTimestamp => set.creation_timestamp.map(SortKey::Number),
+        SortField::UpdateTimestamp => set.update_timestamp.map(SortKey::Number),
+        SortField::Term => text_key(&set.term),
+        SortField::Language => text_key(&set.language),
+        SortField::TermType => text_key(&set.term_type),
+        SortField::CreatorId => text_key(&set.creator_id),
+        SortField::UpdaterId => text_key(&set.updater_id),
+        SortField::Subject => text_key(&set.subject),
+        SortField::Source => text_key(&set.source),
+        SortField::User => text_key(&set.user),
+        SortField::Attributes => text_key(&set.attributes),
+        SortField::Remark => text_key(&set.remark),
+        SortField::Url => text_key(&set.url),
+        SortField::Context => text_key(&set.context),
+        SortField::Definition => text_key(&set.definition),
+        SortField::Status => set.status.map(|status| SortKey::Text(status.as_str())),
+        SortField::PartOfSpeech => set.part_of_speech.map(|pos| SortKey::Text(pos.as_str())),
+        SortField::Gender => set.gender.map(|gender| SortKey::Text(gender.as_str())),
+        SortField::Number => set.number.map(|number| SortKey::Text(number.as_str())),
+        SortField::Inflection => text_key(&set.inflection),
+    }
+}
+
+/// Orders two terms by `field`, comparing text with `collator`. Empty values
+/// sort last in either direction and ties are broken by term id, so the order
+/// of a page never changes between requests.
+pub fn compare_terms(
+    a: &TermsList,
+    b: &TermsList,
+    field: SortField,
+    order: SortOrder,
+    collator: &Collator,
+) -> Ordering {
+    let by_field = match (sort_key(a, field), sort_key(b, field)) {
+        (Some(key_a), Some(key_b)) => {
+            let ordering = match (key_a, key_b) {
+                (SortKey::Number(x), SortKey::Number(y)) => x.cmp(&y),
+                (SortKey::Text(x), SortKey::Text(y)) => collator.compare(x, y),
+                _ => Ordering::Equal,
+            };
+            match order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+
+    by_field.then_with(|| a.term_id.cmp(&b.term_id))
+}
+
+pub fn sort_by_field<T>(
+    items: &mut [T],
+    entry: impl Fn(&T) -> &TermsList,
+    field: SortField,
+    order: SortOrder,
+    collator: &Collator,
+) {
+    items.sort_by(|a, b| compare_terms(entry(a), entry(b), field, order, collator));
+}
+
+/// Sorts `items` like `sort_by_field`, but only up to `end`, the end of the
+/// requested page; the items after it are dropped unsorted. Paging happens in
+/// memory because hits are ranked and collated here rather than in SQL, so
+/// this saves a page from paying for a full sort of every hit.
+pub fn sort_page_by_field<T>(
+    items: &mut Vec<T>,
+    entry: impl Fn(&T) -> &TermsList,
+    field: SortField,
+    order: SortOrder,
+    collator: &Collator,
+    end: Option<usize>,
+) {
+    let compare = |a: &T, b: &T| compare_terms(entry(a), entry(b), field, order, collator);
+    sort_page(items, compare, end);
+}
+
+fn sort_page<T>(items: &mut Vec<T>, compare: impl Fn(&T, &T) -> Ordering, end: Option<usize>) {
+    if let Some(end) = end.filter(|end| *end < items.len()) {
+        items.select_nth_unstable_by(end, &compare);
+        items.truncate(end);
+    }
+    items.sort_by(compare);
+}
+
+fn language_key(entry: &TermsList) -> String {
+    entry.language_or_default().trim().to_lowercase()
+}
+
+/// Sorts one page of terms found for `language`, as `sort_page_by_field`
+/// does. Without a language the terms can be in several, and no single
+/// collation suits them all: unless sorting by language or by a number, the
+/// terms are grouped by language and each group uses its own collation.
+pub fn sort_page_for_language<T>(
+    items: &mut Vec<T>,
+    entry: impl Fn(&T) -> &TermsList,
+    field: SortField,
+    order: SortOrder,
+    language: &str,
+    end: Option<usize>,
+) {
+    let by_number = matches!(
+        field,
+        SortField::TermId
+            | SortField::TermSetId
+            | SortField::CreationTimestamp
+            | SortField::UpdateTimestamp
+    );
+    if !language.trim().is_empty() || by_number || field == SortField::Language {
+        let collator = collator_for_language(language);
+        sort_page_by_field(items, entry, field, order, &collator, end);
+        return;
+    }
+
+    let mut collators: HashMap<String, Collator> = HashMap::new();
+    for item in items.iter() {
+        let language = language_key(entry(item));
+        collators
+            .entry(language.clone())
+            .or_insert_with(|| collator_for_language(&language));
+    }
+    let compare = |a: &T, b: &T| {
+        let (a, b) = (entry(a), entry(b));
+        let (language_a, language_b) = (language_key(a), language_key(b));
+        // Terms without a language go last, like other empty values.
+        (language_a.is_empty(), &language_a)
+            .cmp(&(language_b.is_empty(), &language_b))
+            .then_with(|| compare_terms(a, b, field, order, &collators[&language_a]))
+    };
+    sort_page(items, compare, end);
+}
+
+pub fn paginate<T>(items: Vec<T>, offset: usize, limit: Option<usize>) -> Vec<T> {
+    let limit = limit.unwrap_or(usize::MAX);
+    items.into_iter().skip(offset).take(limit).collect()
+}
+
+pub fn clamp_limit(limit: Option<usize>) -> Option<usize> {
+    limit.map(|limit| limit.clamp(1, MAX_PAGE_SIZE))
+}
+
+/// Builds an RFC 8288 `Link` header value with first/prev/next/last links,
+/// keeping every other query parameter of the request as it was.
+pub fn page_links(
+    path: &str,
+    raw_query: Option<&str>,
+    offset: usize,
+    limit: usize,
+    total: usize,
+) -> String {
+    let other_params: Vec<(String, String)> =
+        form_urlencoded::parse(raw_query.unwrap_or("").as_bytes())
+            .filter(|(key, _)| key != "offset" && key != "limit")
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+
+    let link = |page_offset: usize, rel: &str| {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(other_params.iter())
+            .append_pair("offset", &page_offset.to_string())
+            .append_pair("limit", &limit.to_string())
+            .finish();
+        format!("<{path}?{query}>; rel=\"{rel}\"")
+    };
+
+    let last_offset = total.saturating_sub(1) / limit * limit;
+    let mut links = vec![link(0, "first")];
+    if offset > 0 {
+        links.push(link(offset.saturating_sub(limit), "prev"));
+    }
+    if offset + limit < total {
+        links.push(link(offset + limit, "next"));
+    }
+    links.push(link(last_offset, "last"));
+
+    links.join(", ")
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+
+use crate::dictionary::database::TermsList;
+use crate::import::parse::{
+    deserialize_gender, deserialize_number, deserialize_part_of_speech, deserialize_status,
+    GrammaticalGender, GrammaticalNumber, PartOfSpeech, TermLanguageSet, TermStatus,
+};
+
+#[derive(Debug)]
+pub enum UpdateError {
+    NotFound(i32),
+    /// The term changed since the version the update was based on.
+    Conflict {
+        version: i64,
+        term: Box<TermsList>,
+    },
+    /// The update would clear a field every term needs.
+    Required(&'static str),
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::NotFound(term_id) => write!(f, "term {term_id} not found"),
+            UpdateError::Conflict { version, term } => write!(
+                f,
+                "term {} was changed by someone else, it is now at version {version}",
+                term.term_id
+            ),
+            UpdateError::Required(field) => write!(f, "{field} cannot be empty"),
+            UpdateError::Database(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for UpdateError {}
+
+impl From<rusqlite::Error> for UpdateError {
+    fn from(err: rusqlite::Error) -> Self {
+        UpdateError::Database(err)
+    }
+}
+
+/// One field of a partial update. In JSON an absent field is left unchanged,
+/// `null` clears it and any other value sets it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum FieldUpdate<T> {
+    #[default]
+    Unchanged,
+    Clear,
+    Set(T),
+}
+
+impl<T> FieldUpdate<T> {
+    pub fn as_ref(&self) -> FieldUpdate<&T> {
+        match self {
+            FieldUpdate::Unchanged => FieldUpdate::Unchanged,
+            FieldUpdate::Clear => FieldUpdate::Clear,
+            FieldUpdate::Set(value) => FieldUpdate::Set(value),
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> FieldUpdate<U> {
+        match self {
+            FieldUpdate::Unchanged => FieldUpdate::Unchanged,
+            FieldUpdate::Clear => FieldUpdate::Clear,
+            FieldUpdate::Set(value) => FieldUpdate::Set(f(value)),
+        }
+    }
+}
+
+/// `None` leaves the field unchanged, as `update_term` has always done.
+impl<T> From<Option<T>> for FieldUpdate<T> {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(FieldUpdate::Unchanged, FieldUpdate::Set)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for FieldUpdate<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Option::<T>::deserialize(deserializer)?.map_or(FieldUpdate::Clear, FieldUpdate::Set))
+    }
+}
+
+fn controlled<T>(value: Option<T>) -> FieldUpdate<T> {
+    value.map_or(FieldUpdate::Clear, FieldUpdate::Set)
+}
+
+fn deserialize_status_update<'de, D>(deserializer: D) -> Result<FieldUpdate<TermStatus>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_status(deserializer).map(controlled)
+}
+
+fn deserialize_part_of_speech_update<'de, D>(
+    deserializer: D,
+) -> Result<FieldUpdate<PartOfSpeech>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_part_of_speech(deserializer).map(controlled)
+}
+
+fn deserialize_gender_update<'de, D>(
+    deserializer: D,
+) -> Result<FieldUpdate<GrammaticalGender>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_gender(deserializer).map(controlled)
+}
+
+fn deserialize_number_update<'de, D>(
+    deserializer: D,
+) -> Result<FieldUpdate<GrammaticalNumber>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_number(deserializer).map(controlled)
+}
+
+/// A partial update of a term. Setting a text field to an empty string
+/// clears it, as does an empty string for the controlled lists.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TermPatch {
+    pub language: FieldUpdate<String>,
+    pub term: FieldUpdate<String>,
+    pub term_type: FieldUpdate<String>,
+    pub creator_id: FieldUpdate<String>,
+    pub creation_timestamp: FieldUpdate<i64>,
+    pub updater_id: FieldUpdate<String>,
+    pub update_timestamp: FieldUpdate<i64>,
+    pub subject: FieldUpdate<String>,
+    pub source: FieldUpdate<String>,
+    pub user: FieldUpdate<String>,
+    pub attributes: FieldUpdate<String>,
+    pub remark: FieldUpdate<String>,
+    pub url: FieldUpdate<String>,
+    pub context: FieldUpdate<String>,
+    pub definition: FieldUpdate<String>,
+    #[serde(deserialize_with = "deserialize_status_update")]
+    pub status: FieldUpdate<TermStatus>,
+    #[serde(deserialize_with = "deserialize_part_of_speech_update")]
+    pub part_of_speech: FieldUpdate<PartOfSpeech>,
+    #[serde(deserialize_with = "deserialize_gender_update")]
+    pub gender: FieldUpdate<GrammaticalGender>,
+    #[serde(deserialize_with = "deserialize_number_update")]
+    pub number: FieldUpdate<GrammaticalNumber>,
+    pub inflection: FieldUpdate<String>,
+}
+
+impl TermPatch {
+    /// Rejects clearing the term or its language, which would leave a term
+    /// that cannot be found or shown.
+    pub fn check_required(&self) -> Result<(), UpdateError> {
+        for (field, update) in [("term", &self.term), ("language", &self.language)] {
+            let cleared = match update {
+                FieldUpdate::Unchanged => false,
+                FieldUpdate::Clear => true,
+                FieldUpdate::Set(value) => value.trim().is_empty(),
+            };
+            if cleared {
+                return Err(UpdateError::Required(field));
+            }
+        }
+        Ok(())
+    }
+
+    /// The free-text field called `field`.
+    pub fn text_field_mut(&mut self, field: &str) -> Option<&mut FieldUpdate<String>> {
+        match field {
+            "language" => Some(&mut self.language),
+            "term" => Some(&mut self.term),
+            "term_type" => Some(&mut self.term_type),
+            "creator_id" => Some(&mut self.creator_id),
+            "updater_id" => Some(&mut self.updater_id),
+            "subject" => Some(&mut self.subject),
+            "source" => Some(&mut self.source),
+            "user" => Some(&mut self.user),
+            "attributes" => Some(&mut self.attributes),
+            "remark" => Some(&mut self.remark),
+            "url" => Some(&mut self.url),
+            "context" => Some(&mut self.context),
+            "definition" => Some(&mut self.definition),
+            "inflection" => Some(&mut self.inflection),
+            _ => None,
+        }
+    }
+}
+
+/// Sets the fields that have a value and leaves the others unchanged.
+impl From<&TermLanguageSet> for TermPatch {
+    fn from(set: &TermLanguageSet) -> Self {
+        TermPatch {
+            language: set.language.clone().into(),
+            term: set.term.clone().into(),
+            term_type: set.term_type.clone().into(),
+            creator_id: set.creator_id.clone().into(),
+            creation_timestamp: set.creation_timestamp.into(),
+            updater_id: set.updater_id.clone().into(),
+            update_timestamp: set.update_timestamp.into(),
+            subject: set.subject.clone().into(),
+            source: set.source.clone().into(),
+            user: set.user.clone().into(),
+            attributes: set.attributes.clone().into(),
+            remark: set.remark.clone().into(),
+            url: set.url.clone().into(),
+            context: set.context.clone().into(),
+            definition: set.definition.clone().into(),
+            status: set.status.into(),
+            part_of_speech: set.part_of_speech.into(),
+            gender: set.gender.into(),
+            number: set.number.into(),
+            inflection: set.inflection.clone().into(),
+        }
+    }
+}
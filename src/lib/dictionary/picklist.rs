@@ -0,0 +1,395 @@
+use axum::extract::State;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+use crate::dictionary::database::{connect_db, AppState};
+use crate::dictionary::patch::{FieldUpdate, TermPatch};
+use crate::dictionary::search::{fold_text, SearchOptions};
+use crate::dictionary::taxonomy::link_subjects;
+use crate::import::parse::TermLanguageSet;
+
+/// The free-text columns that can be restricted to a picklist.
+pub const PICKLIST_FIELDS: &[&str] = &["subject", "term_type", "source", "attributes"];
+
+#[derive(Debug)]
+pub enum PicklistError {
+    UnknownField(String),
+    /// A value that is not in a closed picklist.
+    NotAllowed {
+        field: String,
+        value: String,
+    },
+    /// A merge without a value to merge into.
+    EmptyValue,
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for PicklistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PicklistError::UnknownField(field) => write!(
+                f,
+                "unknown picklist field '{field}', expected one of: {}",
+                PICKLIST_FIELDS.join(", ")
+            ),
+            PicklistError::NotAllowed { field, value } => {
+                write!(f, "'{value}' is not in the {field} picklist")
+            }
+            PicklistError::EmptyValue => write!(f, "the value to merge into cannot be empty"),
+            PicklistError::Database(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for PicklistError {}
+
+impl From<rusqlite::Error> for PicklistError {
+    fn from(err: rusqlite::Error) -> Self {
+        PicklistError::Database(err)
+    }
+}
+
+/// The allowed values of a field. A field with an empty picklist is free text.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Picklist {
+    pub field: String,
+    /// Whether values outside the picklist are accepted and added to it.
+    pub allow_new: bool,
+    pub values: Vec<String>,
+}
+
+fn fold_value(value: &str) -> String {
+    fold_text(value.trim(), &SearchOptions::default())
+}
+
+impl Picklist {
+    /// The picklist spelling of `value`, ignoring case, width and surrounding
+    /// whitespace.
+    pub fn find(&self, value: &str) -> Option<&str> {
+        let folded = fold_value(value);
+        self.values
+            .iter()
+            .find(|candidate| fold_value(candidate) == folded)
+            .map(String::as_str)
+    }
+}
+
+pub fn check_field(field: &str) -> Result<&'static str, PicklistError> {
+    PICKLIST_FIELDS
+        .iter()
+        .find(|candidate| **candidate == field)
+        .copied()
+        .ok_or_else(|| PicklistError::UnknownField(field.to_string()))
+}
+
+fn field_value<'a>(set: &'a mut TermLanguageSet, field: &str) -> &'a mut Option<String> {
+    match field {
+        "subject" => &mut set.subject,
+        "term_type" => &mut set.term_type,
+        "source" => &mut set.source,
+        _ => &mut set.attributes,
+    }
+}
+
+pub fn create_picklist_tables(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(), rusqlite::Error> {
+    debug!("Create picklist tables");
+    let conn = connect_db(State(app_state))?;
+    conn.execute_batch(
+        "
+        BEGIN;
+
+        CREATE TABLE IF NOT EXISTS picklist_fields (
+            field TEXT PRIMARY KEY,
+            allow_new INTEGER NOT NULL DEFAULT 0
+        );
+
+        CREATE TABLE IF NOT EXISTS picklist_values (
+            field TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (field, value)
+        );
+
+        COMMIT;
+        ",
+    )?;
+    Ok(())
+}
+
+fn read_picklist(conn: &Connection, field: &str) -> Result<Picklist, rusqlite::Error> {
+    let allow_new: bool = conn
+        .query_row(
+            "SELECT allow_new FROM picklist_fields WHERE field = ?1",
+            params![field],
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(false);
+    let mut stmt = conn.prepare(
+        "SELECT value FROM picklist_values WHERE field = ?1 ORDER BY value COLLATE NOCASE, value",
+    )?;
+    let values = stmt
+        .query_map(params![field], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+
+    Ok(Picklist {
+        field: field.to_string(),
+        allow_new,
+        values,
+    })
+}
+
+pub fn get_picklist(
+    State(app_state): State<Arc<AppState>>,
+    field: &str,
+) -> Result<Picklist, PicklistError> {
+    let field = check_field(field)?;
+    let conn = connect_db(State(app_state))?;
+    Ok(read_picklist(&conn, field)?)
+}
+
+/// Every picklist field, in `PICKLIST_FIELDS` order.
+pub fn get_picklists(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Vec<Picklist>, rusqlite::Error> {
+    let conn = connect_db(State(app_state))?;
+    PICKLIST_FIELDS
+        .iter()
+        .map(|field| read_picklist(&conn, field))
+        .collect()
+}
+
+/// Adds a value unless the picklist already has it in some spelling. Returns
+/// whether it was added.
+pub fn add_picklist_value(
+    State(app_state): State<Arc<AppState>>,
+    field: &str,
+    value: &str,
+) -> Result<bool, PicklistError> {
+    let field = check_field(field)?;
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(false);
+    }
+    let conn = connect_db(State(app_state))?;
+    Ok(insert_picklist_value(&conn, field, value)?)
+}
+
+/// `add_picklist_value` on a connection the caller owns, so the value can be
+/// added in the transaction that starts using it. `field` must already be
+/// checked and `value` trimmed and non-empty.
+pub fn insert_picklist_value(
+    conn: &Connection,
+    field: &str,
+    value: &str,
+) -> Result<bool, rusqlite::Error> {
+    if read_picklist(conn, field)?.find(value).is_some() {
+        return Ok(false);
+    }
+    info!("Adding {:?} to the {} picklist", value, field);
+    conn.execute(
+        "INSERT INTO picklist_values (field, value) VALUES (?1, ?2)",
+        params![field, value],
+    )?;
+    Ok(true)
+}
+
+pub fn remove_picklist_value(
+    State(app_state): State<Arc<AppState>>,
+    field: &str,
+    value: &str,
+) -> Result<bool, PicklistError> {
+    let field = check_field(field)?;
+    let conn = connect_db(State(app_state))?;
+    let removed = conn.execute(
+        "DELETE FROM picklist_values WHERE field = ?1 AND value = ?2",
+        params![field, value],
+    )?;
+    Ok(removed > 0)
+}
+
+pub fn set_allow_new(
+    State(app_state): State<Arc<AppState>>,
+    field: &str,
+    allow_new: bool,
+) -> Result<(), PicklistError> {
+    let field = check_field(field)?;
+    let conn = connect_db(State(app_state))?;
+    conn.execute(
+        "INSERT INTO picklist_fields (field, allow_new) VALUES (?1, ?2)
+         ON CONFLICT(field) DO UPDATE SET allow_new = excluded.allow_new",
+        params![field, allow_new],
+    )?;
+    Ok(())
+}
+
+/// Fills a picklist with the values the terms already use. Values that only
+/// differ in case, width or surrounding whitespace are added once, in their
+/// most used spelling. Returns the number of values added.
+pub fn seed_picklist(
+    State(app_state): State<Arc<AppState>>,
+    field: &str,
+) -> Result<usize, PicklistError> {
+    let field = check_field(field)?;
+    let table = &app_state.db_info.table_name;
+    let conn = connect_db(State(app_state.clone()))?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {field}, COUNT(*) AS uses FROM {table}
+         WHERE TRIM(COALESCE({field}, '')) != '' AND deleted = 0
+         GROUP BY {field}
+         ORDER BY uses DESC, {field}"
+    ))?;
+    let used = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+
+    let mut added = 0;
+    for value in used {
+        if add_picklist_value(State(app_state.clone()), field, &value)? {
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+/// Checks the picklist fields of a term about to be stored and rewrites them
+/// to their picklist spelling. Fields with an empty picklist are left alone.
+/// For fields with a picklist an empty value means none, and a new value is
+/// added to the picklist when it allows new values and rejected otherwise.
+pub fn apply_picklists(
+    State(app_state): State<Arc<AppState>>,
+    set: &mut TermLanguageSet,
+) -> Result<(), PicklistError> {
+    for picklist in get_picklists(State(app_state.clone()))? {
+        if picklist.values.is_empty() {
+            continue;
+        }
+        let slot = field_value(set, &picklist.field);
+        let Some(value) = slot.as_deref().map(str::trim) else {
+            continue;
+        };
+        if value.is_empty() {
+            *slot = None;
+            continue;
+        }
+
+        *slot = Some(picklist_value(State(app_state.clone()), &picklist, value)?);
+    }
+    Ok(())
+}
+
+/// The picklist spelling of a non-empty `value`, adding it to the picklist
+/// when that allows new values.
+fn picklist_value(
+    State(app_state): State<Arc<AppState>>,
+    picklist: &Picklist,
+    value: &str,
+) -> Result<String, PicklistError> {
+    match picklist.find(value) {
+        Some(known) => Ok(known.to_string()),
+        None if picklist.allow_new => {
+            add_picklist_value(State(app_state), &picklist.field, value)?;
+            Ok(value.to_string())
+        }
+        None => Err(PicklistError::NotAllowed {
+            field: picklist.field.clone(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+/// Like `apply_picklists`, for the fields a partial update sets. Setting a
+/// picklist field to an empty value clears it. New values are not added yet:
+/// they are returned for `patch_term_with_picklists` to add along with the
+/// update, so a failed update leaves the picklists as they were.
+pub fn apply_picklists_to_patch(
+    State(app_state): State<Arc<AppState>>,
+    patch: &mut TermPatch,
+) -> Result<Vec<(String, String)>, PicklistError> {
+    let picklists = get_picklists(State(app_state))?;
+    check_patch_picklists(&picklists, patch)
+}
+
+/// Checks the picklist fields a partial update sets without changing any
+/// picklist, and spells known values as in their picklist. Returns the
+/// fields and values still to be added to open picklists.
+pub fn check_patch_picklists(
+    picklists: &[Picklist],
+    patch: &mut TermPatch,
+) -> Result<Vec<(String, String)>, PicklistError> {
+    let mut new_values = Vec::new();
+    for picklist in picklists {
+        if picklist.values.is_empty() {
+            continue;
+        }
+        let Some(update) = patch.text_field_mut(&picklist.field) else {
+            continue;
+        };
+        let FieldUpdate::Set(value) = update else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        *update = if value.is_empty() {
+            FieldUpdate::Clear
+        } else if let Some(known) = picklist.find(&value) {
+            FieldUpdate::Set(known.to_string())
+        } else if picklist.allow_new {
+            new_values.push((picklist.field.clone(), value.clone()));
+            FieldUpdate::Set(value)
+        } else {
+            return Err(PicklistError::NotAllowed {
+                field: picklist.field.clone(),
+                value,
+            });
+        };
+    }
+    Ok(new_values)
+}
+
+/// Rewrites every term using one of the `from` values of `field` to `to`,
+/// and replaces them by `to` in the picklist, all in one transaction. Returns
+/// the number of terms changed.
+pub fn merge_values(
+    State(app_state): State<Arc<AppState>>,
+    field: &str,
+    from: &[String],
+    to: &str,
+) -> Result<usize, PicklistError> {
+    let field = check_field(field)?;
+    let to = to.trim();
+    if to.is_empty() {
+        return Err(PicklistError::EmptyValue);
+    }
+    let table = &app_state.db_info.table_name;
+    let mut conn = connect_db(State(app_state.clone()))?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let had_values = !read_picklist(&tx, field)?.values.is_empty();
+
+    let mut changed = 0;
+    for value in from.iter().filter(|value| value.as_str() != to) {
+        changed += tx.execute(
+            &format!("UPDATE {table} SET {field} = ?1 WHERE {field} = ?2"),
+            params![to, value],
+        )?;
+        tx.execute(
+            "DELETE FROM picklist_values WHERE field = ?1 AND value = ?2",
+            params![field, value],
+        )?;
+    }
+    if had_values {
+        insert_picklist_value(&tx, field, to)?;
+    }
+    if field == "subject" {
+        link_subjects(&tx, table)?;
+    }
+    tx.commit()?;
+    info!(
+        "Merged {:?} into {:?} for {}: {} terms changed",
+        from, to, field, changed
+    );
+    Ok(changed)
+}
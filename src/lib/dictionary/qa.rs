@@ -0,0 +1,252 @@
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::dictionary::database::{AppState, TermsList};
+use crate::dictionary::recognize::{recognize_terms, RecognizedTerm};
+use crate::dictionary::search::SearchOptions;
+use crate::import::parse::TermStatus;
+
+pub const MAX_QA_SEGMENTS: usize = 10000;
+
+/// Marks in the term type or attributes of terms without a status that must
+/// not be used in translations.
+const FORBIDDEN_MARKERS: &[&str] = &["forbidden", "deprecated", "do not use"];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Segment {
+    /// Identifies the segment in the report; defaults to its position.
+    #[serde(default)]
+    pub id: Option<String>,
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QaIssueKind {
+    /// A source term was found but none of its approved translations.
+    Missing,
+    /// The target uses a forbidden term.
+    Forbidden,
+    /// The target uses a different translation of a term than most segments.
+    Inconsistent,
+}
+
+impl QaIssueKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QaIssueKind::Missing => "missing",
+            QaIssueKind::Forbidden => "forbidden",
+            QaIssueKind::Inconsistent => "inconsistent",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QaIssue {
+    pub segment: String,
+    pub kind: QaIssueKind,
+    pub term_set_id: i32,
+    /// The source term as it appears in the source segment.
+    pub source_term: Option<String>,
+    /// The offending target terms as they appear in the target segment.
+    pub found: Vec<String>,
+    /// The translations that were expected instead.
+    pub expected: Vec<String>,
+}
+
+impl QaIssue {
+    pub fn kind_name(&self) -> &'static str {
+        self.kind.name()
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct QaReport {
+    pub checked_segments: usize,
+    pub issues: Vec<QaIssue>,
+}
+
+/// Whether a term must not be used: its status is deprecated or forbidden, or,
+/// for terms without a status, its term type or attributes say so.
+pub fn is_forbidden(entry: &TermsList) -> bool {
+    let set = &entry.term_language_set;
+    if let Some(status) = set.status {
+        return matches!(status, TermStatus::Deprecated | TermStatus::Forbidden);
+    }
+    [&set.term_type, &set.attributes]
+        .into_iter()
+        .flatten()
+        .map(|value| value.to_lowercase())
+        .any(|value| {
+            FORBIDDEN_MARKERS
+                .iter()
+                .any(|marker| value.contains(marker))
+        })
+}
+
+fn term_of(entry: &TermsList) -> String {
+    entry.term_language_set.term.clone().unwrap_or_default()
+}
+
+fn approved_terms(targets: &[TermsList]) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for target in targets.iter().filter(|target| !is_forbidden(target)) {
+        let term = term_of(target);
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms
+}
+
+/// The approved translations of `term_set_id` used in a target segment.
+fn used_translations(target_terms: &[RecognizedTerm], term_set_id: i32) -> Vec<String> {
+    let mut used: Vec<String> = Vec::new();
+    for concept in target_terms
+        .iter()
+        .flat_map(|term| &term.concepts)
+        .filter(|concept| concept.term_set_id == term_set_id)
+    {
+        for entry in concept.source.iter().filter(|entry| !is_forbidden(entry)) {
+            let term = term_of(entry);
+            if !used.contains(&term) {
+                used.push(term);
+            }
+        }
+    }
+    used
+}
+
+struct ConceptUse {
+    segment: String,
+    source_term: String,
+    used: Vec<String>,
+}
+
+/// Checks translated segments against the termbase: every `from` term found
+/// in a source needs one of its approved `to` translations in the target,
+/// forbidden `to` terms must not be used, and a term should be translated the
+/// same way in every segment.
+pub fn check_segments(
+    State(app_state): State<Arc<AppState>>,
+    segments: &[Segment],
+    from: &str,
+    to: &str,
+    options: &SearchOptions,
+) -> Result<QaReport, rusqlite::Error> {
+    debug!(
+        "Checking {} segments from {:?} to {:?}",
+        segments.len(),
+        from,
+        to
+    );
+    let mut issues = Vec::new();
+    let mut uses: HashMap<i32, Vec<ConceptUse>> = HashMap::new();
+    let mut concept_order: Vec<i32> = Vec::new();
+
+    for (index, segment) in segments.iter().enumerate() {
+        let segment_id = segment.id.clone().unwrap_or_else(|| index.to_string());
+        let source_terms =
+            recognize_terms(State(app_state.clone()), &segment.source, from, to, options)?;
+        let target_terms =
+            recognize_terms(State(app_state.clone()), &segment.target, to, to, options)?;
+
+        for source_term in &source_terms {
+            for concept in &source_term.concepts {
+                let expected = approved_terms(&concept.targets);
+                if expected.is_empty() {
+                    continue;
+                }
+                let used = used_translations(&target_terms, concept.term_set_id);
+                if used.is_empty() {
+                    issues.push(QaIssue {
+                        segment: segment_id.clone(),
+                        kind: QaIssueKind::Missing,
+                        term_set_id: concept.term_set_id,
+                        source_term: Some(source_term.text.clone()),
+                        found: Vec::new(),
+                        expected,
+                    });
+                    continue;
+                }
+                if !uses.contains_key(&concept.term_set_id) {
+                    concept_order.push(concept.term_set_id);
+                }
+                uses.entry(concept.term_set_id)
+                    .or_default()
+                    .push(ConceptUse {
+                        segment: segment_id.clone(),
+                        source_term: source_term.text.clone(),
+                        used,
+                    });
+            }
+        }
+
+        for target_term in &target_terms {
+            for concept in &target_term.concepts {
+                if concept.source.iter().any(is_forbidden) {
+                    issues.push(QaIssue {
+                        segment: segment_id.clone(),
+                        kind: QaIssueKind::Forbidden,
+                        term_set_id: concept.term_set_id,
+                        source_term: None,
+                        found: vec![target_term.text.clone()],
+                        expected: approved_terms(&concept.targets),
+                    });
+                }
+            }
+        }
+    }
+
+    for term_set_id in concept_order {
+        issues.extend(inconsistent_uses(term_set_id, &uses[&term_set_id]));
+    }
+
+    Ok(QaReport {
+        checked_segments: segments.len(),
+        issues,
+    })
+}
+
+/// Flags the segments that do not use the translation most segments use for
+/// the concept; ties go to the translation used first.
+fn inconsistent_uses(term_set_id: i32, uses: &[ConceptUse]) -> Vec<QaIssue> {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for concept_use in uses {
+        for term in &concept_use.used {
+            match counts.iter_mut().find(|(used, _)| used == term) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((term, 1)),
+            }
+        }
+    }
+    if counts.len() < 2 {
+        return Vec::new();
+    }
+    let majority = counts
+        .iter()
+        .fold(counts[0], |best, candidate| {
+            if candidate.1 > best.1 {
+                *candidate
+            } else {
+                best
+            }
+        })
+        .0;
+
+    uses.iter()
+        .filter(|concept_use| !concept_use.used.iter().any(|term| term == majority))
+        .map(|concept_use| QaIssue {
+            segment: concept_use.segment.clone(),
+            kind: QaIssueKind::Inconsistent,
+            term_set_id,
+            source_term: Some(concept_use.source_term.clone()),
+            found: concept_use.used.clone(),
+            expected: vec![majority.to_string()],
+        })
+        .collect()
+}
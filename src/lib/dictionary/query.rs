@@ -0,0 +1,649 @@
+use chrono::{Datelike, NaiveDate};
+use rusqlite::types::Value as SqlValue;
+use std::fmt;
+
+use crate::dictionary::search::{fold_text, SearchOptions};
+use crate::import::parse::{GrammaticalGender, GrammaticalNumber, PartOfSpeech, TermStatus};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Term,
+    Language,
+    TermType,
+    Creator,
+    Updater,
+    Subject,
+    Source,
+    User,
+    Attributes,
+    Remark,
+    Url,
+    Context,
+    Definition,
+    Status,
+    PartOfSpeech,
+    Gender,
+    Number,
+    Inflection,
+    Created,
+    Updated,
+    TermId,
+    TermSetId,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldKind {
+    Text,
+    Language,
+    /// A value from a controlled list, matched exactly.
+    Controlled,
+    Date,
+    Number,
+}
+
+const FIELD_NAMES: &[(&str, Field)] = &[
+    ("term", Field::Term),
+    ("lang", Field::Language),
+    ("language", Field::Language),
+    ("type", Field::TermType),
+    ("term_type", Field::TermType),
+    ("creator", Field::Creator),
+    ("updater", Field::Updater),
+    ("subject", Field::Subject),
+    ("source", Field::Source),
+    ("user", Field::User),
+    ("attributes", Field::Attributes),
+    ("remark", Field::Remark),
+    ("url", Field::Url),
+    ("context", Field::Context),
+    ("definition", Field::Definition),
+    ("status", Field::Status),
+    ("pos", Field::PartOfSpeech),
+    ("part_of_speech", Field::PartOfSpeech),
+    ("gender", Field::Gender),
+    ("number", Field::Number),
+    ("inflection", Field::Inflection),
+    ("created", Field::Created),
+    ("updated", Field::Updated),
+    ("id", Field::TermId),
+    ("set", Field::TermSetId),
+];
+
+impl Field {
+    pub fn from_name(name: &str) -> Option<Field> {
+        let name = name.to_lowercase();
+        FIELD_NAMES
+            .iter()
+            .find(|(field_name, _)| *field_name == name)
+            .map(|(_, field)| *field)
+    }
+
+    pub fn column(&self) -> &'static str {
+        match self {
+            Field::Term => "term",
+            Field::Language => "language",
+            Field::TermType => "term_type",
+            Field::Creator => "creator_id",
+            Field::Updater => "updater_id",
+            Field::Subject => "subject",
+            Field::Source => "source",
+            Field::User => "user",
+            Field::Attributes => "attributes",
+            Field::Remark => "remark",
+            Field::Url => "url",
+            Field::Context => "context",
+            Field::Definition => "definition",
+            Field::Status => "status",
+            Field::PartOfSpeech => "part_of_speech",
+            Field::Gender => "gender",
+            Field::Number => "number",
+            Field::Inflection => "inflection",
+            Field::Created => "creation_timestamp",
+            Field::Updated => "update_timestamp",
+            Field::TermId => "term_id",
+            Field::TermSetId => "term_set_id",
+        }
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Field::Language => FieldKind::Language,
+            Field::Status | Field::PartOfSpeech | Field::Gender | Field::Number => {
+                FieldKind::Controlled
+            }
+            Field::Created | Field::Updated => FieldKind::Date,
+            Field::TermId | Field::TermSetId => FieldKind::Number,
+            _ => FieldKind::Text,
+        }
+    }
+
+    /// The stored spelling of a value of a controlled field.
+    fn controlled_value(&self, value: &str) -> Option<&'static str> {
+        match self {
+            Field::Status => TermStatus::parse(value).map(|status| status.as_str()),
+            Field::PartOfSpeech => PartOfSpeech::parse(value).map(|pos| pos.as_str()),
+            Field::Gender => GrammaticalGender::parse(value).map(|gender| gender.as_str()),
+            Field::Number => GrammaticalNumber::parse(value).map(|number| number.as_str()),
+            _ => None,
+        }
+    }
+
+    fn controlled_values(&self) -> Vec<&'static str> {
+        match self {
+            Field::Status => TermStatus::ALL
+                .iter()
+                .map(|status| status.as_str())
+                .collect(),
+            Field::PartOfSpeech => PartOfSpeech::ALL.iter().map(|pos| pos.as_str()).collect(),
+            Field::Gender => GrammaticalGender::ALL
+                .iter()
+                .map(|gender| gender.as_str())
+                .collect(),
+            Field::Number => GrammaticalNumber::ALL
+                .iter()
+                .map(|number| number.as_str())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    /// `field:value`: substring for text, same day for dates, equality otherwise.
+    Matches,
+    Equals,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Comparison {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Matches => ":",
+            Comparison::Equals => "=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Text(String),
+    Date(NaiveDate),
+    Number(i64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// A bare word or a quoted phrase, matched against the term.
+    Text(String),
+    Field {
+        field: Field,
+        comparison: Comparison,
+        value: Value,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clause {
+    pub negated: bool,
+    pub condition: Condition,
+}
+
+/// A parsed advanced search query. All clauses must hold for a term to match.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    pub clauses: Vec<Clause>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryError {
+    /// 1-based character position in the query string.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn error(&self, pos: usize, message: String) -> QueryError {
+        QueryError {
+            column: pos + 1,
+            message,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, QueryError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(value);
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+                None => {
+                    return Err(self.error(start, "unterminated quoted phrase".to_string()));
+                }
+            }
+        }
+    }
+
+    fn parse_bare(&mut self) -> String {
+        let mut value = String::new();
+        while let Some(c) = self.peek().filter(|c| !c.is_whitespace()) {
+            value.push(c);
+            self.pos += 1;
+        }
+        value
+    }
+
+    fn parse_field_name(&mut self) -> Option<String> {
+        let mut len = 0;
+        while self
+            .peek_at(len)
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        {
+            len += 1;
+        }
+        if len > 0 && self.peek_at(len) == Some(':') {
+            let name: String = self.chars[self.pos..self.pos + len].iter().collect();
+            self.pos += len + 1;
+            Some(name)
+        } else {
+            None
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Comparison {
+        let (comparison, len) = match (self.peek(), self.peek_at(1)) {
+            (Some('>'), Some('=')) => (Comparison::GreaterOrEqual, 2),
+            (Some('<'), Some('=')) => (Comparison::LessOrEqual, 2),
+            (Some('>'), _) => (Comparison::Greater, 1),
+            (Some('<'), _) => (Comparison::Less, 1),
+            (Some('='), _) => (Comparison::Equals, 1),
+            _ => (Comparison::Matches, 0),
+        };
+        self.pos += len;
+        comparison
+    }
+
+    fn parse_clause(&mut self) -> Result<Clause, QueryError> {
+        let negated =
+            self.peek() == Some('-') && self.peek_at(1).is_some_and(|c| !c.is_whitespace());
+        if negated {
+            self.pos += 1;
+        }
+
+        if self.peek() == Some('"') {
+            let phrase = self.parse_quoted()?;
+            return Ok(Clause {
+                negated,
+                condition: Condition::Text(phrase),
+            });
+        }
+
+        let field_start = self.pos;
+        if let Some(name) = self.parse_field_name() {
+            let field = Field::from_name(&name).ok_or_else(|| {
+                let known: Vec<&str> = FIELD_NAMES.iter().map(|(name, _)| *name).collect();
+                self.error(
+                    field_start,
+                    format!(
+                        "unknown field '{name}', expected one of: {}",
+                        known.join(", ")
+                    ),
+                )
+            })?;
+            let comparison = self.parse_comparison();
+            let value_start = self.pos;
+            let raw_value = if self.peek() == Some('"') {
+                self.parse_quoted()?
+            } else {
+                self.parse_bare()
+            };
+            if raw_value.is_empty() {
+                return Err(self.error(value_start, format!("missing value for field '{name}'")));
+            }
+            let value = self.typed_value(field, &name, comparison, raw_value, value_start)?;
+            return Ok(Clause {
+                negated,
+                condition: Condition::Field {
+                    field,
+                    comparison,
+                    value,
+                },
+            });
+        }
+
+        let word = self.parse_bare();
+        Ok(Clause {
+            negated,
+            condition: Condition::Text(word),
+        })
+    }
+
+    fn typed_value(
+        &self,
+        field: Field,
+        name: &str,
+        comparison: Comparison,
+        raw_value: String,
+        value_start: usize,
+    ) -> Result<Value, QueryError> {
+        match field.kind() {
+            FieldKind::Text | FieldKind::Language | FieldKind::Controlled => {
+                if !matches!(comparison, Comparison::Matches | Comparison::Equals) {
+                    return Err(self.error(
+                        value_start - comparison.symbol().len(),
+                        format!(
+                            "operator '{}' is not supported for text field '{name}'",
+                            comparison.symbol()
+                        ),
+                    ));
+                }
+                if field.kind() != FieldKind::Controlled {
+                    return Ok(Value::Text(raw_value));
+                }
+                field
+                    .controlled_value(&raw_value)
+                    .map(|value| Value::Text(value.to_string()))
+                    .ok_or_else(|| {
+                        self.error(
+                            value_start,
+                            format!(
+                                "invalid value '{raw_value}' for field '{name}', expected one of: {}",
+                                field.controlled_values().join(", ")
+                            ),
+                        )
+                    })
+            }
+            FieldKind::Date => parse_date(&raw_value).map(Value::Date).ok_or_else(|| {
+                self.error(
+                    value_start,
+                    format!("invalid date '{raw_value}' for field '{name}', expected YYYY-MM-DD"),
+                )
+            }),
+            FieldKind::Number => raw_value.parse().map(Value::Number).map_err(|_| {
+                self.error(
+                    value_start,
+                    format!("invalid number '{raw_value}' for field '{name}'"),
+                )
+            }),
+        }
+    }
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+fn day_start(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()
+}
+
+pub fn parse_query(input: &str) -> Result<Query, QueryError> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+    };
+    let mut clauses = Vec::new();
+
+    loop {
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            break;
+        }
+        clauses.push(parser.parse_clause()?);
+    }
+
+    Ok(Query { clauses })
+}
+
+impl Query {
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    /// The positive bare words and phrases, which are what the user is
+    /// looking for as opposed to filtering on.
+    pub fn free_text(&self) -> String {
+        self.clauses
+            .iter()
+            .filter(|clause| !clause.negated)
+            .filter_map(|clause| match &clause.condition {
+                Condition::Text(text) => Some(text.as_str()),
+                Condition::Field { .. } => None,
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Compiles the query into an SQL boolean expression over the terms
+    /// table plus its parameters, in order, for the anonymous `?` placeholders.
+    pub fn to_sql(&self, options: &SearchOptions) -> (String, Vec<SqlValue>) {
+        if self.clauses.is_empty() {
+            return ("1 = 1".to_string(), Vec::new());
+        }
+
+        let mut params = Vec::new();
+        let conditions: Vec<String> = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                let sql = condition_to_sql(&clause.condition, options, &mut params);
+                if clause.negated {
+                    match nullable_column(&clause.condition) {
+                        // `NOT` of a comparison with NULL is still NULL, so
+                        // terms without a value have to be let through.
+                        Some(column) => format!("({column} IS NULL OR NOT ({sql}))"),
+                        None => format!("NOT ({sql})"),
+                    }
+                } else {
+                    format!("({sql})")
+                }
+            })
+            .collect();
+
+        (conditions.join(" AND "), params)
+    }
+}
+
+/// The column a condition compares as is, when it can be NULL. Text columns
+/// are compared through `COALESCE` and never are.
+fn nullable_column(condition: &Condition) -> Option<&'static str> {
+    match condition {
+        Condition::Field { field, .. }
+            if matches!(field.kind(), FieldKind::Date | FieldKind::Number) =>
+        {
+            Some(field.column())
+        }
+        _ => None,
+    }
+}
+
+fn folded_column(column: &str, options: &SearchOptions, params: &mut Vec<SqlValue>) -> String {
+    params.push(SqlValue::Integer(options.flags()));
+    format!("ts_fold(COALESCE({column}, ''), ?)")
+}
+
+fn text_condition(
+    column: &str,
+    text: &str,
+    comparison: Comparison,
+    options: &SearchOptions,
+    params: &mut Vec<SqlValue>,
+) -> String {
+    let folded = folded_column(column, options, params);
+    params.push(SqlValue::Text(fold_text(text, options)));
+    if comparison == Comparison::Equals {
+        format!("{folded} = ?")
+    } else {
+        format!("instr({folded}, ?) > 0")
+    }
+}
+
+fn condition_to_sql(
+    condition: &Condition,
+    options: &SearchOptions,
+    params: &mut Vec<SqlValue>,
+) -> String {
+    match condition {
+        Condition::Text(text) => {
+            let folded = folded_column("term", options, params);
+            params.push(SqlValue::Text(fold_text(text, options)));
+            format!("instr({folded}, ?) > 0")
+        }
+        Condition::Field {
+            field,
+            comparison,
+            value,
+        } => {
+            let column = field.column();
+            match (field.kind(), value) {
+                (FieldKind::Text, Value::Text(text)) => {
+                    let sql = text_condition(column, text, *comparison, options, params);
+                    if *field != Field::Subject {
+                        return sql;
+                    }
+                    // A subject also matches the terms filed under any
+                    // subject below it in the taxonomy.
+                    let names = text_condition("name", text, *comparison, options, params);
+                    format!(
+                        "{sql} OR COALESCE(subject_id, 0) IN (
+                            WITH RECURSIVE matched(id) AS (
+                                SELECT id FROM subjects WHERE {names}
+                                UNION
+                                SELECT subjects.id FROM subjects
+                                JOIN matched ON subjects.parent_id = matched.id
+                            )
+                            SELECT id FROM matched
+                        )"
+                    )
+                }
+                (FieldKind::Language, Value::Text(text)) => {
+                    let folded_value = fold_text(text, options);
+                    let exact = folded_column(column, options, params);
+                    params.push(SqlValue::Text(folded_value.clone()));
+                    if *comparison == Comparison::Equals {
+                        return format!("{exact} = ?");
+                    }
+                    let prefix = folded_column(column, options, params);
+                    params.push(SqlValue::Text(format!("{folded_value}-")));
+                    format!("{exact} = ? OR instr({prefix}, ?) = 1")
+                }
+                (FieldKind::Controlled, Value::Text(text)) => {
+                    params.push(SqlValue::Text(text.clone()));
+                    format!("COALESCE({column}, '') = ?")
+                }
+                (FieldKind::Date, Value::Date(date)) => {
+                    let start = day_start(*date);
+                    let next = day_start(date.succ_opt().unwrap_or(*date));
+                    let (sql, bounds) = match comparison {
+                        Comparison::Matches | Comparison::Equals => {
+                            (format!("{column} >= ? AND {column} < ?"), vec![start, next])
+                        }
+                        Comparison::Greater => (format!("{column} >= ?"), vec![next]),
+                        Comparison::GreaterOrEqual => (format!("{column} >= ?"), vec![start]),
+                        Comparison::Less => (format!("{column} < ?"), vec![start]),
+                        Comparison::LessOrEqual => (format!("{column} < ?"), vec![next]),
+                    };
+                    params.extend(bounds.into_iter().map(SqlValue::Integer));
+                    sql
+                }
+                (FieldKind::Number, Value::Number(number)) => {
+                    params.push(SqlValue::Integer(*number));
+                    let symbol = match comparison {
+                        Comparison::Matches => "=",
+                        other => other.symbol(),
+                    };
+                    format!("{column} {symbol} ?")
+                }
+                _ => "0 = 1".to_string(),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                let negation = if clause.negated { "-" } else { "" };
+                match &clause.condition {
+                    Condition::Text(text) => format!("{negation}{}", quote(text)),
+                    Condition::Field {
+                        field,
+                        comparison,
+                        value,
+                    } => {
+                        let name = FIELD_NAMES
+                            .iter()
+                            .find(|(_, candidate)| candidate == field)
+                            .map(|(name, _)| *name)
+                            .unwrap_or("term");
+                        let symbol = match comparison {
+                            Comparison::Matches => "",
+                            other => other.symbol(),
+                        };
+                        let value = match value {
+                            Value::Text(text) => quote(text),
+                            Value::Date(date) => {
+                                format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day())
+                            }
+                            Value::Number(number) => number.to_string(),
+                        };
+                        format!("{negation}{name}:{symbol}{value}")
+                    }
+                }
+            })
+            .collect();
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+fn quote(text: &str) -> String {
+    if text.is_empty() || text.contains(char::is_whitespace) || text.contains(':') {
+        format!("\"{text}\"")
+    } else {
+        text.to_string()
+    }
+}
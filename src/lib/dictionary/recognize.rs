@@ -0,0 +1,413 @@
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind as AhoMatchKind};
+use axum::extract::State;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tracing::{debug, info};
+use unicode_normalization::char::is_combining_mark;
+
+use crate::dictionary::database::{
+    connect_db, search_terms_by_languages, search_terms_by_term_set_id, AppState, TermsList,
+};
+use crate::dictionary::language::{collator_for_language, language_matches};
+use crate::dictionary::paging::{sort_by_field, SortField, SortOrder};
+use crate::dictionary::search::{fold_text, SearchOptions};
+
+lazy_static! {
+    static ref RECOGNIZERS: RwLock<HashMap<(String, String, i64), CachedRecognizer>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Bumped whenever terms change, so cached automatons know to check whether
+/// their language was affected.
+static TERMS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// The live terms of one stored language, summed up cheaply enough to tell
+/// whether any of them was added, changed or removed: every change of a term
+/// bumps its version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LanguageStats {
+    language: String,
+    count: i64,
+    term_ids: i64,
+    versions: i64,
+}
+
+struct CachedRecognizer {
+    recognizer: Arc<Recognizer>,
+    languages: Vec<LanguageStats>,
+    generation: u64,
+}
+
+/// Drops every cached automaton; each language is rebuilt on its next use.
+pub fn clear_recognizers() {
+    RECOGNIZERS.write().unwrap().clear();
+}
+
+/// Marks the cached automatons as possibly out of date. On its next use each
+/// one compares the terms of its own languages with what it was built from
+/// and is rebuilt only when they differ, so a change to German terms keeps
+/// the English automaton. An automaton is never updated in place: a language
+/// that changed is rebuilt from its terms.
+pub fn terms_changed() {
+    TERMS_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// A concept found in the text, with its source-language terms spelled as
+/// found and its target-language terms.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecognizedConcept {
+    pub term_set_id: i32,
+    pub source: Vec<TermsList>,
+    pub targets: Vec<TermsList>,
+}
+
+/// A span of the text that matches one or more terms. `start` and `end` count
+/// characters (Unicode scalar values), not bytes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecognizedTerm {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    pub concepts: Vec<RecognizedConcept>,
+}
+
+/// Text folded cluster by cluster, remembering which original cluster every
+/// folded byte came from.
+struct FoldedText {
+    text: String,
+    origins: Vec<usize>,
+    clusters: Vec<Cluster>,
+}
+
+struct Cluster {
+    byte_start: usize,
+    byte_end: usize,
+    char_start: usize,
+    char_end: usize,
+}
+
+impl FoldedText {
+    /// Folds a base character together with its combining marks so decomposed
+    /// input folds the same way as the precomposed terms.
+    fn new(text: &str, options: &SearchOptions) -> Self {
+        let mut clusters: Vec<Cluster> = Vec::new();
+        for (char_index, (byte_index, c)) in text.char_indices().enumerate() {
+            match clusters.last_mut() {
+                Some(cluster) if is_combining_mark(c) => {
+                    cluster.byte_end = byte_index + c.len_utf8();
+                    cluster.char_end = char_index + 1;
+                }
+                _ => clusters.push(Cluster {
+                    byte_start: byte_index,
+                    byte_end: byte_index + c.len_utf8(),
+                    char_start: char_index,
+                    char_end: char_index + 1,
+                }),
+            }
+        }
+
+        let mut folded = String::new();
+        let mut origins = Vec::new();
+        for (index, cluster) in clusters.iter().enumerate() {
+            let piece = fold_text(&text[cluster.byte_start..cluster.byte_end], options);
+            origins.extend(std::iter::repeat_n(index, piece.len()));
+            folded.push_str(&piece);
+        }
+
+        FoldedText {
+            text: folded,
+            origins,
+            clusters,
+        }
+    }
+
+    /// The original clusters covered by the folded byte range, or `None` when
+    /// the range starts or ends inside the folding of a single cluster.
+    fn cluster_range(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        if start >= end {
+            return None;
+        }
+        let first = self.origins[start];
+        let last = self.origins[end - 1];
+        let starts_cluster = start == 0 || self.origins[start - 1] != first;
+        let ends_cluster = end == self.origins.len() || self.origins[end] != last;
+        (starts_cluster && ends_cluster).then_some((first, last))
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether the span does not start or end in the middle of a word. Terms that
+/// begin or end with punctuation, such as `C++`, only need a boundary on their
+/// word side.
+fn at_word_boundaries(text: &str, byte_start: usize, byte_end: usize) -> bool {
+    let span = &text[byte_start..byte_end];
+    let starts_word = span.chars().next().is_some_and(is_word_char);
+    let ends_word = span.chars().next_back().is_some_and(is_word_char);
+    let before = text[..byte_start]
+        .chars()
+        .rev()
+        .find(|c| !is_combining_mark(*c));
+    let after = text[byte_end..].chars().next();
+
+    let splits_start = starts_word && before.is_some_and(is_word_char);
+    let splits_end = ends_word && after.is_some_and(is_word_char);
+    !splits_start && !splits_end
+}
+
+/// An Aho-Corasick automaton over the folded terms of one language.
+pub struct Recognizer {
+    options: SearchOptions,
+    automaton: Option<AhoCorasick>,
+    entries: Vec<Vec<TermsList>>,
+}
+
+impl Recognizer {
+    pub fn new(terms: Vec<TermsList>, language: &str, options: SearchOptions) -> Self {
+        let mut patterns: Vec<String> = Vec::new();
+        let mut entries: Vec<Vec<TermsList>> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+
+        for entry in terms {
+            let stored = entry.term_language_set.language.as_deref().unwrap_or("");
+            if !language_matches(stored, language) {
+                continue;
+            }
+            let folded = fold_text(
+                entry.term_language_set.term.as_deref().unwrap_or("").trim(),
+                &options,
+            );
+            if folded.is_empty() {
+                continue;
+            }
+            match positions.get(&folded) {
+                Some(&position) => entries[position].push(entry),
+                None => {
+                    positions.insert(folded.clone(), patterns.len());
+                    patterns.push(folded);
+                    entries.push(vec![entry]);
+                }
+            }
+        }
+
+        let automaton = if patterns.is_empty() {
+            None
+        } else {
+            AhoCorasickBuilder::new()
+                .match_kind(AhoMatchKind::Standard)
+                .build(&patterns)
+                .ok()
+        };
+
+        Recognizer {
+            options,
+            automaton,
+            entries,
+        }
+    }
+
+    pub fn pattern_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Finds the terms occurring in `text`. Overlapping candidates are
+    /// resolved leftmost-longest, so `brake pad` wins over `brake` and `pad`.
+    pub fn find(&self, text: &str) -> Vec<(usize, usize, &[TermsList])> {
+        let Some(automaton) = &self.automaton else {
+            return Vec::new();
+        };
+        let folded = FoldedText::new(text, &self.options);
+
+        let mut candidates: Vec<(usize, usize, usize)> = automaton
+            .find_overlapping_iter(&folded.text)
+            .filter_map(|found| {
+                let (first, last) = folded.cluster_range(found.start(), found.end())?;
+                let byte_start = folded.clusters[first].byte_start;
+                let byte_end = folded.clusters[last].byte_end;
+                at_word_boundaries(text, byte_start, byte_end).then_some((
+                    first,
+                    last,
+                    found.pattern().as_usize(),
+                ))
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(&a.1)));
+
+        let mut found = Vec::new();
+        let mut next_free = 0;
+        for (first, last, pattern) in candidates {
+            if first < next_free {
+                continue;
+            }
+            next_free = last + 1;
+            found.push((first, last, self.entries[pattern].as_slice()));
+        }
+
+        found
+            .into_iter()
+            .map(|(first, last, entries)| {
+                (
+                    folded.clusters[first].char_start,
+                    folded.clusters[last].char_end,
+                    entries,
+                )
+            })
+            .collect()
+    }
+}
+
+/// The stored languages that `language` covers, with their statistics.
+fn language_stats(
+    State(app_state): State<Arc<AppState>>,
+    language: &str,
+) -> Result<Vec<LanguageStats>, rusqlite::Error> {
+    let conn = connect_db(State(app_state.clone()))?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT COALESCE(language, ''), COUNT(*), SUM(term_id), SUM(version)
+        FROM {}
+        WHERE deleted = 0
+        GROUP BY COALESCE(language, '')
+        ORDER BY COALESCE(language, '')",
+        app_state.db_info.table_name
+    ))?;
+    let stats = stmt
+        .query_map([], |row| {
+            Ok(LanguageStats {
+                language: row.get(0)?,
+                count: row.get(1)?,
+                term_ids: row.get(2)?,
+                versions: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(stats
+        .into_iter()
+        .filter(|stats| language_matches(&stats.language, language))
+        .collect())
+}
+
+fn cached_recognizer(
+    State(app_state): State<Arc<AppState>>,
+    language: &str,
+    options: &SearchOptions,
+) -> Result<Arc<Recognizer>, rusqlite::Error> {
+    let key = (
+        format!(
+            "{}:{}",
+            app_state.db_info.path(),
+            app_state.db_info.table_name
+        ),
+        language.to_string(),
+        options.flags(),
+    );
+    let generation = TERMS_GENERATION.load(Ordering::SeqCst);
+    if let Some(cached) = RECOGNIZERS.read().unwrap().get(&key) {
+        if cached.generation == generation {
+            return Ok(cached.recognizer.clone());
+        }
+    }
+
+    let languages = language_stats(State(app_state.clone()), language)?;
+    if let Some(cached) = RECOGNIZERS.write().unwrap().get_mut(&key) {
+        if cached.languages == languages {
+            cached.generation = generation;
+            return Ok(cached.recognizer.clone());
+        }
+    }
+
+    let stored: Vec<String> = languages
+        .iter()
+        .map(|stats| stats.language.clone())
+        .collect();
+    let terms = search_terms_by_languages(State(app_state), &stored)?;
+    let recognizer = Arc::new(Recognizer::new(terms, language, *options));
+    info!(
+        "Built term recognizer for {:?} with {} patterns",
+        language,
+        recognizer.pattern_count()
+    );
+    RECOGNIZERS.write().unwrap().insert(
+        key,
+        CachedRecognizer {
+            recognizer: recognizer.clone(),
+            languages,
+            generation,
+        },
+    );
+    Ok(recognizer)
+}
+
+/// Finds the `from` language terms occurring in `text` and adds the `to`
+/// language terms of each concept found. With an empty `to` no targets are
+/// looked up.
+pub fn recognize_terms(
+    State(app_state): State<Arc<AppState>>,
+    text: &str,
+    from: &str,
+    to: &str,
+    options: &SearchOptions,
+) -> Result<Vec<RecognizedTerm>, rusqlite::Error> {
+    let recognizer = cached_recognizer(State(app_state.clone()), from, options)?;
+    let found = recognizer.find(text);
+    debug!(
+        "Recognized {} terms in {} characters",
+        found.len(),
+        text.chars().count()
+    );
+
+    let collator = collator_for_language(to);
+    let mut targets: HashMap<i32, Vec<TermsList>> = HashMap::new();
+    let chars: Vec<char> = text.chars().collect();
+
+    found
+        .into_iter()
+        .map(|(start, end, entries)| {
+            let mut concepts: Vec<RecognizedConcept> = Vec::new();
+            for entry in entries {
+                if let Some(concept) = concepts
+                    .iter_mut()
+                    .find(|concept| concept.term_set_id == entry.term_set_id)
+                {
+                    concept.source.push(entry.clone());
+                    continue;
+                }
+                if !to.trim().is_empty() && !targets.contains_key(&entry.term_set_id) {
+                    let mut concept_targets: Vec<TermsList> =
+                        search_terms_by_term_set_id(State(app_state.clone()), entry.term_set_id)?
+                            .into_iter()
+                            .filter(|target| {
+                                let language =
+                                    target.term_language_set.language.as_deref().unwrap_or("");
+                                language_matches(language, to)
+                            })
+                            .collect();
+                    sort_by_field(
+                        &mut concept_targets,
+                        |target| target,
+                        SortField::Term,
+                        SortOrder::Asc,
+                        &collator,
+                    );
+                    targets.insert(entry.term_set_id, concept_targets);
+                }
+                concepts.push(RecognizedConcept {
+                    term_set_id: entry.term_set_id,
+                    source: vec![entry.clone()],
+                    targets: targets.get(&entry.term_set_id).cloned().unwrap_or_default(),
+                });
+            }
+
+            Ok(RecognizedTerm {
+                start,
+                end,
+                text: chars[start..end].iter().collect(),
+                concepts,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,170 @@
+use axum::extract::State;
+use chrono::DateTime;
+use rusqlite::params;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::dictionary::database::{
+    connect_db, current_epoch, terms_list_from_row, AppState, TermsList,
+};
+use crate::dictionary::relations::prune_relations;
+
+/// Days a deleted term stays in the recycle bin before it is purged.
+pub const DEFAULT_RETENTION_DAYS: u64 = 30;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeletedTerm {
+    #[serde(flatten)]
+    pub term: TermsList,
+    pub deleted_by: Option<String>,
+    pub deleted_at: Option<i64>,
+}
+
+impl DeletedTerm {
+    pub fn deleted_by_or_default(&self) -> &str {
+        self.deleted_by.as_deref().unwrap_or("Unknown")
+    }
+
+    pub fn deleted_date(&self) -> String {
+        self.deleted_at
+            .and_then(|deleted_at| DateTime::from_timestamp(deleted_at, 0))
+            .map_or("No Date".to_string(), |date| {
+                date.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+            })
+    }
+}
+
+/// The terms in the recycle bin, most recently deleted first.
+pub fn get_deleted_terms(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Vec<DeletedTerm>, rusqlite::Error> {
+    let conn = connect_db(State(app_state.clone()))?;
+    let sql = format!(
+        "SELECT
+            term_id,
+            term_set_id,
+            term,
+            language,
+            term_type,
+            creator_id,
+            creation_timestamp,
+            updater_id,
+            update_timestamp,
+            subject,
+            source,
+            user,
+            attributes,
+            remark,
+            url,
+            context,
+            definition,
+            status,
+            part_of_speech,
+            gender,
+            number,
+            inflection,
+            deleted_by,
+            deleted_at
+        FROM {}
+        WHERE deleted = 1
+        ORDER BY deleted_at DESC, term_id",
+        app_state.db_info.table_name
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let terms_iter = stmt.query_map([], |row| {
+        Ok(DeletedTerm {
+            term: terms_list_from_row(row)?,
+            deleted_by: row.get(22)?,
+            deleted_at: row.get(23)?,
+        })
+    })?;
+
+    terms_iter.collect()
+}
+
+/// Takes a term out of the recycle bin. Returns whether it was there.
+pub fn restore_term(
+    State(app_state): State<Arc<AppState>>,
+    term_id: i32,
+    restored_by: Option<&str>,
+) -> Result<bool, rusqlite::Error> {
+    let conn = connect_db(State(app_state.clone()))?;
+    let restored = conn.execute(
+        &format!(
+            "UPDATE {} SET
+                deleted = 0,
+                deleted_by = NULL,
+                deleted_at = NULL,
+                updater_id = COALESCE(?2, updater_id),
+                update_timestamp = ?3
+            WHERE term_id = ?1 AND deleted = 1",
+            app_state.db_info.table_name
+        ),
+        params![term_id, restored_by, current_epoch()],
+    )?;
+    if restored > 0 {
+        info!("Restored term {} from the recycle bin", term_id);
+    }
+    Ok(restored > 0)
+}
+
+/// Removes a term in the recycle bin for good. Returns whether it was there.
+pub fn purge_term(
+    State(app_state): State<Arc<AppState>>,
+    term_id: i32,
+) -> Result<bool, rusqlite::Error> {
+    let conn = connect_db(State(app_state.clone()))?;
+    let purged = conn.execute(
+        &format!(
+            "DELETE FROM {} WHERE term_id = ?1 AND deleted = 1",
+            app_state.db_info.table_name
+        ),
+        params![term_id],
+    )?;
+    if purged > 0 {
+        info!("Purged term {} from the recycle bin", term_id);
+        prune_relations(State(app_state))?;
+    }
+    Ok(purged > 0)
+}
+
+/// Removes the terms that have been in the recycle bin for at least
+/// `retention_days`, or all of them when it is 0. Returns how many were
+/// removed.
+pub fn purge_expired(
+    State(app_state): State<Arc<AppState>>,
+    retention_days: u64,
+) -> Result<usize, rusqlite::Error> {
+    // Saturates, so a huge retention keeps everything instead of overflowing.
+    let retention = i64::try_from(retention_days.saturating_mul(24 * 60 * 60)).unwrap_or(i64::MAX);
+    let cutoff = current_epoch().saturating_sub(retention);
+    let conn = connect_db(State(app_state.clone()))?;
+    let purged = conn.execute(
+        &format!(
+            "DELETE FROM {} WHERE deleted = 1 AND COALESCE(deleted_at, 0) <= ?1",
+            app_state.db_info.table_name
+        ),
+        params![cutoff],
+    )?;
+    if purged > 0 {
+        info!("Purged {} terms from the recycle bin", purged);
+        prune_relations(State(app_state))?;
+    }
+    Ok(purged)
+}
+
+/// Purges expired terms every hour. Runs until the server stops.
+pub async fn purge_expired_periodically(app_state: Arc<AppState>, retention_days: u64) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = purge_expired(State(app_state.clone()), retention_days) {
+            error!("Failed to purge the recycle bin: {}", err);
+        }
+    }
+}
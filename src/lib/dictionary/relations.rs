@@ -0,0 +1,329 @@
+use axum::extract::State;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+use crate::dictionary::database::{connect_db, search_terms_by_term_set_id, AppState, TermsList};
+
+/// How one term set relates to another, read as in SKOS: a term set with a
+/// `broader` relation to another is the narrower of the two.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RelationType {
+    Broader,
+    Narrower,
+    Related,
+    Antonym,
+    #[serde(alias = "see_also", alias = "see-also")]
+    SeeAlso,
+}
+
+impl RelationType {
+    pub const ALL: [RelationType; 5] = [
+        RelationType::Broader,
+        RelationType::Narrower,
+        RelationType::Related,
+        RelationType::Antonym,
+        RelationType::SeeAlso,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RelationType::Broader => "broader",
+            RelationType::Narrower => "narrower",
+            RelationType::Related => "related",
+            RelationType::Antonym => "antonym",
+            RelationType::SeeAlso => "seeAlso",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<RelationType> {
+        let value: String = value
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '_' | '-'))
+            .collect();
+        RelationType::ALL
+            .into_iter()
+            .find(|relation| relation.as_str().to_lowercase() == value)
+    }
+
+    /// The relation stored in the other direction.
+    pub fn inverse(&self) -> RelationType {
+        match self {
+            RelationType::Broader => RelationType::Narrower,
+            RelationType::Narrower => RelationType::Broader,
+            other => *other,
+        }
+    }
+
+    /// The TBX data category of the relation.
+    pub fn tbx_type(&self) -> &'static str {
+        match self {
+            RelationType::Broader => "broaderConceptGeneric",
+            RelationType::Narrower => "subordinateConceptGeneric",
+            RelationType::Related => "relatedConcept",
+            RelationType::Antonym => "antonymConcept",
+            RelationType::SeeAlso => "crossReference",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RelationType::Broader => "Broader",
+            RelationType::Narrower => "Narrower",
+            RelationType::Related => "Related",
+            RelationType::Antonym => "Antonym",
+            RelationType::SeeAlso => "See also",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RelationError {
+    SameTermSet(i32),
+    UnknownTermSet(i32),
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for RelationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelationError::SameTermSet(id) => {
+                write!(f, "term set {id} cannot be related to itself")
+            }
+            RelationError::UnknownTermSet(id) => write!(f, "term set {id} not found"),
+            RelationError::Database(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for RelationError {}
+
+impl From<rusqlite::Error> for RelationError {
+    fn from(err: rusqlite::Error) -> Self {
+        RelationError::Database(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TermSetRelation {
+    pub source_set_id: i32,
+    pub relation: RelationType,
+    pub target_set_id: i32,
+}
+
+/// A term set related to the one being shown, with its terms.
+#[derive(Clone, Debug, Serialize)]
+pub struct RelatedTermSet {
+    pub relation: RelationType,
+    pub term_set_id: i32,
+    pub terms: Vec<TermsList>,
+}
+
+pub fn create_relations_table(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<(), rusqlite::Error> {
+    debug!("Create relations table");
+    let conn = connect_db(State(app_state))?;
+    conn.execute_batch(
+        "
+        BEGIN;
+
+        CREATE TABLE IF NOT EXISTS term_set_relations (
+            source_set_id INTEGER NOT NULL,
+            relation TEXT NOT NULL,
+            target_set_id INTEGER NOT NULL,
+            PRIMARY KEY (source_set_id, relation, target_set_id)
+        );
+
+        CREATE INDEX IF NOT EXISTS term_set_relations_target
+            ON term_set_relations (target_set_id);
+
+        COMMIT;
+        ",
+    )?;
+    Ok(())
+}
+
+fn check_term_set(conn: &Connection, table: &str, term_set_id: i32) -> Result<(), RelationError> {
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {table} WHERE term_set_id = ?1 AND deleted = 0)"),
+        params![term_set_id],
+        |row| row.get(0),
+    )?;
+    if exists {
+        Ok(())
+    } else {
+        Err(RelationError::UnknownTermSet(term_set_id))
+    }
+}
+
+fn relation_from_row(row: &rusqlite::Row) -> Result<Option<TermSetRelation>, rusqlite::Error> {
+    let relation: String = row.get(1)?;
+    let Some(relation) = RelationType::parse(&relation) else {
+        return Ok(None);
+    };
+    Ok(Some(TermSetRelation {
+        source_set_id: row.get(0)?,
+        relation,
+        target_set_id: row.get(2)?,
+    }))
+}
+
+/// Relates two term sets and stores the inverse relation as well, so giving
+/// `disc brake` the broader concept `brake` also gives `brake` the narrower
+/// concept `disc brake`. Returns whether the relation is new.
+pub fn add_relation(
+    State(app_state): State<Arc<AppState>>,
+    relation: &TermSetRelation,
+) -> Result<bool, RelationError> {
+    if relation.source_set_id == relation.target_set_id {
+        return Err(RelationError::SameTermSet(relation.source_set_id));
+    }
+    let mut conn = connect_db(State(app_state.clone()))?;
+    check_term_set(&conn, &app_state.db_info.table_name, relation.source_set_id)?;
+    check_term_set(&conn, &app_state.db_info.table_name, relation.target_set_id)?;
+
+    let tx = conn.transaction()?;
+    let added = tx.execute(
+        "INSERT OR IGNORE INTO term_set_relations (source_set_id, relation, target_set_id)
+         VALUES (?1, ?2, ?3)",
+        params![
+            relation.source_set_id,
+            relation.relation.as_str(),
+            relation.target_set_id
+        ],
+    )?;
+    tx.execute(
+        "INSERT OR IGNORE INTO term_set_relations (source_set_id, relation, target_set_id)
+         VALUES (?1, ?2, ?3)",
+        params![
+            relation.target_set_id,
+            relation.relation.inverse().as_str(),
+            relation.source_set_id
+        ],
+    )?;
+    tx.commit()?;
+    if added > 0 {
+        info!(
+            "Term set {} is now {} of {}",
+            relation.source_set_id,
+            relation.relation.as_str(),
+            relation.target_set_id
+        );
+    }
+    Ok(added > 0)
+}
+
+/// Moves the relations of term set `from` to `into` when the two are merged,
+/// dropping any relations between them.
+pub fn merge_relations(conn: &Connection, from: i32, into: i32) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "UPDATE OR IGNORE term_set_relations SET source_set_id = ?2 WHERE source_set_id = ?1",
+        params![from, into],
+    )?;
+    conn.execute(
+        "UPDATE OR IGNORE term_set_relations SET target_set_id = ?2 WHERE target_set_id = ?1",
+        params![from, into],
+    )?;
+    conn.execute(
+        "DELETE FROM term_set_relations
+         WHERE source_set_id = ?1 OR target_set_id = ?1 OR source_set_id = target_set_id",
+        params![from],
+    )?;
+    Ok(())
+}
+
+/// Removes a relation together with its inverse. Returns whether it existed.
+pub fn remove_relation(
+    State(app_state): State<Arc<AppState>>,
+    relation: &TermSetRelation,
+) -> Result<bool, rusqlite::Error> {
+    let mut conn = connect_db(State(app_state))?;
+    let tx = conn.transaction()?;
+    let removed = tx.execute(
+        "DELETE FROM term_set_relations
+         WHERE source_set_id = ?1 AND relation = ?2 AND target_set_id = ?3",
+        params![
+            relation.source_set_id,
+            relation.relation.as_str(),
+            relation.target_set_id
+        ],
+    )?;
+    tx.execute(
+        "DELETE FROM term_set_relations
+         WHERE source_set_id = ?1 AND relation = ?2 AND target_set_id = ?3",
+        params![
+            relation.target_set_id,
+            relation.relation.inverse().as_str(),
+            relation.source_set_id
+        ],
+    )?;
+    tx.commit()?;
+    Ok(removed > 0)
+}
+
+/// The relations of the given term sets, or of all term sets when `None`.
+pub fn get_relations(
+    State(app_state): State<Arc<AppState>>,
+    term_set_ids: Option<&[i32]>,
+) -> Result<Vec<TermSetRelation>, rusqlite::Error> {
+    let conn = connect_db(State(app_state))?;
+    let mut stmt = conn.prepare(
+        "SELECT source_set_id, relation, target_set_id FROM term_set_relations
+         ORDER BY source_set_id, relation, target_set_id",
+    )?;
+    let relations = stmt
+        .query_map([], relation_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(relations
+        .into_iter()
+        .flatten()
+        .filter(|relation| term_set_ids.is_none_or(|ids| ids.contains(&relation.source_set_id)))
+        .collect())
+}
+
+/// The term sets related to `term_set_id`, grouped by relation type. Term sets
+/// whose terms are all in the recycle bin are left out.
+pub fn get_related_term_sets(
+    State(app_state): State<Arc<AppState>>,
+    term_set_id: i32,
+) -> Result<Vec<RelatedTermSet>, rusqlite::Error> {
+    let mut relations = get_relations(State(app_state.clone()), Some(&[term_set_id]))?;
+    relations.sort_by_key(|relation| (relation.relation, relation.target_set_id));
+    let mut related = Vec::new();
+    for relation in relations {
+        let terms = search_terms_by_term_set_id(State(app_state.clone()), relation.target_set_id)?;
+        if !terms.is_empty() {
+            related.push(RelatedTermSet {
+                relation: relation.relation,
+                term_set_id: relation.target_set_id,
+                terms,
+            });
+        }
+    }
+    Ok(related)
+}
+
+/// Removes the relations of term sets that no longer have any terms, once
+/// they have been purged from the recycle bin.
+pub fn prune_relations(State(app_state): State<Arc<AppState>>) -> Result<usize, rusqlite::Error> {
+    let table = &app_state.db_info.table_name;
+    let conn = connect_db(State(app_state.clone()))?;
+    let pruned = conn.execute(
+        &format!(
+            "DELETE FROM term_set_relations
+             WHERE source_set_id NOT IN (SELECT term_set_id FROM {table})
+                OR target_set_id NOT IN (SELECT term_set_id FROM {table})"
+        ),
+        params![],
+    )?;
+    if pruned > 0 {
+        info!("Removed {} relations of deleted term sets", pruned);
+    }
+    Ok(pruned)
+}
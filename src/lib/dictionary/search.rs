@@ -0,0 +1,262 @@
+use icu_casemap::CaseMapper;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::Connection;
+use rust_fuzzy_search::fuzzy_compare;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use strsim::normalized_damerau_levenshtein;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::dictionary::database::TermsList;
+
+pub const DEFAULT_MIN_SCORE: f64 = 0.6;
+
+const IGNORE_CASE_FLAG: i64 = 1;
+const IGNORE_WIDTH_FLAG: i64 = 2;
+const IGNORE_ACCENTS_FLAG: i64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SearchOptions {
+    pub ignore_case: bool,
+    pub ignore_width: bool,
+    pub ignore_accents: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            ignore_case: true,
+            ignore_width: true,
+            ignore_accents: false,
+        }
+    }
+}
+
+impl SearchOptions {
+    pub fn flags(&self) -> i64 {
+        let mut flags = 0;
+        if self.ignore_case {
+            flags |= IGNORE_CASE_FLAG;
+        }
+        if self.ignore_width {
+            flags |= IGNORE_WIDTH_FLAG;
+        }
+        if self.ignore_accents {
+            flags |= IGNORE_ACCENTS_FLAG;
+        }
+        flags
+    }
+
+    pub fn from_flags(flags: i64) -> Self {
+        SearchOptions {
+            ignore_case: flags & IGNORE_CASE_FLAG != 0,
+            ignore_width: flags & IGNORE_WIDTH_FLAG != 0,
+            ignore_accents: flags & IGNORE_ACCENTS_FLAG != 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    #[default]
+    Substring,
+    Fuzzy,
+    Fulltext,
+}
+
+/// How a hit relates to the query. The variant order is the ranking order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Exact,
+    Prefix,
+    Substring,
+    Fuzzy,
+    Fulltext,
+    /// Matched an advanced query only through its field filters.
+    Filter,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub entry: TermsList,
+    pub match_kind: MatchKind,
+    pub score: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/// Normalizes `text` so that two strings which should be considered equal
+/// under `options` compare equal byte for byte. This is the single
+/// implementation used by the in-memory search and, through the `ts_fold`
+/// SQL function, by queries against the database.
+pub fn fold_text(text: &str, options: &SearchOptions) -> String {
+    let folded = if options.ignore_case {
+        CaseMapper::new().fold_string(text)
+    } else {
+        text.to_string()
+    };
+
+    let normalized: String = if options.ignore_width {
+        folded.nfkc().collect()
+    } else {
+        folded.nfc().collect()
+    };
+
+    if options.ignore_accents {
+        normalized
+            .nfd()
+            .filter(|c| !is_combining_mark(*c))
+            .nfc()
+            .collect()
+    } else {
+        normalized
+    }
+}
+
+pub fn register_search_functions(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.create_scalar_function(
+        "ts_fold",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let text = ctx.get::<Option<String>>(0)?.unwrap_or_default();
+            let options = SearchOptions::from_flags(ctx.get::<i64>(1)?);
+            Ok(fold_text(&text, &options))
+        },
+    )
+}
+
+pub fn term_matches(
+    entry: &TermsList,
+    folded_term: &str,
+    language: &str,
+    options: &SearchOptions,
+) -> bool {
+    if !language.is_empty() && entry.term_language_set.language.as_deref().unwrap_or("") != language
+    {
+        return false;
+    }
+
+    let term_str = entry.term_language_set.term.as_deref().unwrap_or("");
+    fold_text(term_str, options).contains(folded_term)
+}
+
+/// Similarity between two already folded strings in `0.0..=1.0`: the best of
+/// the normalized Damerau-Levenshtein distance and the trigram overlap, both
+/// for the whole term and for its individual words.
+pub fn similarity(query: &str, term: &str) -> f64 {
+    let whole = string_similarity(query, term);
+    let best_word = term
+        .split_whitespace()
+        .map(|word| string_similarity(query, word))
+        .fold(0.0, f64::max);
+
+    whole.max(best_word)
+}
+
+fn string_similarity(a: &str, b: &str) -> f64 {
+    let edit = normalized_damerau_levenshtein(a, b);
+    let trigram = (fuzzy_compare(a, b) as f64 + fuzzy_compare(b, a) as f64) / 2.0;
+    edit.max(trigram)
+}
+
+pub fn rank_match(
+    folded_query: &str,
+    folded_term: &str,
+    mode: SearchMode,
+    min_score: f64,
+) -> Option<(MatchKind, f64)> {
+    let coverage = || {
+        let term_len = folded_term.chars().count().max(1);
+        folded_query.chars().count() as f64 / term_len as f64
+    };
+
+    if folded_term == folded_query {
+        Some((MatchKind::Exact, 1.0))
+    } else if folded_term.starts_with(folded_query) {
+        Some((MatchKind::Prefix, coverage()))
+    } else if folded_term.contains(folded_query) {
+        Some((MatchKind::Substring, coverage()))
+    } else if mode == SearchMode::Fuzzy {
+        let score = similarity(folded_query, folded_term);
+        (score >= min_score).then_some((MatchKind::Fuzzy, score))
+    } else {
+        None
+    }
+}
+
+pub fn rank_hits(
+    candidates: &[TermsList],
+    query: &str,
+    options: &SearchOptions,
+    mode: SearchMode,
+    min_score: f64,
+) -> Vec<SearchHit> {
+    let folded_query = fold_text(query, options);
+
+    let mut hits: Vec<SearchHit> = candidates
+        .iter()
+        .filter_map(|entry| {
+            let term_str = entry.term_language_set.term.as_deref().unwrap_or("");
+            let folded_term = fold_text(term_str, options);
+            rank_match(&folded_query, &folded_term, mode, min_score).map(|(match_kind, score)| {
+                SearchHit {
+                    entry: entry.clone(),
+                    match_kind,
+                    score,
+                    snippet: None,
+                }
+            })
+        })
+        .collect();
+
+    hits.sort_by(compare_hits);
+    hits
+}
+
+/// Ranks the results of an advanced query by its free text, if any. Terms
+/// that only satisfy the field filters keep their query order at the end.
+pub fn rank_query_hits(
+    results: &[TermsList],
+    free_text: &str,
+    options: &SearchOptions,
+) -> Vec<SearchHit> {
+    let folded_query = fold_text(free_text, options);
+
+    let mut hits: Vec<SearchHit> = results
+        .iter()
+        .map(|entry| {
+            let term_str = entry.term_language_set.term.as_deref().unwrap_or("");
+            let folded_term = fold_text(term_str, options);
+            let (match_kind, score) = if folded_query.is_empty() {
+                None
+            } else {
+                rank_match(&folded_query, &folded_term, SearchMode::Substring, 1.0)
+            }
+            .unwrap_or((MatchKind::Filter, 1.0));
+            SearchHit {
+                entry: entry.clone(),
+                match_kind,
+                score,
+                snippet: None,
+            }
+        })
+        .collect();
+
+    hits.sort_by(|a, b| match (a.match_kind, b.match_kind) {
+        (MatchKind::Filter, MatchKind::Filter) => Ordering::Equal,
+        _ => compare_hits(a, b),
+    });
+    hits
+}
+
+pub fn compare_hits(a: &SearchHit, b: &SearchHit) -> Ordering {
+    a.match_kind
+        .cmp(&b.match_kind)
+        .then_with(|| b.score.total_cmp(&a.score))
+        .then_with(|| a.entry.term_id.cmp(&b.entry.term_id))
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use crate::dictionary::database::TermsList;
+use crate::dictionary::export::group_term_sets;
+use crate::dictionary::relations::{RelationType, TermSetRelation};
+use crate::dictionary::taxonomy::SubjectTree;
+use crate::import::parse::TermStatus;
+
+/// Prefix of the concept and concept scheme URIs when none is given.
+pub const DEFAULT_BASE_URI: &str = "urn:term-squire:";
+
+const PREFIXES: &[(&str, &str)] = &[
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+    ("skos", "http://www.w3.org/2004/02/skos/core#"),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SkosValue {
+    Literal {
+        text: String,
+        language: Option<String>,
+    },
+    Resource(String),
+}
+
+/// A concept or concept scheme with its properties. Property names are
+/// prefixed names such as `skos:prefLabel`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkosResource {
+    pub uri: String,
+    pub class: &'static str,
+    pub properties: Vec<(&'static str, SkosValue)>,
+}
+
+impl SkosResource {
+    fn new(uri: String, class: &'static str) -> Self {
+        SkosResource {
+            uri,
+            class,
+            properties: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, property: &'static str, value: SkosValue) {
+        if !self.properties.contains(&(property, value.clone())) {
+            self.properties.push((property, value));
+        }
+    }
+
+    fn add_literal(&mut self, property: &'static str, text: &str, language: Option<String>) {
+        let text = text.trim();
+        if !text.is_empty() {
+            self.add(
+                property,
+                SkosValue::Literal {
+                    text: text.to_string(),
+                    language,
+                },
+            );
+        }
+    }
+}
+
+/// The language tag for a language code, or `None` when the code is not a
+/// valid BCP 47-style tag.
+fn language_tag(language: Option<&str>) -> Option<String> {
+    let tag = language?.trim().replace('_', "-");
+    let mut parts = tag.split('-');
+    let valid = parts
+        .next()
+        .is_some_and(|first| !first.is_empty() && first.chars().all(|c| c.is_ascii_alphabetic()))
+        && parts.all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+    valid.then_some(tag)
+}
+
+fn is_hidden(status: Option<TermStatus>) -> bool {
+    matches!(
+        status,
+        Some(TermStatus::Deprecated) | Some(TermStatus::Forbidden)
+    )
+}
+
+fn relation_property(relation: RelationType) -> &'static str {
+    match relation {
+        RelationType::Broader => "skos:broader",
+        RelationType::Narrower => "skos:narrower",
+        RelationType::Related | RelationType::Antonym => "skos:related",
+        RelationType::SeeAlso => "rdfs:seeAlso",
+    }
+}
+
+fn flatten_subjects(subjects: &[SubjectTree], paths: &mut HashMap<String, i64>) {
+    for subject in subjects {
+        paths.insert(subject.path.clone(), subject.id);
+        flatten_subjects(&subject.children, paths);
+    }
+}
+
+/// Maps term sets to `skos:Concept`s. Per language, the preferred term, or
+/// else the first term that is neither deprecated nor forbidden, becomes the
+/// `skos:prefLabel`; other terms become `skos:altLabel`s, and deprecated and
+/// forbidden terms `skos:hiddenLabel`s. The subjects of the terms become
+/// `skos:ConceptScheme`s.
+pub fn skos_graph(
+    terms: &[TermsList],
+    relations: &[TermSetRelation],
+    subjects: &[SubjectTree],
+    base_uri: &str,
+) -> Vec<SkosResource> {
+    let mut subject_ids = HashMap::new();
+    flatten_subjects(subjects, &mut subject_ids);
+
+    let mut schemes: Vec<SkosResource> = Vec::new();
+    let mut concepts = Vec::new();
+
+    for term_set in group_term_sets(terms, relations) {
+        let mut concept = SkosResource::new(
+            format!("{base_uri}concept/{}", term_set.term_set_id),
+            "skos:Concept",
+        );
+        concept.add_literal("skos:notation", &term_set.term_set_id.to_string(), None);
+
+        let mut languages: Vec<Option<&str>> = Vec::new();
+        for entry in &term_set.terms {
+            let language = entry.term_language_set.language.as_deref();
+            if !languages.contains(&language) {
+                languages.push(language);
+            }
+        }
+
+        for language in languages {
+            let sets: Vec<_> = term_set
+                .terms
+                .iter()
+                .map(|entry| &entry.term_language_set)
+                .filter(|set| set.language.as_deref() == language)
+                .collect();
+            let preferred = sets
+                .iter()
+                .position(|set| set.status == Some(TermStatus::Preferred))
+                .or_else(|| sets.iter().position(|set| !is_hidden(set.status)));
+            let tag = language_tag(language);
+
+            for (index, set) in sets.iter().enumerate() {
+                let property = if Some(index) == preferred {
+                    "skos:prefLabel"
+                } else if is_hidden(set.status) {
+                    "skos:hiddenLabel"
+                } else {
+                    "skos:altLabel"
+                };
+                concept.add_literal(property, set.term.as_deref().unwrap_or(""), tag.clone());
+                concept.add_literal(
+                    "skos:definition",
+                    set.definition.as_deref().unwrap_or(""),
+                    tag.clone(),
+                );
+
+                let Some(subject) = set.subject.as_deref() else {
+                    continue;
+                };
+                let Some(subject_id) = subject_ids.get(subject) else {
+                    continue;
+                };
+                let scheme_uri = format!("{base_uri}scheme/{subject_id}");
+                if !schemes.iter().any(|scheme| scheme.uri == scheme_uri) {
+                    let mut scheme = SkosResource::new(scheme_uri.clone(), "skos:ConceptScheme");
+                    scheme.add_literal("skos:prefLabel", subject, None);
+                    schemes.push(scheme);
+                }
+                concept.add("skos:inScheme", SkosValue::Resource(scheme_uri));
+            }
+        }
+
+        for relation in &term_set.relations {
+            concept.add(
+                relation_property(relation.relation),
+                SkosValue::Resource(format!("{base_uri}concept/{}", relation.term_set_id)),
+            );
+        }
+        concepts.push(concept);
+    }
+
+    schemes.sort_by(|a, b| a.uri.cmp(&b.uri));
+    schemes.extend(concepts);
+    schemes
+}
+
+fn escape_turtle(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn turtle_value(value: &SkosValue) -> String {
+    match value {
+        SkosValue::Literal { text, language } => match language {
+            Some(language) => format!("\"{}\"@{language}", escape_turtle(text)),
+            None => format!("\"{}\"", escape_turtle(text)),
+        },
+        SkosValue::Resource(uri) => format!("<{uri}>"),
+    }
+}
+
+pub fn export_turtle(resources: &[SkosResource]) -> String {
+    let mut turtle = String::new();
+    for (prefix, namespace) in PREFIXES {
+        turtle.push_str(&format!("@prefix {prefix}: <{namespace}> .\n"));
+    }
+
+    for resource in resources {
+        turtle.push_str(&format!("\n<{}> a {}", resource.uri, resource.class));
+        for (property, value) in &resource.properties {
+            turtle.push_str(&format!(" ;\n    {property} {}", turtle_value(value)));
+        }
+        turtle.push_str(" .\n");
+    }
+    turtle
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn export_rdf_xml(resources: &[SkosResource]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rdf:RDF");
+    for (prefix, namespace) in PREFIXES {
+        xml.push_str(&format!("\n    xmlns:{prefix}=\"{namespace}\""));
+    }
+    xml.push_str(">\n");
+
+    for resource in resources {
+        xml.push_str(&format!(
+            "  <{} rdf:about=\"{}\">\n",
+            resource.class,
+            escape_xml(&resource.uri)
+        ));
+        for (property, value) in &resource.properties {
+            match value {
+                SkosValue::Literal { text, language } => {
+                    let lang = language
+                        .as_deref()
+                        .map(|language| format!(" xml:lang=\"{language}\""))
+                        .unwrap_or_default();
+                    xml.push_str(&format!(
+                        "    <{property}{lang}>{}</{property}>\n",
+                        escape_xml(text)
+                    ));
+                }
+                SkosValue::Resource(uri) => xml.push_str(&format!(
+                    "    <{property} rdf:resource=\"{}\"/>\n",
+                    escape_xml(uri)
+                )),
+            }
+        }
+        xml.push_str(&format!("  </{}>\n", resource.class));
+    }
+    xml.push_str("</rdf:RDF>\n");
+    xml
+}
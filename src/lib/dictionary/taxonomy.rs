@@ -0,0 +1,456 @@
+use axum::extract::State;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+use crate::dictionary::database::{connect_db, AppState};
+
+/// Separates the levels of a subject path, as in `Engineering > Automotive`.
+pub const SUBJECT_SEPARATOR: &str = ">";
+
+/// How subject paths are written back to the terms table.
+const PATH_JOINER: &str = " > ";
+
+#[derive(Debug)]
+pub enum TaxonomyError {
+    NotFound(i64),
+    InvalidName(String),
+    /// A change that would break the tree, such as deleting a subject that
+    /// is still used or moving a subject below itself.
+    Conflict(String),
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for TaxonomyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaxonomyError::NotFound(id) => write!(f, "subject {id} not found"),
+            TaxonomyError::InvalidName(name) => write!(
+                f,
+                "invalid subject name '{name}': names must not be empty or contain '{SUBJECT_SEPARATOR}'"
+            ),
+            TaxonomyError::Conflict(message) => write!(f, "{message}"),
+            TaxonomyError::Database(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for TaxonomyError {}
+
+impl From<rusqlite::Error> for TaxonomyError {
+    fn from(err: rusqlite::Error) -> Self {
+        TaxonomyError::Database(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubjectNode {
+    pub id: i64,
+    pub name: String,
+    pub parent_id: Option<i64>,
+}
+
+impl SubjectNode {
+    /// The search query for the terms of this subject and its descendants.
+    pub fn search_query(&self) -> String {
+        format!("subject:=\"{}\"", self.name)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SubjectTree {
+    pub id: i64,
+    pub name: String,
+    pub path: String,
+    /// Terms filed under this subject or one of its descendants.
+    pub term_count: usize,
+    pub children: Vec<SubjectTree>,
+}
+
+/// Splits a subject path into its level names, dropping empty levels.
+pub fn split_subject_path(subject: &str, separator: &str) -> Vec<String> {
+    let subject = subject.trim();
+    if separator.is_empty() {
+        return if subject.is_empty() {
+            Vec::new()
+        } else {
+            vec![subject.to_string()]
+        };
+    }
+    subject
+        .split(separator)
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Rewrites a subject path using `separator`, such as a TermStar subject
+/// `Engineering/Automotive`, to the `Engineering > Automotive` form used
+/// by the terms table.
+pub fn normalize_subject_path(subject: &str, separator: &str) -> Option<String> {
+    let names = split_subject_path(subject, separator);
+    if names.is_empty() {
+        None
+    } else {
+        Some(names.join(PATH_JOINER))
+    }
+}
+
+pub fn create_subject_table(conn: &Connection) -> Result<(), rusqlite::Error> {
+    debug!("Create subject table");
+    conn.execute_batch(
+        "
+        BEGIN;
+
+        CREATE TABLE IF NOT EXISTS subjects (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            parent_id INTEGER REFERENCES subjects(id)
+        );
+
+        CREATE UNIQUE INDEX IF NOT EXISTS subjects_parent_name
+            ON subjects (COALESCE(parent_id, 0), name COLLATE NOCASE);
+
+        COMMIT;
+        ",
+    )?;
+    Ok(())
+}
+
+fn node_from_row(row: &rusqlite::Row) -> Result<SubjectNode, rusqlite::Error> {
+    Ok(SubjectNode {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        parent_id: row.get(2)?,
+    })
+}
+
+fn read_node(conn: &Connection, id: i64) -> Result<Option<SubjectNode>, rusqlite::Error> {
+    conn.query_row(
+        "SELECT id, name, parent_id FROM subjects WHERE id = ?1",
+        params![id],
+        node_from_row,
+    )
+    .optional()
+}
+
+fn find_child(
+    conn: &Connection,
+    parent_id: Option<i64>,
+    name: &str,
+) -> Result<Option<SubjectNode>, rusqlite::Error> {
+    conn.query_row(
+        "SELECT id, name, parent_id FROM subjects
+         WHERE parent_id IS ?1 AND name = ?2 COLLATE NOCASE",
+        params![parent_id, name],
+        node_from_row,
+    )
+    .optional()
+}
+
+/// The subject node for a path of level names, creating missing levels.
+fn find_or_create_path(
+    conn: &Connection,
+    names: &[String],
+) -> Result<Option<i64>, rusqlite::Error> {
+    let mut parent_id = None;
+    for name in names {
+        let id = match find_child(conn, parent_id, name)? {
+            Some(node) => node.id,
+            None => {
+                conn.execute(
+                    "INSERT INTO subjects (name, parent_id) VALUES (?1, ?2)",
+                    params![name, parent_id],
+                )?;
+                conn.last_insert_rowid()
+            }
+        };
+        parent_id = Some(id);
+    }
+    Ok(parent_id)
+}
+
+/// The nodes from the root down to `id`.
+fn read_path(conn: &Connection, id: i64) -> Result<Vec<SubjectNode>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE ancestors(id, name, parent_id, depth) AS (
+             SELECT id, name, parent_id, 0 FROM subjects WHERE id = ?1
+             UNION ALL
+             SELECT subjects.id, subjects.name, subjects.parent_id, ancestors.depth + 1
+             FROM subjects JOIN ancestors ON subjects.id = ancestors.parent_id
+         )
+         SELECT id, name, parent_id FROM ancestors ORDER BY depth DESC",
+    )?;
+    let nodes = stmt.query_map(params![id], node_from_row)?;
+    nodes.collect()
+}
+
+fn path_text(path: &[SubjectNode]) -> String {
+    path.iter()
+        .map(|node| node.name.as_str())
+        .collect::<Vec<&str>>()
+        .join(PATH_JOINER)
+}
+
+/// Links a subject path to its taxonomy node, creating missing levels.
+/// Returns the node and the path in stored form, or `None` for an empty
+/// subject.
+pub fn resolve_subject(
+    conn: &Connection,
+    subject: Option<&str>,
+) -> Result<Option<(i64, String)>, rusqlite::Error> {
+    let names = split_subject_path(subject.unwrap_or(""), SUBJECT_SEPARATOR);
+    let Some(id) = find_or_create_path(conn, &names)? else {
+        return Ok(None);
+    };
+    Ok(Some((id, path_text(&read_path(conn, id)?))))
+}
+
+/// Links every term with a subject to its taxonomy node and rewrites the
+/// subject to the node's path.
+pub fn link_subjects(conn: &Connection, table: &str) -> Result<usize, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT subject FROM {table} WHERE TRIM(COALESCE(subject, '')) != ''"
+    ))?;
+    let subjects = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+
+    let mut linked = 0;
+    for subject in subjects {
+        if let Some((id, path)) = resolve_subject(conn, Some(&subject))? {
+            linked += conn.execute(
+                &format!("UPDATE {table} SET subject_id = ?1, subject = ?2 WHERE subject = ?3"),
+                params![id, path, subject],
+            )?;
+        }
+    }
+    info!("Linked {} terms to subjects", linked);
+    Ok(linked)
+}
+
+/// The ids of `id` and all subjects below it.
+fn subtree_ids(conn: &Connection, id: i64) -> Result<Vec<i64>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE subtree(id) AS (
+             SELECT ?1
+             UNION
+             SELECT subjects.id FROM subjects JOIN subtree ON subjects.parent_id = subtree.id
+         )
+         SELECT id FROM subtree",
+    )?;
+    let ids = stmt.query_map(params![id], |row| row.get(0))?;
+    ids.collect()
+}
+
+/// Rewrites the subject of the terms filed under `id` or below it after the
+/// subject was renamed or moved.
+fn refresh_term_paths(conn: &Connection, table: &str, id: i64) -> Result<(), rusqlite::Error> {
+    for node_id in subtree_ids(conn, id)? {
+        let path = path_text(&read_path(conn, node_id)?);
+        conn.execute(
+            &format!("UPDATE {table} SET subject = ?1 WHERE subject_id = ?2"),
+            params![path, node_id],
+        )?;
+    }
+    Ok(())
+}
+
+fn check_name(name: &str) -> Result<&str, TaxonomyError> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(SUBJECT_SEPARATOR) {
+        return Err(TaxonomyError::InvalidName(name.to_string()));
+    }
+    Ok(name)
+}
+
+fn check_parent(conn: &Connection, parent_id: Option<i64>) -> Result<(), TaxonomyError> {
+    match parent_id {
+        Some(parent_id) if read_node(conn, parent_id)?.is_none() => {
+            Err(TaxonomyError::NotFound(parent_id))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn get_subjects(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Vec<SubjectNode>, rusqlite::Error> {
+    let conn = connect_db(State(app_state))?;
+    let mut stmt =
+        conn.prepare("SELECT id, name, parent_id FROM subjects ORDER BY name COLLATE NOCASE")?;
+    let nodes = stmt.query_map([], node_from_row)?;
+    nodes.collect()
+}
+
+/// The subject tree, with the number of terms filed under each subject.
+pub fn get_subject_tree(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<Vec<SubjectTree>, rusqlite::Error> {
+    let nodes = get_subjects(State(app_state.clone()))?;
+    let conn = connect_db(State(app_state.clone()))?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT subject_id, COUNT(*) FROM {}
+         WHERE subject_id IS NOT NULL AND deleted = 0
+         GROUP BY subject_id",
+        app_state.db_info.table_name
+    ))?;
+    let counts = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<i64, usize>, _>>()?;
+
+    fn build(
+        nodes: &[SubjectNode],
+        counts: &HashMap<i64, usize>,
+        parent_id: Option<i64>,
+        parent_path: &str,
+    ) -> Vec<SubjectTree> {
+        nodes
+            .iter()
+            .filter(|node| node.parent_id == parent_id)
+            .map(|node| {
+                let path = if parent_path.is_empty() {
+                    node.name.clone()
+                } else {
+                    format!("{parent_path}{PATH_JOINER}{}", node.name)
+                };
+                let children = build(nodes, counts, Some(node.id), &path);
+                let term_count = counts.get(&node.id).copied().unwrap_or(0)
+                    + children.iter().map(|child| child.term_count).sum::<usize>();
+                SubjectTree {
+                    id: node.id,
+                    name: node.name.clone(),
+                    path,
+                    term_count,
+                    children,
+                }
+            })
+            .collect()
+    }
+
+    Ok(build(&nodes, &counts, None, ""))
+}
+
+/// The subjects from the root down to the subject of a term, for
+/// breadcrumbs. Empty for terms without a subject.
+pub fn get_term_subject_path(
+    State(app_state): State<Arc<AppState>>,
+    term_id: i32,
+) -> Result<Vec<SubjectNode>, rusqlite::Error> {
+    let conn = connect_db(State(app_state.clone()))?;
+    let subject_id: Option<i64> = conn
+        .query_row(
+            &format!(
+                "SELECT subject_id FROM {} WHERE term_id = ?1",
+                app_state.db_info.table_name
+            ),
+            params![term_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    match subject_id {
+        Some(id) => read_path(&conn, id),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Adds a subject below `parent_id`, or returns the existing subject of that
+/// name.
+pub fn add_subject(
+    State(app_state): State<Arc<AppState>>,
+    name: &str,
+    parent_id: Option<i64>,
+) -> Result<SubjectNode, TaxonomyError> {
+    let name = check_name(name)?;
+    let conn = connect_db(State(app_state))?;
+    check_parent(&conn, parent_id)?;
+    if let Some(existing) = find_child(&conn, parent_id, name)? {
+        return Ok(existing);
+    }
+    conn.execute(
+        "INSERT INTO subjects (name, parent_id) VALUES (?1, ?2)",
+        params![name, parent_id],
+    )?;
+    info!("Added subject {:?} below {:?}", name, parent_id);
+    Ok(SubjectNode {
+        id: conn.last_insert_rowid(),
+        name: name.to_string(),
+        parent_id,
+    })
+}
+
+/// Renames a subject and moves it below `parent_id`, updating the subject
+/// of every term filed under it.
+pub fn update_subject(
+    State(app_state): State<Arc<AppState>>,
+    id: i64,
+    name: &str,
+    parent_id: Option<i64>,
+) -> Result<SubjectNode, TaxonomyError> {
+    let name = check_name(name)?;
+    let mut conn = connect_db(State(app_state.clone()))?;
+    read_node(&conn, id)?.ok_or(TaxonomyError::NotFound(id))?;
+    check_parent(&conn, parent_id)?;
+    if let Some(parent_id) = parent_id {
+        if subtree_ids(&conn, id)?.contains(&parent_id) {
+            return Err(TaxonomyError::Conflict(format!(
+                "subject {id} cannot be moved below itself"
+            )));
+        }
+    }
+    if find_child(&conn, parent_id, name)?.is_some_and(|sibling| sibling.id != id) {
+        return Err(TaxonomyError::Conflict(format!(
+            "a subject named '{name}' already exists there"
+        )));
+    }
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        "UPDATE subjects SET name = ?1, parent_id = ?2 WHERE id = ?3",
+        params![name, parent_id, id],
+    )?;
+    refresh_term_paths(&tx, &app_state.db_info.table_name, id)?;
+    tx.commit()?;
+    info!("Updated subject {}: {:?} below {:?}", id, name, parent_id);
+
+    Ok(SubjectNode {
+        id,
+        name: name.to_string(),
+        parent_id,
+    })
+}
+
+/// Deletes a subject that has no child subjects and no terms.
+pub fn delete_subject(
+    State(app_state): State<Arc<AppState>>,
+    id: i64,
+) -> Result<(), TaxonomyError> {
+    let conn = connect_db(State(app_state.clone()))?;
+    read_node(&conn, id)?.ok_or(TaxonomyError::NotFound(id))?;
+    let children: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM subjects WHERE parent_id = ?1",
+        params![id],
+        |row| row.get(0),
+    )?;
+    let terms: i64 = conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM {} WHERE subject_id = ?1",
+            app_state.db_info.table_name
+        ),
+        params![id],
+        |row| row.get(0),
+    )?;
+    if children > 0 || terms > 0 {
+        return Err(TaxonomyError::Conflict(format!(
+            "subject {id} still has {children} subjects and {terms} terms"
+        )));
+    }
+    conn.execute("DELETE FROM subjects WHERE id = ?1", params![id])?;
+    info!("Deleted subject {}", id);
+    Ok(())
+}
@@ -0,0 +1,347 @@
+use axum::extract::State;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::dictionary::database::{connect_db, current_epoch, AppState};
+use crate::dictionary::relations::{merge_relations, prune_relations};
+
+#[derive(Debug)]
+pub enum TermSetError {
+    UnknownTermSet(i32),
+    UnknownTerm(i32),
+    SameTermSet(i32),
+    /// Both term sets of a merge have terms in these languages.
+    DuplicateLanguages(Vec<String>),
+    /// The terms to split off are not all in one term set.
+    MixedTermSets,
+    /// Splitting off every term of a set would only renumber it.
+    WholeTermSet(i32),
+    NoTerms,
+    NoTermSets,
+    /// A term to discard in a merge is not in one of the merged term sets.
+    NotMerged(i32),
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for TermSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TermSetError::UnknownTermSet(id) => write!(f, "term set {id} not found"),
+            TermSetError::UnknownTerm(id) => write!(f, "term {id} not found"),
+            TermSetError::SameTermSet(id) => write!(f, "the term is already in term set {id}"),
+            TermSetError::DuplicateLanguages(languages) => {
+                write!(f, "both term sets have terms in: {}", languages.join(", "))
+            }
+            TermSetError::MixedTermSets => {
+                write!(f, "the terms to split off must all be in the same term set")
+            }
+            TermSetError::WholeTermSet(id) => {
+                write!(f, "at least one term has to stay in term set {id}")
+            }
+            TermSetError::NoTerms => write!(f, "no terms selected"),
+            TermSetError::NoTermSets => write!(f, "no term sets to merge"),
+            TermSetError::NotMerged(id) => {
+                write!(f, "term {id} is not in one of the merged term sets")
+            }
+            TermSetError::Database(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for TermSetError {}
+
+impl From<rusqlite::Error> for TermSetError {
+    fn from(err: rusqlite::Error) -> Self {
+        TermSetError::Database(err)
+    }
+}
+
+/// What a merge does with the terms of the merged set in languages the
+/// other set already has.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateLanguages {
+    /// Refuse to merge.
+    #[default]
+    Reject,
+    /// Keep them as synonyms.
+    KeepBoth,
+    /// Move them to the recycle bin.
+    KeepTarget,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MergeResult {
+    pub term_set_id: i32,
+    pub moved: usize,
+    pub discarded: usize,
+}
+
+fn check_term_set(conn: &Connection, table: &str, term_set_id: i32) -> Result<(), TermSetError> {
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {table} WHERE term_set_id = ?1 AND deleted = 0)"),
+        params![term_set_id],
+        |row| row.get(0),
+    )?;
+    if exists {
+        Ok(())
+    } else {
+        Err(TermSetError::UnknownTermSet(term_set_id))
+    }
+}
+
+fn term_set_of(conn: &Connection, table: &str, term_id: i32) -> Result<i32, TermSetError> {
+    conn.query_row(
+        &format!("SELECT term_set_id FROM {table} WHERE term_id = ?1 AND deleted = 0"),
+        params![term_id],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or(TermSetError::UnknownTerm(term_id))
+}
+
+/// Matches the terms of `?1` whose language also occurs in `?2`.
+const SHARED_LANGUAGE: &str = "EXISTS (
+    SELECT 1 FROM {table} other
+    WHERE other.term_set_id = ?2 AND other.deleted = 0
+        AND LOWER(TRIM(other.language)) = LOWER(TRIM({table}.language))
+)";
+
+/// Moves a term to another existing term set. Relations of the old set are
+/// dropped once it has no terms left, even in the recycle bin.
+pub fn move_term(
+    State(app_state): State<Arc<AppState>>,
+    term_id: i32,
+    term_set_id: i32,
+    moved_by: Option<&str>,
+) -> Result<(), TermSetError> {
+    let table = &app_state.db_info.table_name;
+    let conn = connect_db(State(app_state.clone()))?;
+    if term_set_of(&conn, table, term_id)? == term_set_id {
+        return Err(TermSetError::SameTermSet(term_set_id));
+    }
+    check_term_set(&conn, table, term_set_id)?;
+
+    conn.execute(
+        &format!(
+            "UPDATE {table} SET
+                term_set_id = ?2,
+                updater_id = COALESCE(?3, updater_id),
+                update_timestamp = ?4
+            WHERE term_id = ?1"
+        ),
+        params![term_id, term_set_id, moved_by, current_epoch()],
+    )?;
+    info!("Moved term {} to term set {}", term_id, term_set_id);
+    // The term may have been the last one of its old set.
+    prune_relations(State(app_state))?;
+    Ok(())
+}
+
+/// Merges `merged_set_id` into `term_set_id`: its terms, including those in
+/// the recycle bin, and its relations move over and the merged set is gone.
+pub fn merge_term_sets(
+    State(app_state): State<Arc<AppState>>,
+    term_set_id: i32,
+    merged_set_id: i32,
+    duplicates: DuplicateLanguages,
+    merged_by: Option<&str>,
+) -> Result<MergeResult, TermSetError> {
+    merge_term_sets_into(
+        State(app_state),
+        term_set_id,
+        &[merged_set_id],
+        duplicates,
+        &[],
+        merged_by,
+    )
+}
+
+/// Merges several term sets into `term_set_id` in one transaction, one after
+/// the other, so either all of them are merged or none is. The terms of the
+/// merged sets in `discarded_term_ids` go to the recycle bin instead.
+pub fn merge_term_sets_into(
+    State(app_state): State<Arc<AppState>>,
+    term_set_id: i32,
+    merged_set_ids: &[i32],
+    duplicates: DuplicateLanguages,
+    discarded_term_ids: &[i32],
+    merged_by: Option<&str>,
+) -> Result<MergeResult, TermSetError> {
+    let table = &app_state.db_info.table_name;
+    let mut merged_set_ids = merged_set_ids.to_vec();
+    merged_set_ids.sort_unstable();
+    merged_set_ids.dedup();
+    if merged_set_ids.is_empty() {
+        return Err(TermSetError::NoTermSets);
+    }
+    let mut conn = connect_db(State(app_state.clone()))?;
+    check_term_set(&conn, table, term_set_id)?;
+    for &merged_set_id in &merged_set_ids {
+        if merged_set_id == term_set_id {
+            return Err(TermSetError::SameTermSet(term_set_id));
+        }
+        check_term_set(&conn, table, merged_set_id)?;
+    }
+
+    let now = current_epoch();
+    let tx = conn.transaction()?;
+    let mut result = MergeResult {
+        term_set_id,
+        moved: 0,
+        discarded: 0,
+    };
+    for &term_id in discarded_term_ids {
+        if !merged_set_ids.contains(&term_set_of(&tx, table, term_id)?) {
+            return Err(TermSetError::NotMerged(term_id));
+        }
+        result.discarded += tx.execute(
+            &format!(
+                "UPDATE {table} SET deleted = 1, deleted_by = ?2, deleted_at = ?3
+                WHERE term_id = ?1 AND deleted = 0"
+            ),
+            params![term_id, merged_by, now],
+        )?;
+    }
+    for &merged_set_id in &merged_set_ids {
+        let (moved, discarded) = merge_into(
+            &tx,
+            table,
+            term_set_id,
+            merged_set_id,
+            duplicates,
+            merged_by,
+            now,
+        )?;
+        result.moved += moved;
+        result.discarded += discarded;
+    }
+    tx.commit()?;
+
+    info!(
+        "Merged term sets {:?} into {}: {} terms moved, {} discarded",
+        merged_set_ids, term_set_id, result.moved, result.discarded
+    );
+    Ok(result)
+}
+
+/// Merges one checked term set. Returns the number of terms moved and
+/// discarded.
+fn merge_into(
+    conn: &Connection,
+    table: &str,
+    term_set_id: i32,
+    merged_set_id: i32,
+    duplicates: DuplicateLanguages,
+    merged_by: Option<&str>,
+    now: i64,
+) -> Result<(usize, usize), TermSetError> {
+    let shared_language = SHARED_LANGUAGE.replace("{table}", table);
+
+    if duplicates == DuplicateLanguages::Reject {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT DISTINCT language FROM {table}
+            WHERE term_set_id = ?1 AND deleted = 0 AND {shared_language}
+            ORDER BY language"
+        ))?;
+        let languages = stmt
+            .query_map(params![merged_set_id, term_set_id], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        if !languages.is_empty() {
+            return Err(TermSetError::DuplicateLanguages(languages));
+        }
+    }
+
+    let discarded = if duplicates == DuplicateLanguages::KeepTarget {
+        conn.execute(
+            &format!(
+                "UPDATE {table} SET deleted = 1, deleted_by = ?3, deleted_at = ?4
+                WHERE term_set_id = ?1 AND deleted = 0 AND {shared_language}"
+            ),
+            params![merged_set_id, term_set_id, merged_by, now],
+        )?
+    } else {
+        0
+    };
+    let moved = conn.execute(
+        &format!(
+            "UPDATE {table} SET
+                term_set_id = ?2,
+                updater_id = COALESCE(?3, updater_id),
+                update_timestamp = ?4
+            WHERE term_set_id = ?1 AND deleted = 0"
+        ),
+        params![merged_set_id, term_set_id, merged_by, now],
+    )?;
+    // Terms in the recycle bin follow, so restoring one lands in the merged set.
+    conn.execute(
+        &format!("UPDATE {table} SET term_set_id = ?2 WHERE term_set_id = ?1"),
+        params![merged_set_id, term_set_id],
+    )?;
+    merge_relations(conn, merged_set_id, term_set_id)?;
+    Ok((moved, discarded))
+}
+
+/// Moves some terms of a term set to a new term set. Returns the new term
+/// set's ID.
+pub fn split_term_set(
+    State(app_state): State<Arc<AppState>>,
+    term_ids: &[i32],
+    split_by: Option<&str>,
+) -> Result<i32, TermSetError> {
+    let table = &app_state.db_info.table_name;
+    let mut term_ids = term_ids.to_vec();
+    term_ids.sort_unstable();
+    term_ids.dedup();
+    let Some(&first) = term_ids.first() else {
+        return Err(TermSetError::NoTerms);
+    };
+
+    let mut conn = connect_db(State(app_state.clone()))?;
+    let term_set_id = term_set_of(&conn, table, first)?;
+    for &term_id in &term_ids[1..] {
+        if term_set_of(&conn, table, term_id)? != term_set_id {
+            return Err(TermSetError::MixedTermSets);
+        }
+    }
+    let size: usize = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {table} WHERE term_set_id = ?1 AND deleted = 0"),
+        params![term_set_id],
+        |row| row.get(0),
+    )?;
+    if size <= term_ids.len() {
+        return Err(TermSetError::WholeTermSet(term_set_id));
+    }
+
+    let tx = conn.transaction()?;
+    let new_set_id: i32 = tx.query_row(
+        &format!("SELECT COALESCE(MAX(term_set_id), 0) + 1 FROM {table}"),
+        params![],
+        |row| row.get(0),
+    )?;
+    let now = current_epoch();
+    for term_id in &term_ids {
+        tx.execute(
+            &format!(
+                "UPDATE {table} SET
+                    term_set_id = ?2,
+                    updater_id = COALESCE(?3, updater_id),
+                    update_timestamp = ?4
+                WHERE term_id = ?1"
+            ),
+            params![term_id, new_set_id, split_by, now],
+        )?;
+    }
+    tx.commit()?;
+
+    info!(
+        "Split {} terms off term set {} into term set {}",
+        term_ids.len(),
+        term_set_id,
+        new_set_id
+    );
+    Ok(new_set_id)
+}
@@ -0,0 +1,262 @@
+use axum::extract::State;
+use elementtree::Element;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::dictionary::database::AppState;
+use crate::dictionary::language::primary_language;
+use crate::dictionary::qa::{check_segments, QaIssue, Segment};
+use crate::dictionary::search::SearchOptions;
+
+/// Inline elements holding native codes rather than translatable text.
+const CODE_ELEMENTS: &[&str] = &["bpt", "ept", "it", "ph", "sc", "ec", "sm", "em"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XliffError {
+    pub message: String,
+}
+
+impl fmt::Display for XliffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid XLIFF: {}", self.message)
+    }
+}
+
+impl std::error::Error for XliffError {}
+
+fn xliff_error(message: impl Into<String>) -> XliffError {
+    XliffError {
+        message: message.into(),
+    }
+}
+
+/// A `trans-unit` (XLIFF 1.2) or `unit` (XLIFF 2.x) with its plain text.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct XliffUnit {
+    pub file: String,
+    pub id: String,
+    pub source_language: String,
+    pub target_language: String,
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct XliffDocument {
+    pub version: String,
+    pub units: Vec<XliffUnit>,
+}
+
+fn children_named<'a>(element: &'a Element, name: &'a str) -> impl Iterator<Item = &'a Element> {
+    element
+        .children()
+        .filter(move |child| child.tag().name() == name)
+}
+
+fn attr(element: &Element, name: &str) -> String {
+    element.get_attr(name).unwrap_or("").to_string()
+}
+
+/// The translatable text of a `source` or `target`, leaving out inline codes.
+fn inline_text(element: &Element, text: &mut String) {
+    text.push_str(element.text());
+    for child in element.children() {
+        if !CODE_ELEMENTS.contains(&child.tag().name()) {
+            inline_text(child, text);
+        }
+        text.push_str(child.tail());
+    }
+}
+
+fn text_of(element: Option<&Element>) -> String {
+    let mut text = String::new();
+    if let Some(element) = element {
+        inline_text(element, &mut text);
+    }
+    text
+}
+
+fn translatable(element: &Element) -> bool {
+    element.get_attr("translate") != Some("no")
+}
+
+fn read_units_1_2(element: &Element, file: &XliffUnit, units: &mut Vec<XliffUnit>) {
+    for child in element.children().filter(|child| translatable(child)) {
+        match child.tag().name() {
+            "trans-unit" => units.push(XliffUnit {
+                id: attr(child, "id"),
+                source: text_of(children_named(child, "source").next()),
+                target: text_of(children_named(child, "target").next()),
+                ..file.clone()
+            }),
+            "body" | "group" => read_units_1_2(child, file, units),
+            _ => {}
+        }
+    }
+}
+
+fn read_units_2(element: &Element, file: &XliffUnit, units: &mut Vec<XliffUnit>) {
+    for child in element.children().filter(|child| translatable(child)) {
+        match child.tag().name() {
+            "unit" => {
+                let mut unit = XliffUnit {
+                    id: attr(child, "id"),
+                    ..file.clone()
+                };
+                for part in child
+                    .children()
+                    .filter(|part| matches!(part.tag().name(), "segment" | "ignorable"))
+                {
+                    unit.source
+                        .push_str(&text_of(children_named(part, "source").next()));
+                    unit.target
+                        .push_str(&text_of(children_named(part, "target").next()));
+                }
+                units.push(unit);
+            }
+            "group" => read_units_2(child, file, units),
+            _ => {}
+        }
+    }
+}
+
+/// Reads the units of an XLIFF 1.2 or 2.x document. Languages come from the
+/// `file` element in 1.2 and from the root element in 2.x.
+pub fn read_xliff(bytes: &[u8]) -> Result<XliffDocument, XliffError> {
+    let root = Element::from_reader(bytes).map_err(|err| xliff_error(err.to_string()))?;
+    if root.tag().name() != "xliff" {
+        return Err(xliff_error(format!(
+            "expected an <xliff> root element, found <{}>",
+            root.tag().name()
+        )));
+    }
+    let version = attr(&root, "version");
+    let is_version_2 = version.starts_with('2');
+
+    let mut units = Vec::new();
+    for file in children_named(&root, "file") {
+        let template = if is_version_2 {
+            XliffUnit {
+                file: attr(file, "id"),
+                id: String::new(),
+                source_language: attr(&root, "srcLang"),
+                target_language: attr(&root, "trgLang"),
+                source: String::new(),
+                target: String::new(),
+            }
+        } else {
+            XliffUnit {
+                file: attr(file, "original"),
+                id: String::new(),
+                source_language: attr(file, "source-language"),
+                target_language: attr(file, "target-language"),
+                source: String::new(),
+                target: String::new(),
+            }
+        };
+
+        if is_version_2 {
+            read_units_2(file, &template, &mut units);
+        } else {
+            read_units_1_2(file, &template, &mut units);
+        }
+    }
+
+    debug!("Read {} units from XLIFF {}", units.len(), version);
+    Ok(XliffDocument { version, units })
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct XliffUnitReport {
+    pub file: String,
+    pub id: String,
+    pub source_language: String,
+    pub target_language: String,
+    pub issues: Vec<QaIssue>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct XliffQaReport {
+    pub version: String,
+    pub checked_units: usize,
+    /// The units with at least one issue, in document order.
+    pub units: Vec<XliffUnitReport>,
+}
+
+/// Checks every unit against the termbase for its file's language pair. Terms
+/// of every region of those languages are used, since XLIFF files and the
+/// termbase rarely agree on regions.
+pub fn check_xliff(
+    State(app_state): State<Arc<AppState>>,
+    document: &XliffDocument,
+    options: &SearchOptions,
+) -> Result<XliffQaReport, rusqlite::Error> {
+    let mut issues: Vec<Vec<QaIssue>> = vec![Vec::new(); document.units.len()];
+    let mut language_pairs: Vec<(String, String)> = Vec::new();
+    for unit in &document.units {
+        let pair = (
+            primary_language(&unit.source_language).unwrap_or_default(),
+            primary_language(&unit.target_language).unwrap_or_default(),
+        );
+        if !language_pairs.contains(&pair) {
+            language_pairs.push(pair);
+        }
+    }
+
+    for (from, to) in &language_pairs {
+        let (indexes, segments): (Vec<usize>, Vec<Segment>) = document
+            .units
+            .iter()
+            .enumerate()
+            .filter(|(_, unit)| {
+                primary_language(&unit.source_language).unwrap_or_default() == *from
+                    && primary_language(&unit.target_language).unwrap_or_default() == *to
+            })
+            .map(|(index, unit)| {
+                (
+                    index,
+                    Segment {
+                        id: Some(index.to_string()),
+                        source: unit.source.clone(),
+                        target: unit.target.clone(),
+                    },
+                )
+            })
+            .unzip();
+        debug!(
+            "Checking {} XLIFF units from {:?} to {:?}",
+            indexes.len(),
+            from,
+            to
+        );
+
+        let report = check_segments(State(app_state.clone()), &segments, from, to, options)?;
+        for mut issue in report.issues {
+            let Ok(index) = issue.segment.parse::<usize>() else {
+                continue;
+            };
+            issue.segment = document.units[index].id.clone();
+            issues[index].push(issue);
+        }
+    }
+
+    Ok(XliffQaReport {
+        version: document.version.clone(),
+        checked_units: document.units.len(),
+        units: document
+            .units
+            .iter()
+            .zip(issues)
+            .filter(|(_, issues)| !issues.is_empty())
+            .map(|(unit, issues)| XliffUnitReport {
+                file: unit.file.clone(),
+                id: unit.id.clone(),
+                source_language: unit.source_language.clone(),
+                target_language: unit.target_language.clone(),
+                issues,
+            })
+            .collect(),
+    })
+}
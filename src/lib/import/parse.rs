@@ -1,7 +1,7 @@
 use chrono::format::ParseError;
 use chrono::{DateTime, NaiveDateTime};
 use elementtree::Element;
-use serde::{self, Deserialize, Serialize};
+use serde::{self, Deserialize, Deserializer, Serialize};
 use std::{fmt, fs, io};
 
 #[derive(Debug, Serialize)]
@@ -15,6 +15,247 @@ pub struct DictionaryEntry {
     pub language_sets: Vec<TermLanguageSet>,
 }
 
+/// Usage status of a term, as used by style guides.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TermStatus {
+    Preferred,
+    Admitted,
+    Deprecated,
+    Forbidden,
+}
+
+impl TermStatus {
+    pub const ALL: [TermStatus; 4] = [
+        TermStatus::Preferred,
+        TermStatus::Admitted,
+        TermStatus::Deprecated,
+        TermStatus::Forbidden,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TermStatus::Preferred => "preferred",
+            TermStatus::Admitted => "admitted",
+            TermStatus::Deprecated => "deprecated",
+            TermStatus::Forbidden => "forbidden",
+        }
+    }
+
+    /// Reads a status name as well as the TBX `normativeAuthorization` and
+    /// `administrativeStatus` values, such as `preferredTerm` or
+    /// `deprecatedTerm-admn-sts`.
+    pub fn parse(value: &str) -> Option<TermStatus> {
+        let value = value.trim().to_lowercase();
+        let value = value.strip_suffix("-admn-sts").unwrap_or(&value);
+        let value = value.strip_suffix("term").unwrap_or(value).trim();
+        match value {
+            "preferred" | "standardized" | "legal" | "regulated" => Some(TermStatus::Preferred),
+            "admitted" => Some(TermStatus::Admitted),
+            "deprecated" | "superseded" | "obsolete" | "not recommended" | "notrecommended" => {
+                Some(TermStatus::Deprecated)
+            }
+            "forbidden" | "do not use" => Some(TermStatus::Forbidden),
+            _ => None,
+        }
+    }
+
+    /// The status named by one of the `;` or `,` separated parts of a free
+    /// text field, such as TermStar attributes.
+    pub fn find_in(text: &str) -> Option<TermStatus> {
+        text.split([';', ',']).find_map(TermStatus::parse)
+    }
+}
+
+/// Lowercases a controlled value and drops the separators, so `properNoun`,
+/// `proper noun` and `proper_noun` compare equal.
+fn normalize_value(value: &str) -> String {
+    value
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '_' | '-' | '.'))
+        .collect()
+}
+
+/// Part of speech, named as in TBX `partOfSpeech`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PartOfSpeech {
+    Noun,
+    Verb,
+    Adjective,
+    Adverb,
+    ProperNoun,
+    Other,
+}
+
+impl PartOfSpeech {
+    pub const ALL: [PartOfSpeech; 6] = [
+        PartOfSpeech::Noun,
+        PartOfSpeech::Verb,
+        PartOfSpeech::Adjective,
+        PartOfSpeech::Adverb,
+        PartOfSpeech::ProperNoun,
+        PartOfSpeech::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PartOfSpeech::Noun => "noun",
+            PartOfSpeech::Verb => "verb",
+            PartOfSpeech::Adjective => "adjective",
+            PartOfSpeech::Adverb => "adverb",
+            PartOfSpeech::ProperNoun => "properNoun",
+            PartOfSpeech::Other => "other",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<PartOfSpeech> {
+        match normalize_value(value).as_str() {
+            "noun" | "n" | "substantive" => Some(PartOfSpeech::Noun),
+            "verb" | "v" => Some(PartOfSpeech::Verb),
+            "adjective" | "adj" => Some(PartOfSpeech::Adjective),
+            "adverb" | "adv" => Some(PartOfSpeech::Adverb),
+            "propernoun" | "propern" => Some(PartOfSpeech::ProperNoun),
+            "other" => Some(PartOfSpeech::Other),
+            _ => None,
+        }
+    }
+}
+
+/// Grammatical gender, named as in TBX `grammaticalGender`. `common` covers
+/// the merged masculine/feminine gender of Dutch and the Scandinavian languages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GrammaticalGender {
+    Masculine,
+    Feminine,
+    Neuter,
+    Common,
+    OtherGender,
+}
+
+impl GrammaticalGender {
+    pub const ALL: [GrammaticalGender; 5] = [
+        GrammaticalGender::Masculine,
+        GrammaticalGender::Feminine,
+        GrammaticalGender::Neuter,
+        GrammaticalGender::Common,
+        GrammaticalGender::OtherGender,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrammaticalGender::Masculine => "masculine",
+            GrammaticalGender::Feminine => "feminine",
+            GrammaticalGender::Neuter => "neuter",
+            GrammaticalGender::Common => "common",
+            GrammaticalGender::OtherGender => "otherGender",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<GrammaticalGender> {
+        match normalize_value(value).as_str() {
+            "masculine" | "masc" | "m" => Some(GrammaticalGender::Masculine),
+            "feminine" | "fem" | "f" => Some(GrammaticalGender::Feminine),
+            "neuter" | "neut" | "n" => Some(GrammaticalGender::Neuter),
+            "common" | "c" => Some(GrammaticalGender::Common),
+            "othergender" | "other" => Some(GrammaticalGender::OtherGender),
+            _ => None,
+        }
+    }
+}
+
+/// Grammatical number, named as in TBX `grammaticalNumber`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GrammaticalNumber {
+    Singular,
+    Plural,
+    Dual,
+    Mass,
+    OtherNumber,
+}
+
+impl GrammaticalNumber {
+    pub const ALL: [GrammaticalNumber; 5] = [
+        GrammaticalNumber::Singular,
+        GrammaticalNumber::Plural,
+        GrammaticalNumber::Dual,
+        GrammaticalNumber::Mass,
+        GrammaticalNumber::OtherNumber,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrammaticalNumber::Singular => "singular",
+            GrammaticalNumber::Plural => "plural",
+            GrammaticalNumber::Dual => "dual",
+            GrammaticalNumber::Mass => "mass",
+            GrammaticalNumber::OtherNumber => "otherNumber",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<GrammaticalNumber> {
+        match normalize_value(value).as_str() {
+            "singular" | "sg" | "sing" => Some(GrammaticalNumber::Singular),
+            "plural" | "pl" => Some(GrammaticalNumber::Plural),
+            "dual" => Some(GrammaticalNumber::Dual),
+            "mass" | "uncountable" => Some(GrammaticalNumber::Mass),
+            "othernumber" | "other" => Some(GrammaticalNumber::OtherNumber),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a value from a controlled list where an empty string, as sent by
+/// forms, means none.
+fn deserialize_controlled<'de, D, T>(
+    deserializer: D,
+    list: &str,
+    parse: fn(&str) -> Option<T>,
+) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => parse(value)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown {list}: {value}"))),
+    }
+}
+
+pub fn deserialize_status<'de, D>(deserializer: D) -> Result<Option<TermStatus>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_controlled(deserializer, "term status", TermStatus::parse)
+}
+
+pub fn deserialize_part_of_speech<'de, D>(deserializer: D) -> Result<Option<PartOfSpeech>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_controlled(deserializer, "part of speech", PartOfSpeech::parse)
+}
+
+pub fn deserialize_gender<'de, D>(deserializer: D) -> Result<Option<GrammaticalGender>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_controlled(deserializer, "grammatical gender", GrammaticalGender::parse)
+}
+
+pub fn deserialize_number<'de, D>(deserializer: D) -> Result<Option<GrammaticalNumber>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_controlled(deserializer, "grammatical number", GrammaticalNumber::parse)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TermLanguageSet {
     pub language: Option<String>,
@@ -32,6 +273,17 @@ pub struct TermLanguageSet {
     pub url: Option<String>,
     pub context: Option<String>,
     pub definition: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_status")]
+    pub status: Option<TermStatus>,
+    #[serde(default, deserialize_with = "deserialize_part_of_speech")]
+    pub part_of_speech: Option<PartOfSpeech>,
+    #[serde(default, deserialize_with = "deserialize_gender")]
+    pub gender: Option<GrammaticalGender>,
+    #[serde(default, deserialize_with = "deserialize_number")]
+    pub number: Option<GrammaticalNumber>,
+    /// The plural or another inflected form translators need.
+    #[serde(default)]
+    pub inflection: Option<String>,
 }
 
 impl Default for TermLanguageSet {
@@ -52,6 +304,11 @@ impl Default for TermLanguageSet {
             url: None,
             context: None,
             definition: None,
+            status: None,
+            part_of_speech: None,
+            gender: None,
+            number: None,
+            inflection: None,
         }
     }
 }
@@ -125,6 +382,31 @@ impl fmt::Display for Dictionary {
                     "\tDefinition: {}\n",
                     lang_set.definition.as_deref().unwrap_or("")
                 )?;
+                writeln!(
+                    f,
+                    "\tStatus: {}",
+                    lang_set.status.map_or("", |status| status.as_str())
+                )?;
+                writeln!(
+                    f,
+                    "\tPart of Speech: {}",
+                    lang_set.part_of_speech.map_or("", |pos| pos.as_str())
+                )?;
+                writeln!(
+                    f,
+                    "\tGender: {}",
+                    lang_set.gender.map_or("", |gender| gender.as_str())
+                )?;
+                writeln!(
+                    f,
+                    "\tNumber: {}",
+                    lang_set.number.map_or("", |number| number.as_str())
+                )?;
+                writeln!(
+                    f,
+                    "\tInflection: {}",
+                    lang_set.inflection.as_deref().unwrap_or("")
+                )?;
                 write!(f, "\n")?;
             }
             write!(f, "\n")?;
@@ -206,6 +488,21 @@ impl Dictionary {
                         }
                         "TS_Remark" => lang_set_obj.remark = Some(term_note.text().to_string()),
                         "TS_Hyperlink" => lang_set_obj.url = Some(term_note.text().to_string()),
+                        "normativeAuthorization" | "administrativeStatus" => {
+                            lang_set_obj.status = TermStatus::parse(term_note.text())
+                        }
+                        "partOfSpeech" => {
+                            lang_set_obj.part_of_speech = PartOfSpeech::parse(term_note.text())
+                        }
+                        "grammaticalGender" => {
+                            lang_set_obj.gender = GrammaticalGender::parse(term_note.text())
+                        }
+                        "grammaticalNumber" => {
+                            lang_set_obj.number = GrammaticalNumber::parse(term_note.text())
+                        }
+                        "inflection" | "plural" | "TS_Plural" => {
+                            lang_set_obj.inflection = Some(term_note.text().to_string())
+                        }
                         _ => {}
                     }
                 }
@@ -235,6 +532,19 @@ impl Dictionary {
                     }
                 }
 
+                // TermStar keeps the status in the term type or attributes.
+                if lang_set_obj.status.is_none() {
+                    lang_set_obj.status = [&lang_set_obj.term_type, &lang_set_obj.attributes]
+                        .into_iter()
+                        .flatten()
+                        .find_map(|value| TermStatus::find_in(value));
+                }
+                // Older exports only name the part of speech as the term type.
+                if lang_set_obj.part_of_speech.is_none() {
+                    lang_set_obj.part_of_speech =
+                        lang_set_obj.term_type.as_deref().and_then(PartOfSpeech::parse);
+                }
+
                 entry.language_sets.push(lang_set_obj);
             }
             self.add_entry(entry);
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use crate::dictionary::database::*;
+use crate::dictionary::taxonomy::normalize_subject_path;
 use crate::import::parse::*;
 use axum::extract::State;
 use rusqlite::Result;
@@ -23,17 +24,34 @@ pub fn create_term_to_insert(term: &TermLanguageSet) -> TermLanguageSet {
         url: term.url.clone(),
         context: term.context.clone(),
         definition: term.definition.clone(),
+        status: term.status,
+        part_of_speech: term.part_of_speech,
+        gender: term.gender,
+        number: term.number,
+        inflection: term.inflection.clone(),
     }
 }
 pub async fn import_dictionary_data(
     State(app_state): State<Arc<AppState>>,
     filename: &str,
+    subject_separator: &str,
 ) -> Result<(), String> {
     info!("Importing dictionary from file: {}", filename);
 
     let mut dictionary = Dictionary::new();
     dictionary.import_from_xml(filename);
 
+    for lang_set in dictionary
+        .entries
+        .iter_mut()
+        .flat_map(|entry| entry.language_sets.iter_mut())
+    {
+        lang_set.subject = lang_set
+            .subject
+            .as_deref()
+            .and_then(|subject| normalize_subject_path(subject, subject_separator));
+    }
+
     let _ = dictionary.serialize_to_json("processed_dictionary.json");
 
     create_terms_table(State(app_state.clone()));
@@ -45,9 +63,6 @@ pub async fn import_dictionary_data(
         }
     }
 
-    let unique_values_result = extract_and_insert_unique_values(State(app_state.clone()));
-    handle_insert_unique_values_result(unique_values_result);
-
     info!("Dictionary import completed");
     Ok(())
 }
@@ -2,7 +2,7 @@ use axum::extract::{DefaultBodyLimit, State};
 use axum::routing::{delete, get, post};
 use axum::Router;
 use clap::{arg, command, Parser};
-use term_squire::dictionary::{database::*, handlers::*};
+use term_squire::dictionary::{database::*, handlers::*, recycle::*};
 use term_squire::init::*;
 use tracing::info;
 
@@ -19,6 +19,9 @@ struct Args {
     /// Port number used for server
     #[arg(short, long, default_value_t = 1234)]
     port: u64,
+    /// Days deleted terms are kept in the recycle bin, 0 to keep them until purged
+    #[arg(long, default_value_t = DEFAULT_RETENTION_DAYS)]
+    retention_days: u64,
 }
 
 #[tokio::main]
@@ -31,24 +34,78 @@ async fn main() -> Result<(), anyhow::Error> {
     let app_state = init_app_state(db_info.clone())?;
     init_db(State(app_state.clone()))?;
 
+    if args.retention_days > 0 {
+        tokio::spawn(purge_expired_periodically(
+            app_state.clone(),
+            args.retention_days,
+        ));
+    }
+
     let app = Router::new()
         .route("/add_term_set", post(handle_add_term_set))
+        .route("/batch_lookup", post(handle_batch_lookup))
+        .route("/bulk_edit", post(handle_bulk_edit))
+        .route("/bulk_preview", post(handle_bulk_preview))
         .route("/database_management", get(handle_database_management))
+        .route("/delete_subject", delete(handle_delete_subject))
         .route("/delete_term", delete(handle_delete_term))
+        .route("/delete_term_set", delete(handle_delete_term_set))
+        .route("/deleted_terms", get(handle_get_deleted_terms))
         .route("/download_db_file", get(handle_download_db_file))
+        .route("/duplicate_terms", get(handle_get_duplicate_terms))
+        .route("/duplicates", get(handle_duplicates))
+        .route("/export", get(handle_export))
         .route("/import_dictionary", post(handle_import_dictionary_data))
         .route("/import_form", get(handle_import_form))
         .route("/insert_form", get(handle_insert_form))
+        .route("/insert_subject", post(handle_insert_subject))
         .route("/insert_term", post(handle_insert_term))
+        .route("/lookup", get(handle_lookup))
+        .route("/lookup_form", get(handle_lookup_form))
+        .route("/merge_term_sets", post(handle_merge_term_sets))
+        .route("/merge_values", post(handle_merge_values))
+        .route("/move_term", post(handle_move_term))
+        .route(
+            "/picklist_value",
+            post(handle_add_picklist_value).delete(handle_remove_picklist_value),
+        )
+        .route("/picklist_settings", post(handle_picklist_settings))
+        .route("/picklists", get(handle_get_picklists))
+        .route("/picklists_form", get(handle_picklists_form))
+        .route("/purge_term", delete(handle_purge_term))
+        .route("/qa", post(handle_qa))
+        .route("/qa_xliff", post(handle_qa_xliff))
+        .route("/qa_xliff_form", get(handle_qa_xliff_form))
+        .route("/recognize", post(handle_recognize))
+        .route("/recognize_form", get(handle_recognize_form))
+        .route("/recycle_bin", get(handle_recycle_bin))
+        .route("/restore_term", post(handle_restore_term))
+        .route("/revert_term", post(handle_revert_term))
+        .route(
+            "/relations",
+            get(handle_get_relations)
+                .post(handle_add_relation)
+                .delete(handle_remove_relation),
+        )
         .route("/search", get(handle_search_terms))
+        .route("/seed_picklist", post(handle_seed_picklist))
+        .route("/search_facets", get(handle_search_facets))
         .route(
             "/search_terms_by_term_set_id",
             get(handle_search_terms_by_term_set_id),
         )
         .route("/settings", get(handle_get_settings))
+        .route("/split_term_set", post(handle_split_term_set))
+        .route("/subjects", get(handle_get_subjects))
+        .route("/term_history", get(handle_get_term_history))
+        .route("/term_version", get(handle_get_term_version))
         .route("/terms", get(handle_terms))
         .route("/term_detail", get(handle_get_term_details))
-        .route("/update_term", post(handle_update_term))
+        .route("/update_subject", post(handle_update_subject))
+        .route(
+            "/update_term",
+            post(handle_update_term).patch(handle_update_term),
+        )
         .route("/upload_db_file", post(handle_upload_db_file))
         .layer(DefaultBodyLimit::max(10 * 1024 * 1024))
         .with_state(app_state)
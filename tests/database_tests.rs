@@ -4,7 +4,28 @@ mod tests {
 
     use axum::extract::State;
     use lazy_static::lazy_static;
+    use term_squire::dictionary::bulk::*;
     use term_squire::dictionary::database::*;
+    use term_squire::dictionary::duplicates::*;
+    use term_squire::dictionary::export::*;
+    use term_squire::dictionary::facets::*;
+    use term_squire::dictionary::fulltext::*;
+    use term_squire::dictionary::history::*;
+    use term_squire::dictionary::language::*;
+    use term_squire::dictionary::lookup::*;
+    use term_squire::dictionary::paging::*;
+    use term_squire::dictionary::patch::*;
+    use term_squire::dictionary::picklist::*;
+    use term_squire::dictionary::qa::*;
+    use term_squire::dictionary::query::*;
+    use term_squire::dictionary::recognize::*;
+    use term_squire::dictionary::recycle::*;
+    use term_squire::dictionary::relations::*;
+    use term_squire::dictionary::search::*;
+    use term_squire::dictionary::skos::*;
+    use term_squire::dictionary::taxonomy::*;
+    use term_squire::dictionary::term_sets::*;
+    use term_squire::dictionary::xliff::*;
     use term_squire::import::parse::*;
     use term_squire::import::process::*;
 
@@ -35,6 +56,11 @@ mod tests {
             url: None,
             context: None,
             definition: None,
+            status: None,
+            part_of_speech: None,
+            gender: None,
+            number: None,
+            inflection: None,
         };
         static ref TERM_SET_2: TermLanguageSet = TermLanguageSet {
             term: Some("term_2".to_string()),
@@ -52,6 +78,11 @@ mod tests {
             url: None,
             context: None,
             definition: None,
+            status: None,
+            part_of_speech: None,
+            gender: None,
+            number: None,
+            inflection: None,
         };
         static ref TERM_SET_3: TermLanguageSet = TermLanguageSet {
             term: Some("term_3".to_string()),
@@ -69,6 +100,11 @@ mod tests {
             url: None,
             context: Some("context for term_3".to_string()),
             definition: Some("Definition of term_3".to_string()),
+            status: None,
+            part_of_speech: None,
+            gender: None,
+            number: None,
+            inflection: None,
         };
         static ref TERM_SET_4: TermLanguageSet = TermLanguageSet {
             term: None,
@@ -86,6 +122,11 @@ mod tests {
             url: None,
             context: None,
             definition: None,
+            status: None,
+            part_of_speech: None,
+            gender: None,
+            number: None,
+            inflection: None,
         };
     }
 
@@ -121,6 +162,14 @@ mod tests {
         add_term(State(app_state.clone()), term_set)
     }
 
+    fn test_term(term: &str, language: &str) -> TermLanguageSet {
+        TermLanguageSet {
+            term: Some(term.to_string()),
+            language: Some(language.to_string()),
+            ..Default::default()
+        }
+    }
+
     fn assert_term_exists(app_state: &Arc<AppState>, term: &str, language: &str) {
         let terms = search_terms(State(app_state.clone()), term, language).expect("Search failed");
         assert_eq!(terms.len(), 1);
@@ -225,6 +274,1795 @@ mod tests {
         remove_test_db(&app_state);
     }
 
+    #[test]
+    fn test_db_search_folds_case_width_and_accents() {
+        let app_state = create_test_app_state("test_db_search_folds_case_width_and_accents");
+        let cafe = TermLanguageSet {
+            term: Some("café".to_string()),
+            language: Some("fr".to_string()),
+            ..Default::default()
+        };
+        let overview = TermLanguageSet {
+            term: Some("Übersicht".to_string()),
+            language: Some("de".to_string()),
+            ..Default::default()
+        };
+        add_term_wrapper(&app_state, &cafe).unwrap();
+        add_term_wrapper(&app_state, &overview).unwrap();
+
+        let ignore_accents = SearchOptions {
+            ignore_accents: true,
+            ..SearchOptions::default()
+        };
+
+        for use_cache in [false, true] {
+            if use_cache {
+                let all_terms = get_all_terms(State(app_state.clone())).unwrap();
+                *app_state.terms_cache.lock().unwrap() = Some(all_terms);
+            }
+
+            let search = |term: &str, language: &str, options: &SearchOptions| {
+                search_terms_with_options(State(app_state.clone()), term, language, options)
+                    .unwrap()
+                    .len()
+            };
+
+            assert_eq!(search("ÜBERSICHT", "de", &SearchOptions::default()), 1);
+            assert_eq!(search("ｃａｆé", "fr", &SearchOptions::default()), 1);
+            assert_eq!(search("Cafe", "fr", &SearchOptions::default()), 0);
+            assert_eq!(search("Cafe", "fr", &ignore_accents), 1);
+            assert_eq!(search("uber", "", &ignore_accents), 1);
+            assert_eq!(search("", "", &SearchOptions::default()), 2);
+        }
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_search_ranked_fuzzy() {
+        let app_state = create_test_app_state("test_db_search_ranked_fuzzy");
+        for term in ["brake pad", "brake", "disc brake", "brakes", "clutch"] {
+            add_term_wrapper(&app_state, &test_term(term, "en")).unwrap();
+        }
+
+        let options = SearchOptions::default();
+        let hits = search_terms_ranked(
+            State(app_state.clone()),
+            "brake",
+            "en",
+            &options,
+            SearchMode::Substring,
+            DEFAULT_MIN_SCORE,
+        )
+        .unwrap();
+        let kinds: Vec<MatchKind> = hits.iter().map(|hit| hit.match_kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                MatchKind::Exact,
+                MatchKind::Prefix,
+                MatchKind::Prefix,
+                MatchKind::Substring
+            ]
+        );
+        assert_eq!(hits[1].entry.term_language_set.term.as_deref(), Some("brakes"));
+
+        let typo = |min_score: f64| {
+            search_terms_ranked(
+                State(app_state.clone()),
+                "braek pda",
+                "en",
+                &options,
+                SearchMode::Fuzzy,
+                min_score,
+            )
+            .unwrap()
+        };
+        let hits = typo(DEFAULT_MIN_SCORE);
+        assert_eq!(hits[0].match_kind, MatchKind::Fuzzy);
+        assert_eq!(hits[0].entry.term_language_set.term.as_deref(), Some("brake pad"));
+        assert!(hits.iter().all(|hit| hit.score >= DEFAULT_MIN_SCORE));
+        assert!(hits
+            .iter()
+            .all(|hit| hit.entry.term_language_set.term.as_deref() != Some("clutch")));
+        assert!(typo(1.0).is_empty());
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_search_fulltext() {
+        let app_state = create_test_app_state("test_db_search_fulltext");
+        create_fulltext_index(State(app_state.clone())).unwrap();
+
+        let brake_pad = TermLanguageSet {
+            term: Some("brake pad".to_string()),
+            language: Some("en".to_string()),
+            definition: Some("Friction <material> pressed against the brake disc".to_string()),
+            ..Default::default()
+        };
+        let clutch = TermLanguageSet {
+            term: Some("clutch".to_string()),
+            language: Some("en".to_string()),
+            context: Some("Press the clutch pedal before changing gear".to_string()),
+            remark: Some("obsolete".to_string()),
+            ..Default::default()
+        };
+        add_term_wrapper(&app_state, &brake_pad).unwrap();
+        add_term_wrapper(&app_state, &clutch).unwrap();
+
+        let search = |query: &str| search_fulltext(State(app_state.clone()), query, "").unwrap();
+
+        let hits = search("\"brake disc\"");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].match_kind, MatchKind::Fulltext);
+        let snippet = hits[0].snippet.as_deref().unwrap();
+        assert!(snippet.contains("<mark>brake disc</mark>"));
+        assert!(snippet.contains("&lt;material&gt;"));
+
+        assert_eq!(search("pedal OR friction").len(), 2);
+        assert_eq!(search("friction NOT obsolete").len(), 1);
+        assert_eq!(search("pedal NOT obsolete").len(), 0);
+        assert_eq!(search("remark: obsolete").len(), 1);
+        assert!(search_fulltext(State(app_state.clone()), "\"unbalanced", "").is_err());
+
+        let clutch_id = get_term_set_id(State(app_state.clone()), "clutch", "en")
+            .unwrap()
+            .unwrap();
+        let update = TermLanguageSet {
+            context: Some("Depress before shifting".to_string()),
+            ..Default::default()
+        };
+        update_term(State(app_state.clone()), clutch_id, &update).unwrap();
+        assert_eq!(search("pedal").len(), 0);
+        assert_eq!(search("shifting").len(), 1);
+
+        delete_term(State(app_state.clone()), clutch_id, None).unwrap();
+        assert_eq!(search("shifting").len(), 0);
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query =
+            parse_query("subject:automotive lang:de-DE updated:>2024-01-01 \"brake pad\" -remark:obsolete")
+                .unwrap();
+        assert_eq!(query.clauses.len(), 5);
+        assert_eq!(
+            query.clauses[2].condition,
+            Condition::Field {
+                field: Field::Updated,
+                comparison: Comparison::Greater,
+                value: Value::Date(chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+            }
+        );
+        assert_eq!(query.clauses[3].condition, Condition::Text("brake pad".to_string()));
+        assert!(query.clauses[4].negated);
+        assert_eq!(query.free_text(), "brake pad");
+
+        let err = parse_query("brake colour:red").unwrap_err();
+        assert_eq!(err.column, 7);
+        assert!(err.message.contains("unknown field 'colour'"));
+        assert_eq!(parse_query("\"brake pad").unwrap_err().column, 1);
+        assert!(parse_query("updated:>yesterday").unwrap_err().message.contains("invalid date"));
+        assert!(parse_query("subject:>a").unwrap_err().message.contains("not supported"));
+        assert!(parse_query("subject:").unwrap_err().message.contains("missing value"));
+    }
+
+    #[test]
+    fn test_db_search_by_query() {
+        let app_state = create_test_app_state("test_db_search_by_query");
+        let day = |d: u32| {
+            chrono::NaiveDate::from_ymd_opt(2024, 1, d)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+                .and_utc()
+                .timestamp()
+        };
+        let brake_pad = TermLanguageSet {
+            term: Some("Bremsbelag".to_string()),
+            language: Some("de-DE".to_string()),
+            subject: Some("Automotive".to_string()),
+            creator_id: Some("Alice".to_string()),
+            update_timestamp: Some(day(2)),
+            ..Default::default()
+        };
+        let old_pad = TermLanguageSet {
+            term: Some("Bremsklotz".to_string()),
+            language: Some("de-DE".to_string()),
+            subject: Some("automotive".to_string()),
+            creator_id: Some("Alice".to_string()),
+            update_timestamp: Some(day(1)),
+            remark: Some("obsolete".to_string()),
+            ..Default::default()
+        };
+        let english = TermLanguageSet {
+            term: Some("brake pad".to_string()),
+            language: Some("en".to_string()),
+            subject: Some("automotive".to_string()),
+            creator_id: Some("Bob".to_string()),
+            update_timestamp: Some(day(3)),
+            ..Default::default()
+        };
+        add_term_wrapper(&app_state, &brake_pad).unwrap();
+        add_term_wrapper(&app_state, &old_pad).unwrap();
+        add_term_wrapper(&app_state, &english).unwrap();
+        add_term_wrapper(&app_state, &test_term("Bremse", "de")).unwrap();
+
+        let search = |q: &str| {
+            let query = parse_query(q).unwrap();
+            search_terms_by_query(State(app_state.clone()), &query, "", &SearchOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.term_language_set.term.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(search("subject:automotive lang:de creator:alice").len(), 2);
+        assert_eq!(search("lang:de-de -remark:obsolete"), vec!["Bremsbelag"]);
+        assert_eq!(search("updated:>2024-01-01"), vec!["Bremsbelag", "brake pad"]);
+        assert_eq!(search("updated:2024-01-01"), vec!["Bremsklotz"]);
+        assert_eq!(search("updated:<=2024-01-02 brems").len(), 2);
+        assert_eq!(search("\"brake pad\" creator:=bob"), vec!["brake pad"]);
+        assert_eq!(search("lang:d").len(), 0);
+        // Terms without a date are not after it either.
+        assert_eq!(search("-updated:>2024-01-01 brems"), vec!["Bremsklotz", "Bremse"]);
+        assert_eq!(search("").len(), 4);
+
+        let query = parse_query("bremsbelag").unwrap();
+        let results =
+            search_terms_by_query(State(app_state.clone()), &query, "de-DE", &SearchOptions::default())
+                .unwrap();
+        let hits = rank_query_hits(&results, &query.free_text(), &SearchOptions::default());
+        assert_eq!(hits[0].match_kind, MatchKind::Exact);
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let app_state = create_test_app_state("test_export_round_trip");
+        add_term_wrapper(&app_state, &TERM_SET_1).unwrap();
+        add_term_wrapper(&app_state, &TERM_SET_3).unwrap();
+        let terms = get_all_terms(State(app_state.clone())).unwrap();
+
+        let json: Vec<ExportedTermSet> = serde_json::from_str(&export_json(&terms, &[]).unwrap()).unwrap();
+        assert_eq!(json.len(), 2);
+        assert_eq!(json[0].terms[0].term_language_set.term.as_deref(), Some("term_1"));
+
+        let tbx = export_tbx(&terms, &[]).unwrap();
+        let mut dictionary = Dictionary::new();
+        dictionary.process_entries(elementtree::Element::from_reader(tbx.as_bytes()).unwrap());
+        assert_eq!(dictionary.entries.len(), 2);
+        let term_3 = &dictionary.entries[1].language_sets[0];
+        assert_eq!(term_3.term.as_deref(), Some("term_3"));
+        assert_eq!(term_3.language.as_deref(), Some("fr"));
+        assert_eq!(term_3.definition.as_deref(), Some("Definition of term_3"));
+        assert_eq!(term_3.creator_id.as_deref(), Some("user_3"));
+        assert_eq!(term_3.creation_timestamp, TERM_SET_3.creation_timestamp);
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_relations() {
+        assert_eq!(RelationType::parse("see-also"), Some(RelationType::SeeAlso));
+        assert_eq!(RelationType::Broader.inverse(), RelationType::Narrower);
+        assert_eq!(RelationType::Antonym.inverse(), RelationType::Antonym);
+
+        let app_state = create_test_app_state("test_db_relations");
+        create_relations_table(State(app_state.clone())).unwrap();
+        let term = |term: &str| test_term(term, "en");
+        add_term_wrapper(&app_state, &term("brake")).unwrap();
+        add_term_wrapper(&app_state, &term("disc brake")).unwrap();
+        add_term_wrapper(&app_state, &term("accelerator")).unwrap();
+
+        let relation = |source_set_id: i32, relation: RelationType, target_set_id: i32| TermSetRelation {
+            source_set_id,
+            relation,
+            target_set_id,
+        };
+        let disc_brake_broader = relation(2, RelationType::Broader, 1);
+        assert!(add_relation(State(app_state.clone()), &disc_brake_broader).unwrap());
+        assert!(!add_relation(State(app_state.clone()), &disc_brake_broader).unwrap());
+        assert!(add_relation(State(app_state.clone()), &relation(1, RelationType::Antonym, 3)).unwrap());
+        assert!(matches!(
+            add_relation(State(app_state.clone()), &relation(1, RelationType::Related, 1)),
+            Err(RelationError::SameTermSet(1))
+        ));
+        assert!(matches!(
+            add_relation(State(app_state.clone()), &relation(1, RelationType::Related, 9)),
+            Err(RelationError::UnknownTermSet(9))
+        ));
+
+        // The inverse relations are kept in step.
+        let related = get_related_term_sets(State(app_state.clone()), 1).unwrap();
+        let summary: Vec<(RelationType, i32)> =
+            related.iter().map(|related| (related.relation, related.term_set_id)).collect();
+        assert_eq!(summary, vec![(RelationType::Narrower, 2), (RelationType::Antonym, 3)]);
+        assert_eq!(related[0].terms[0].term_language_set.term.as_deref(), Some("disc brake"));
+        assert_eq!(
+            get_relations(State(app_state.clone()), Some(&[3])).unwrap(),
+            vec![relation(3, RelationType::Antonym, 1)]
+        );
+
+        let terms = get_all_terms(State(app_state.clone())).unwrap();
+        let relations = get_relations(State(app_state.clone()), None).unwrap();
+        let json: Vec<ExportedTermSet> =
+            serde_json::from_str(&export_json(&terms, &relations).unwrap()).unwrap();
+        assert_eq!(
+            json[1].relations,
+            vec![ExportedRelation { relation: RelationType::Broader, term_set_id: 1 }]
+        );
+        let tbx = export_tbx(&terms, &relations).unwrap();
+        assert!(tbx.contains(r#"<descrip target="1" type="broaderConceptGeneric">brake</descrip>"#));
+        let mut dictionary = Dictionary::new();
+        dictionary.process_entries(elementtree::Element::from_reader(tbx.as_bytes()).unwrap());
+        assert_eq!(dictionary.entries.len(), 3);
+
+        assert!(remove_relation(State(app_state.clone()), &relation(1, RelationType::Narrower, 2)).unwrap());
+        assert!(get_relations(State(app_state.clone()), Some(&[2])).unwrap().is_empty());
+
+        // Relations of deleted term sets are kept until the term set is purged.
+        delete_term(State(app_state.clone()), 3, None).unwrap();
+        assert_eq!(prune_relations(State(app_state.clone())).unwrap(), 0);
+        assert!(get_related_term_sets(State(app_state.clone()), 1).unwrap().is_empty());
+        assert!(purge_term(State(app_state.clone()), 3).unwrap());
+        assert!(get_relations(State(app_state.clone()), None).unwrap().is_empty());
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_term_status() {
+        assert_eq!(TermStatus::parse("preferredTerm-admn-sts"), Some(TermStatus::Preferred));
+        assert_eq!(TermStatus::parse("Admitted"), Some(TermStatus::Admitted));
+        assert_eq!(TermStatus::parse("supersededTerm"), Some(TermStatus::Deprecated));
+        assert_eq!(TermStatus::parse("standardized"), Some(TermStatus::Preferred));
+        assert_eq!(TermStatus::parse("noun"), None);
+        assert_eq!(TermStatus::find_in("customer; Forbidden"), Some(TermStatus::Forbidden));
+        let set: TermLanguageSet = serde_json::from_str(r#"{"term": "pad", "status": ""}"#).unwrap();
+        assert_eq!(set.status, None);
+        assert!(serde_json::from_str::<TermLanguageSet>(r#"{"status": "banned"}"#).is_err());
+
+        let app_state = create_test_app_state("test_db_term_status");
+        let term = |term: &str, status: Option<TermStatus>, attributes: Option<&str>| TermLanguageSet {
+            status,
+            attributes: attributes.map(str::to_string),
+            ..test_term(term, "en")
+        };
+        add_term_wrapper(&app_state, &term("brake pad", Some(TermStatus::Preferred), None)).unwrap();
+        add_term_wrapper(&app_state, &term("brake block", Some(TermStatus::Forbidden), None)).unwrap();
+        add_term_wrapper(&app_state, &term("brake shoe", None, Some("deprecated"))).unwrap();
+
+        let search = |q: &str| {
+            let query = parse_query(q).unwrap();
+            search_terms_by_query(State(app_state.clone()), &query, "", &SearchOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.term_language_set.term.unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(search("status:forbidden"), vec!["brake block"]);
+        assert_eq!(search("brake -status:preferred").len(), 2);
+
+        let terms = get_all_terms(State(app_state.clone())).unwrap();
+        let block = terms.iter().find(|t| t.term_language_set.term.as_deref() == Some("brake block")).unwrap();
+        assert!(is_forbidden(block));
+        let tbx = export_tbx(&terms, &[]).unwrap();
+        assert!(tbx.contains(r#"<termNote type="normativeAuthorization">forbiddenTerm</termNote>"#));
+        let mut dictionary = Dictionary::new();
+        dictionary.process_entries(elementtree::Element::from_reader(tbx.as_bytes()).unwrap());
+        let statuses: Vec<Option<TermStatus>> = dictionary
+            .entries
+            .iter()
+            .map(|entry| entry.language_sets[0].status)
+            .collect();
+        assert_eq!(statuses, vec![
+                Some(TermStatus::Preferred),
+                Some(TermStatus::Forbidden),
+                Some(TermStatus::Deprecated)
+            ]);
+
+        // Tables created before the status column get it back-filled from the attributes.
+        let conn = connect_db(State(app_state.clone())).unwrap();
+        conn.execute_batch("ALTER TABLE terms DROP COLUMN status;").unwrap();
+        create_terms_table(State(app_state.clone())).unwrap();
+        assert_eq!(search("status:deprecated"), vec!["brake shoe"]);
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_grammar() {
+        assert_eq!(PartOfSpeech::parse("proper noun"), Some(PartOfSpeech::ProperNoun));
+        assert_eq!(PartOfSpeech::parse("Adj."), Some(PartOfSpeech::Adjective));
+        assert_eq!(GrammaticalGender::parse("m"), Some(GrammaticalGender::Masculine));
+        assert_eq!(GrammaticalNumber::parse("pl"), Some(GrammaticalNumber::Plural));
+        let set: TermLanguageSet =
+            serde_json::from_str(r#"{"term": "Haus", "gender": "neuter", "number": ""}"#).unwrap();
+        assert_eq!(set.gender, Some(GrammaticalGender::Neuter));
+        assert_eq!(set.number, None);
+        assert!(serde_json::from_str::<TermLanguageSet>(r#"{"gender": "vegetable"}"#).is_err());
+
+        let app_state = create_test_app_state("test_db_grammar");
+        let german = |term: &str, gender: GrammaticalGender, inflection: &str| TermLanguageSet {
+            part_of_speech: Some(PartOfSpeech::Noun),
+            gender: Some(gender),
+            number: Some(GrammaticalNumber::Singular),
+            inflection: Some(inflection.to_string()),
+            ..test_term(term, "de")
+        };
+        add_term_wrapper(&app_state, &german("Bremsbelag", GrammaticalGender::Masculine, "Bremsbeläge")).unwrap();
+        add_term_wrapper(&app_state, &german("Bremsscheibe", GrammaticalGender::Feminine, "Bremsscheiben")).unwrap();
+        add_term_wrapper(
+            &app_state,
+            &TermLanguageSet {
+                term: Some("bremsen".to_string()),
+                language: Some("de".to_string()),
+                term_type: Some("verb".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let search = |q: &str| {
+            let query = parse_query(q).unwrap();
+            search_terms_by_query(State(app_state.clone()), &query, "", &SearchOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.term_language_set.term.unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(search("gender:f"), vec!["Bremsscheibe"]);
+        assert_eq!(search("pos:noun -gender:masculine"), vec!["Bremsscheibe"]);
+        assert_eq!(search("inflection:beläge"), vec!["Bremsbelag"]);
+        assert_eq!(
+            parse_query("gender:vegetable").unwrap_err().message,
+            "invalid value 'vegetable' for field 'gender', expected one of: masculine, feminine, neuter, common, otherGender"
+        );
+
+        let terms = get_all_terms(State(app_state.clone())).unwrap();
+        let tbx = export_tbx(&terms, &[]).unwrap();
+        assert!(tbx.contains(r#"<termNote type="grammaticalGender">feminine</termNote>"#));
+        let mut dictionary = Dictionary::new();
+        dictionary.process_entries(elementtree::Element::from_reader(tbx.as_bytes()).unwrap());
+        let pad = &dictionary.entries[0].language_sets[0];
+        assert_eq!(pad.part_of_speech, Some(PartOfSpeech::Noun));
+        assert_eq!(pad.gender, Some(GrammaticalGender::Masculine));
+        assert_eq!(pad.number, Some(GrammaticalNumber::Singular));
+        assert_eq!(pad.inflection.as_deref(), Some("Bremsbeläge"));
+        assert_eq!(dictionary.entries[2].language_sets[0].part_of_speech, Some(PartOfSpeech::Verb));
+
+        // The part of speech of older tables is taken from the term type.
+        let conn = connect_db(State(app_state.clone())).unwrap();
+        conn.execute_batch("ALTER TABLE terms DROP COLUMN part_of_speech;").unwrap();
+        create_terms_table(State(app_state.clone())).unwrap();
+        assert_eq!(search("pos:verb"), vec!["bremsen"]);
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_picklists() {
+        let app_state = create_test_app_state("test_db_picklists");
+        create_picklist_tables(State(app_state.clone())).unwrap();
+        let source = |term: &str, source: &str| TermLanguageSet {
+            source: Some(source.to_string()),
+            ..test_term(term, "en")
+        };
+        add_term_wrapper(&app_state, &source("brake pad", "Automotive")).unwrap();
+        add_term_wrapper(&app_state, &source("brake disc", "Automotive")).unwrap();
+        add_term_wrapper(&app_state, &source("clutch", "automotive ")).unwrap();
+        add_term_wrapper(&app_state, &source("gearbox", "Auto")).unwrap();
+
+        // Free text until the picklist has values.
+        let mut set = source("axle", "Cars");
+        apply_picklists(State(app_state.clone()), &mut set).unwrap();
+        assert_eq!(set.source.as_deref(), Some("Cars"));
+
+        assert_eq!(seed_picklist(State(app_state.clone()), "source").unwrap(), 2);
+        let picklist = get_picklist(State(app_state.clone()), "source").unwrap();
+        assert_eq!(picklist.values, vec!["Auto", "Automotive"]);
+        assert!(!picklist.allow_new);
+
+        let mut set = source("axle", " AUTOMOTIVE");
+        set.term_type = Some("anything".to_string());
+        apply_picklists(State(app_state.clone()), &mut set).unwrap();
+        assert_eq!(set.source.as_deref(), Some("Automotive"));
+        assert_eq!(set.term_type.as_deref(), Some("anything"));
+        let mut set = source("axle", "");
+        apply_picklists(State(app_state.clone()), &mut set).unwrap();
+        assert_eq!(set.source, None);
+        let mut set = source("axle", "Cars");
+        assert!(matches!(
+            apply_picklists(State(app_state.clone()), &mut set),
+            Err(PicklistError::NotAllowed { .. })
+        ));
+
+        set_allow_new(State(app_state.clone()), "source", true).unwrap();
+        apply_picklists(State(app_state.clone()), &mut set).unwrap();
+        assert_eq!(
+            get_picklist(State(app_state.clone()), "source").unwrap().values,
+            vec!["Auto", "Automotive", "Cars"]
+        );
+
+        let changed = merge_values(
+            State(app_state.clone()),
+            "source",
+            &["Auto".to_string(), "automotive ".to_string()],
+            "Automotive",
+        )
+        .unwrap();
+        assert_eq!(changed, 2);
+        assert_eq!(
+            get_picklist(State(app_state.clone()), "source").unwrap().values,
+            vec!["Automotive", "Cars"]
+        );
+        assert!(matches!(
+            merge_values(State(app_state.clone()), "source", &["Cars".to_string()], " "),
+            Err(PicklistError::EmptyValue)
+        ));
+        let sources: Vec<Option<String>> = get_all_terms(State(app_state.clone()))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.term_language_set.source)
+            .collect();
+        assert!(sources.iter().all(|source| source.as_deref() == Some("Automotive")));
+
+        assert!(remove_picklist_value(State(app_state.clone()), "source", "Cars").unwrap());
+        assert!(matches!(
+            get_picklist(State(app_state.clone()), "colour"),
+            Err(PicklistError::UnknownField(_))
+        ));
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_subject_taxonomy() {
+        assert_eq!(
+            normalize_subject_path(" Engineering/Automotive//Brakes ", "/").as_deref(),
+            Some("Engineering > Automotive > Brakes")
+        );
+        assert_eq!(normalize_subject_path("a/b", "").as_deref(), Some("a/b"));
+        assert_eq!(normalize_subject_path(" > ", ">"), None);
+
+        let app_state = create_test_app_state("test_db_subject_taxonomy");
+        let subject = |term: &str, subject: &str| TermLanguageSet {
+            subject: Some(subject.to_string()),
+            ..test_term(term, "en")
+        };
+        add_term_wrapper(&app_state, &subject("brake pad", "Engineering > Automotive > Brakes")).unwrap();
+        add_term_wrapper(&app_state, &subject("chassis", "engineering>AUTOMOTIVE")).unwrap();
+        add_term_wrapper(&app_state, &subject("fuse", "Engineering > Electrical")).unwrap();
+        add_term_wrapper(&app_state, &subject("whisk", "Cooking")).unwrap();
+
+        let search = |q: &str| {
+            let query = parse_query(q).unwrap();
+            search_terms_by_query(State(app_state.clone()), &query, "", &SearchOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.term_language_set.term.unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(search("subject:=automotive"), vec!["brake pad", "chassis"]);
+        assert_eq!(search("subject:=Engineering"), vec!["brake pad", "chassis", "fuse"]);
+        assert_eq!(search("-subject:=Automotive"), vec!["fuse", "whisk"]);
+        assert_eq!(search("subject:brake"), vec!["brake pad"]);
+
+        let chassis = get_term_by_id(State(app_state.clone()), 2).unwrap().unwrap();
+        assert_eq!(
+            chassis.term_language_set.subject.as_deref(),
+            Some("Engineering > Automotive")
+        );
+        let names = |term_id: i32| {
+            get_term_subject_path(State(app_state.clone()), term_id)
+                .unwrap()
+                .into_iter()
+                .map(|node| node.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(1), vec!["Engineering", "Automotive", "Brakes"]);
+
+        let tree = get_subject_tree(State(app_state.clone())).unwrap();
+        assert_eq!(tree.iter().map(|node| node.name.as_str()).collect::<Vec<_>>(), vec!["Cooking", "Engineering"]);
+        let engineering = &tree[1];
+        assert_eq!(engineering.term_count, 3);
+        assert_eq!(engineering.children[0].path, "Engineering > Automotive");
+        assert_eq!(engineering.children[0].term_count, 2);
+
+        // Renaming and moving a subject rewrites the terms filed below it.
+        let automotive = engineering.children[0].id;
+        let electrical = engineering.children[1].id;
+        update_subject(State(app_state.clone()), automotive, "Vehicles", Some(electrical)).unwrap();
+        let brake_pad = get_term_by_id(State(app_state.clone()), 1).unwrap().unwrap();
+        assert_eq!(
+            brake_pad.term_language_set.subject.as_deref(),
+            Some("Engineering > Electrical > Vehicles > Brakes")
+        );
+        assert_eq!(search("subject:=Electrical"), vec!["brake pad", "chassis", "fuse"]);
+        assert!(matches!(
+            update_subject(State(app_state.clone()), engineering.id, "Engineering", Some(automotive)),
+            Err(TaxonomyError::Conflict(_))
+        ));
+        assert!(matches!(
+            add_subject(State(app_state.clone()), "Cars > Vans", None),
+            Err(TaxonomyError::InvalidName(_))
+        ));
+        assert!(matches!(
+            delete_subject(State(app_state.clone()), engineering.id),
+            Err(TaxonomyError::Conflict(_))
+        ));
+        let spare = add_subject(State(app_state.clone()), "Spare", Some(engineering.id)).unwrap();
+        delete_subject(State(app_state.clone()), spare.id).unwrap();
+
+        let update = subject("whisk", "Cooking > Baking");
+        update_term(State(app_state.clone()), 4, &update).unwrap();
+        assert_eq!(names(4), vec!["Cooking", "Baking"]);
+
+        // Older tables are linked to the taxonomy on startup.
+        let conn = connect_db(State(app_state.clone())).unwrap();
+        conn.execute_batch(
+            "ALTER TABLE terms DROP COLUMN subject_id;
+             UPDATE terms SET subject = 'Cooking>Pastry' WHERE term_id = 4;",
+        )
+        .unwrap();
+        create_terms_table(State(app_state.clone())).unwrap();
+        assert_eq!(names(4), vec!["Cooking", "Pastry"]);
+        assert_eq!(names(3), vec!["Engineering", "Electrical"]);
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_skos_export() {
+        let app_state = create_test_app_state("test_db_skos_export");
+        create_relations_table(State(app_state.clone())).unwrap();
+        let term = |term: &str, language: &str, status: Option<TermStatus>| TermLanguageSet {
+            status,
+            subject: Some("Automotive > Brakes".to_string()),
+            ..test_term(term, language)
+        };
+        add_term_wrapper(&app_state, &TermLanguageSet {
+            definition: Some("Stops the \"wheel\"".to_string()),
+            ..term("brake", "en", None)
+        })
+        .unwrap();
+        add_term_to_term_set(State(app_state.clone()), 1, &term("stopper", "en", Some(TermStatus::Deprecated)))
+            .unwrap();
+        add_term_to_term_set(State(app_state.clone()), 1, &term("Bremse", "de_DE", Some(TermStatus::Preferred)))
+            .unwrap();
+        add_term_wrapper(&app_state, &term("disc brake", "en", None)).unwrap();
+        add_relation(
+            State(app_state.clone()),
+            &TermSetRelation { source_set_id: 2, relation: RelationType::Broader, target_set_id: 1 },
+        )
+        .unwrap();
+
+        let terms = get_all_terms(State(app_state.clone())).unwrap();
+        let relations = get_relations(State(app_state.clone()), None).unwrap();
+        let subjects = get_subject_tree(State(app_state.clone())).unwrap();
+        let graph = skos_graph(&terms, &relations, &subjects, "http://example.com/");
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph[0].class, "skos:ConceptScheme");
+        let brake = &graph[1];
+        assert_eq!(brake.uri, "http://example.com/concept/1");
+        let literal = |text: &str, language: &str| SkosValue::Literal {
+            text: text.to_string(),
+            language: Some(language.to_string()),
+        };
+        assert!(brake.properties.contains(&("skos:prefLabel", literal("brake", "en"))));
+        assert!(brake.properties.contains(&("skos:hiddenLabel", literal("stopper", "en"))));
+        assert!(brake.properties.contains(&("skos:prefLabel", literal("Bremse", "de-DE"))));
+        assert!(brake.properties.contains(&("skos:inScheme", SkosValue::Resource(graph[0].uri.clone()))));
+        assert!(graph[2]
+            .properties
+            .contains(&("skos:broader", SkosValue::Resource(brake.uri.clone()))));
+
+        let turtle = export_turtle(&graph);
+        assert!(turtle.contains("@prefix skos: <http://www.w3.org/2004/02/skos/core#> ."));
+        assert!(turtle.contains(r#"skos:definition "Stops the \"wheel\""@en"#));
+        let rdf = export_rdf_xml(&graph);
+        assert!(rdf.contains(r#"<skos:definition xml:lang="en">Stops the &quot;wheel&quot;</skos:definition>"#));
+        let root = elementtree::Element::from_reader(rdf.as_bytes()).unwrap();
+        assert_eq!(root.children().count(), 3);
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_term_history() {
+        let app_state = create_test_app_state("test_db_term_history");
+        create_history_table(State(app_state.clone())).unwrap();
+        add_term_wrapper(&app_state, &TermLanguageSet {
+            term: Some("brake".to_string()),
+            language: Some("en".to_string()),
+            creator_id: Some("anna".to_string()),
+            definition: Some("Stops a wheel".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        let edit = |definition: &str, updater: &str| TermLanguageSet {
+            definition: Some(definition.to_string()),
+            updater_id: Some(updater.to_string()),
+            ..Default::default()
+        };
+        update_term(State(app_state.clone()), 1, &edit("Slows a wheel", "ben")).unwrap();
+        // Updates that change nothing are not recorded.
+        update_term(State(app_state.clone()), 1, &edit("Slows a wheel", "ben")).unwrap();
+
+        let history = get_term_history(State(app_state.clone()), 1).unwrap();
+        let actions: Vec<RevisionAction> = history.iter().map(|revision| revision.action).collect();
+        assert_eq!(actions, vec![RevisionAction::Update, RevisionAction::Insert]);
+        assert_eq!(history[0].changed_by.as_deref(), Some("ben"));
+        assert_eq!(history[1].changed_by.as_deref(), Some("anna"));
+        assert!(history[0].changes.contains(&FieldChange {
+            field: "definition".to_string(),
+            old: Some("Stops a wheel".to_string()),
+            new: Some("Slows a wheel".to_string()),
+        }));
+        assert!(!history[0].changes.iter().any(|change| change.field == "term"));
+
+        delete_term(State(app_state.clone()), 1, Some("carl")).unwrap();
+        let history = get_term_history(State(app_state.clone()), 1).unwrap();
+        assert_eq!(history[0].action, RevisionAction::Delete);
+        assert_eq!(history[0].changed_by.as_deref(), Some("carl"));
+        assert!(matches!(
+            revert_term(State(app_state.clone()), history[0].revision_id, None),
+            Err(HistoryError::DeletedInRevision(_))
+        ));
+        assert!(matches!(
+            revert_term(State(app_state.clone()), 99, None),
+            Err(HistoryError::NotFound(99))
+        ));
+
+        // Reverting a deleted term takes it out of the recycle bin.
+        let first = history.last().unwrap().revision_id;
+        assert_eq!(revert_term(State(app_state.clone()), first, Some("dana")).unwrap(), 1);
+        let term = get_term_by_id(State(app_state.clone()), 1).unwrap().unwrap();
+        assert_eq!(term.term_language_set.definition.as_deref(), Some("Stops a wheel"));
+        assert_eq!(term.term_language_set.creator_id.as_deref(), Some("anna"));
+        let history = get_term_history(State(app_state.clone()), 1).unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].changed_by.as_deref(), Some("dana"));
+
+        // Saving without changes only moves the update timestamp.
+        let touch = TermLanguageSet { update_timestamp: Some(1), ..Default::default() };
+        update_term(State(app_state.clone()), 1, &touch).unwrap();
+        assert_eq!(get_term_history(State(app_state.clone()), 1).unwrap().len(), 4);
+        // A change that does not name its editor is not credited to the last one.
+        let remark = TermLanguageSet {
+            remark: Some("check".to_string()),
+            update_timestamp: Some(2),
+            ..Default::default()
+        };
+        update_term(State(app_state.clone()), 1, &remark).unwrap();
+        let history = get_term_history(State(app_state.clone()), 1).unwrap();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[0].changed_by, None);
+        assert_eq!(history[0].changed_by_or_default(), "Unknown");
+        let fields: Vec<&str> = history[0].changes.iter().map(|change| change.field.as_str()).collect();
+        assert_eq!(fields, vec!["remark"]);
+
+        // An editor saving twice is credited both times.
+        update_term(State(app_state.clone()), 1, &edit("Halts a wheel", "ben")).unwrap();
+        update_term(State(app_state.clone()), 1, &edit("Halts the wheel", "ben")).unwrap();
+        let history = get_term_history(State(app_state.clone()), 1).unwrap();
+        assert_eq!(history.len(), 7);
+        assert_eq!(history[0].changed_by.as_deref(), Some("ben"));
+        assert_eq!(history[1].changed_by.as_deref(), Some("ben"));
+        // So is a revert by the last editor.
+        assert_eq!(revert_term(State(app_state.clone()), history[1].revision_id, Some("ben")).unwrap(), 1);
+        let history = get_term_history(State(app_state.clone()), 1).unwrap();
+        assert_eq!(history.len(), 8);
+        assert_eq!(history[0].changed_by.as_deref(), Some("ben"));
+        assert_eq!(history[2].changed_by.as_deref(), Some("ben"));
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_recycle_bin() {
+        let app_state = create_test_app_state("test_db_recycle_bin");
+        create_relations_table(State(app_state.clone())).unwrap();
+        create_unique_values_tables(State(app_state.clone())).unwrap();
+        create_facet_index(State(app_state.clone())).unwrap();
+        create_history_table(State(app_state.clone())).unwrap();
+        let term = |term: &str| TermLanguageSet {
+            subject: Some("brakes".to_string()),
+            ..test_term(term, "en")
+        };
+        add_term_wrapper(&app_state, &term("brake")).unwrap();
+        add_term_to_term_set(State(app_state.clone()), 1, &term("stopper")).unwrap();
+
+        delete_term(State(app_state.clone()), 2, Some("anna")).unwrap();
+        assert!(matches!(
+            delete_term(State(app_state.clone()), 2, None),
+            Err(rusqlite::Error::ExecuteReturnedResults)
+        ));
+        assert_eq!(count_terms(State(app_state.clone())).unwrap(), 1);
+        assert_eq!(get_all_terms(State(app_state.clone())).unwrap().len(), 1);
+        assert_eq!(search_terms_by_term_set_id(State(app_state.clone()), 1).unwrap().len(), 1);
+        assert!(get_term_by_id(State(app_state.clone()), 2).unwrap().is_none());
+        let facets = get_facets(State(app_state.clone()), DEFAULT_FACET_LIMIT).unwrap();
+        assert_eq!(facets.subject, vec![FacetCount { value: "brakes".to_string(), count: 1 }]);
+
+        let deleted = get_deleted_terms(State(app_state.clone())).unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].term.term_language_set.term.as_deref(), Some("stopper"));
+        assert_eq!(deleted[0].deleted_by.as_deref(), Some("anna"));
+
+        assert!(restore_term(State(app_state.clone()), 2, Some("ben")).unwrap());
+        assert!(!restore_term(State(app_state.clone()), 2, None).unwrap());
+        let restored = get_term_by_id(State(app_state.clone()), 2).unwrap().unwrap();
+        assert_eq!(restored.term_language_set.updater_id.as_deref(), Some("ben"));
+        let facets = get_facets(State(app_state.clone()), DEFAULT_FACET_LIMIT).unwrap();
+        assert_eq!(facets.subject[0].count, 2);
+
+        // Deleting a term set moves all of its terms to the recycle bin.
+        delete_termset(State(app_state.clone()), 1, None).unwrap();
+        assert_eq!(count_terms(State(app_state.clone())).unwrap(), 0);
+        assert_eq!(purge_expired(State(app_state.clone()), DEFAULT_RETENTION_DAYS).unwrap(), 0);
+        assert_eq!(purge_expired(State(app_state.clone()), u64::MAX).unwrap(), 0);
+        assert!(purge_term(State(app_state.clone()), 1).unwrap());
+        assert!(!purge_term(State(app_state.clone()), 1).unwrap());
+        assert_eq!(purge_expired(State(app_state.clone()), 0).unwrap(), 1);
+        assert!(get_deleted_terms(State(app_state.clone())).unwrap().is_empty());
+
+        let actions: Vec<RevisionAction> = get_term_history(State(app_state.clone()), 2)
+            .unwrap()
+            .iter()
+            .map(|revision| revision.action)
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                RevisionAction::Purge,
+                RevisionAction::Delete,
+                RevisionAction::Restore,
+                RevisionAction::Delete,
+                RevisionAction::Insert
+            ]
+        );
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_patch_term() {
+        let app_state = create_test_app_state("test_db_patch_term");
+        create_picklist_tables(State(app_state.clone())).unwrap();
+        let set = TermLanguageSet {
+            term: Some("brake".to_string()),
+            language: Some("en".to_string()),
+            source: Some("Manual".to_string()),
+            remark: Some("check spelling".to_string()),
+            url: Some("https://example.com".to_string()),
+            status: Some(TermStatus::Admitted),
+            ..Default::default()
+        };
+        add_term_wrapper(&app_state, &set).unwrap();
+
+        let patch: TermPatch = serde_json::from_str(
+            r#"{"remark": null, "url": "", "status": null, "definition": "Slows a wheel",
+                "subject": "vehicles > brakes"}"#,
+        )
+        .unwrap();
+        assert_eq!(patch.term, FieldUpdate::Unchanged);
+        assert_eq!(patch.remark, FieldUpdate::Clear);
+        assert_eq!(patch.url, FieldUpdate::Set(String::new()));
+        assert_eq!(patch.status, FieldUpdate::Clear);
+        patch_term(State(app_state.clone()), 1, &patch, None).unwrap();
+
+        let term = get_term_by_id(State(app_state.clone()), 1).unwrap().unwrap();
+        let patched = &term.term_language_set;
+        assert_eq!(patched.term.as_deref(), Some("brake"));
+        assert_eq!(patched.source.as_deref(), Some("Manual"));
+        assert_eq!(patched.remark, None);
+        assert_eq!(patched.url, None);
+        assert_eq!(patched.status, None);
+        assert_eq!(patched.definition.as_deref(), Some("Slows a wheel"));
+        assert_eq!(patched.subject.as_deref(), Some("vehicles > brakes"));
+
+        // A closed picklist checks set values; an empty value clears the field.
+        add_picklist_value(State(app_state.clone()), "source", "Manual").unwrap();
+        let mut patch: TermPatch = serde_json::from_str(r#"{"source": "Brochure"}"#).unwrap();
+        assert!(matches!(
+            apply_picklists_to_patch(State(app_state.clone()), &mut patch),
+            Err(PicklistError::NotAllowed { .. })
+        ));
+        let mut patch: TermPatch =
+            serde_json::from_str(r#"{"source": " ", "subject": null}"#).unwrap();
+        apply_picklists_to_patch(State(app_state.clone()), &mut patch).unwrap();
+        assert_eq!(patch.source, FieldUpdate::Clear);
+        patch_term(State(app_state.clone()), 1, &patch, None).unwrap();
+        let term = get_term_by_id(State(app_state.clone()), 1).unwrap().unwrap();
+        assert_eq!(term.term_language_set.source, None);
+        assert_eq!(term.term_language_set.subject, None);
+        assert_eq!(term.term_language_set.definition.as_deref(), Some("Slows a wheel"));
+
+        // An open picklist only gains a value when the update goes through.
+        set_allow_new(State(app_state.clone()), "source", true).unwrap();
+        let mut patch: TermPatch = serde_json::from_str(r#"{"source": "Brochure"}"#).unwrap();
+        let new_values = apply_picklists_to_patch(State(app_state.clone()), &mut patch).unwrap();
+        assert_eq!(new_values, vec![("source".to_string(), "Brochure".to_string())]);
+        assert!(matches!(
+            patch_term_with_picklists(State(app_state.clone()), 1, &patch, Some(1), &new_values),
+            Err(UpdateError::Conflict { .. })
+        ));
+        assert_eq!(get_picklist(State(app_state.clone()), "source").unwrap().values, vec!["Manual"]);
+        patch_term_with_picklists(State(app_state.clone()), 1, &patch, None, &new_values).unwrap();
+        assert_eq!(
+            get_picklist(State(app_state.clone()), "source").unwrap().values,
+            vec!["Brochure", "Manual"]
+        );
+
+        // The term and its language cannot be cleared.
+        for json in [r#"{"term": null}"#, r#"{"term": " "}"#, r#"{"language": ""}"#] {
+            let patch: TermPatch = serde_json::from_str(json).unwrap();
+            assert!(matches!(
+                patch_term(State(app_state.clone()), 1, &patch, None),
+                Err(UpdateError::Required(_))
+            ));
+        }
+        let empty_term = TermLanguageSet { term: Some(String::new()), ..Default::default() };
+        assert!(matches!(
+            update_term(State(app_state.clone()), 1, &empty_term),
+            Err(UpdateError::Required("term"))
+        ));
+        assert!(matches!(
+            update_term(State(app_state.clone()), 7, &test_term("brake", "en")),
+            Err(UpdateError::NotFound(7))
+        ));
+        let term = get_term_by_id(State(app_state.clone()), 1).unwrap().unwrap();
+        assert_eq!(term.term_language_set.term.as_deref(), Some("brake"));
+        assert_eq!(term.term_language_set.language.as_deref(), Some("en"));
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_update_conflict() {
+        let app_state = create_test_app_state("test_db_update_conflict");
+        let set = TermLanguageSet {
+            term: Some("brake".to_string()),
+            language: Some("en".to_string()),
+            ..Default::default()
+        };
+        add_term_wrapper(&app_state, &set).unwrap();
+        assert_eq!(get_term_version(State(app_state.clone()), 1).unwrap(), Some(1));
+
+        let patch: TermPatch = serde_json::from_str(r#"{"remark": "first"}"#).unwrap();
+        patch_term(State(app_state.clone()), 1, &patch, Some(1)).unwrap();
+        assert_eq!(get_term_version(State(app_state.clone()), 1).unwrap(), Some(2));
+
+        // A second editor still working from version 1 is turned away.
+        let patch: TermPatch = serde_json::from_str(r#"{"remark": "second"}"#).unwrap();
+        match patch_term(State(app_state.clone()), 1, &patch, Some(1)) {
+            Err(UpdateError::Conflict { version, term }) => {
+                assert_eq!(version, 2);
+                assert_eq!(term.term_language_set.remark.as_deref(), Some("first"));
+            }
+            other => panic!("expected a conflict, got {other:?}"),
+        }
+        assert!(matches!(
+            patch_term(State(app_state.clone()), 7, &patch, Some(1)),
+            Err(UpdateError::NotFound(7))
+        ));
+        // An empty patch changes nothing but is checked all the same.
+        let empty = TermPatch::default();
+        patch_term(State(app_state.clone()), 1, &empty, Some(2)).unwrap();
+        assert!(matches!(
+            patch_term(State(app_state.clone()), 1, &empty, Some(1)),
+            Err(UpdateError::Conflict { version: 2, .. })
+        ));
+        assert!(matches!(
+            patch_term(State(app_state.clone()), 7, &empty, None),
+            Err(UpdateError::NotFound(7))
+        ));
+
+        // Every change bumps the version, not only patches.
+        delete_term(State(app_state.clone()), 1, None).unwrap();
+        assert_eq!(get_term_version(State(app_state.clone()), 1).unwrap(), None);
+        restore_term(State(app_state.clone()), 1, None).unwrap();
+        assert_eq!(get_term_version(State(app_state.clone()), 1).unwrap(), Some(4));
+        patch_term(State(app_state.clone()), 1, &patch, None).unwrap();
+        assert_eq!(get_term_version(State(app_state.clone()), 1).unwrap(), Some(5));
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_term_set_operations() {
+        let app_state = create_test_app_state("test_db_term_set_operations");
+        create_relations_table(State(app_state.clone())).unwrap();
+        add_term_wrapper(&app_state, &test_term("brake", "en")).unwrap();
+        add_term_to_term_set(State(app_state.clone()), 1, &test_term("rem", "nl")).unwrap();
+        add_term_wrapper(&app_state, &test_term("stopper", "en")).unwrap();
+        add_term_to_term_set(State(app_state.clone()), 2, &test_term("Bremse", "de")).unwrap();
+        add_term_wrapper(&app_state, &test_term("wheel", "en")).unwrap();
+        let languages = |term_set_id: i32| -> Vec<String> {
+            let mut languages: Vec<String> =
+                search_terms_by_term_set_id(State(app_state.clone()), term_set_id)
+                    .unwrap()
+                    .into_iter()
+                    .filter_map(|entry| entry.term_language_set.language)
+                    .collect();
+            languages.sort();
+            languages
+        };
+
+        move_term(State(app_state.clone()), 4, 3, Some("anna")).unwrap();
+        assert_eq!(languages(3), vec!["de", "en"]);
+        assert!(matches!(
+            move_term(State(app_state.clone()), 4, 3, None),
+            Err(TermSetError::SameTermSet(3))
+        ));
+        assert!(matches!(
+            move_term(State(app_state.clone()), 4, 9, None),
+            Err(TermSetError::UnknownTermSet(9))
+        ));
+        move_term(State(app_state.clone()), 4, 2, None).unwrap();
+
+        add_relation(
+            State(app_state.clone()),
+            &TermSetRelation { source_set_id: 2, relation: RelationType::Broader, target_set_id: 3 },
+        )
+        .unwrap();
+        match merge_term_sets(State(app_state.clone()), 1, 2, DuplicateLanguages::Reject, None) {
+            Err(TermSetError::DuplicateLanguages(languages)) => assert_eq!(languages, vec!["en"]),
+            other => panic!("expected duplicate languages, got {other:?}"),
+        }
+        let result =
+            merge_term_sets(State(app_state.clone()), 1, 2, DuplicateLanguages::KeepTarget, None)
+                .unwrap();
+        assert_eq!(result, MergeResult { term_set_id: 1, moved: 1, discarded: 1 });
+        assert_eq!(languages(1), vec!["de", "en", "nl"]);
+        assert!(languages(2).is_empty());
+        assert_eq!(get_deleted_terms(State(app_state.clone())).unwrap()[0].term.term_set_id, 1);
+        let relations = get_relations(State(app_state.clone()), None).unwrap();
+        assert_eq!(relations.len(), 2);
+        assert!(relations.iter().all(|relation| relation.source_set_id != 2
+            && relation.target_set_id != 2));
+
+        assert!(matches!(
+            split_term_set(State(app_state.clone()), &[1, 5], None),
+            Err(TermSetError::MixedTermSets)
+        ));
+        assert!(matches!(
+            split_term_set(State(app_state.clone()), &[1, 2, 4], None),
+            Err(TermSetError::WholeTermSet(1))
+        ));
+        assert_eq!(split_term_set(State(app_state.clone()), &[2, 4], Some("anna")).unwrap(), 4);
+        assert_eq!(languages(4), vec!["de", "nl"]);
+        assert_eq!(languages(1), vec!["en"]);
+
+        delete_termset(State(app_state.clone()), 4, None).unwrap();
+        assert!(languages(4).is_empty());
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_bulk_edit() {
+        let app_state = create_test_app_state("test_db_bulk_edit");
+        create_history_table(State(app_state.clone())).unwrap();
+        create_picklist_tables(State(app_state.clone())).unwrap();
+        let term = |term: &str, language: &str, remark: &str| TermLanguageSet {
+            remark: Some(remark.to_string()),
+            attributes: Some("legacy".to_string()),
+            ..test_term(term, language)
+        };
+        add_term_wrapper(&app_state, &term("brake pad", "en", "see ISO 611")).unwrap();
+        add_term_wrapper(&app_state, &term("brake disc", "en", "see ISO 7638")).unwrap();
+        add_term_wrapper(&app_state, &term("wheel", "en", "")).unwrap();
+        let selection = BulkSelection {
+            q: "brake".to_string(),
+            ..Default::default()
+        };
+        let remark = |term_id: i32| {
+            get_term_by_id(State(app_state.clone()), term_id)
+                .unwrap()
+                .unwrap()
+                .term_language_set
+                .remark
+        };
+
+        let replace = BulkAction::Replace(FindReplace {
+            fields: vec!["remark".to_string()],
+            find: r"ISO (\d+)".to_string(),
+            replace: "ISO-$1".to_string(),
+            regex: true,
+            ignore_case: false,
+        });
+        let preview = preview_bulk_edit(State(app_state.clone()), &selection, &replace).unwrap();
+        assert_eq!(preview.len(), 2);
+        assert_eq!(preview[1].changes[0].new.as_deref(), Some("see ISO-7638"));
+        assert_eq!(remark(2).as_deref(), Some("see ISO 7638"));
+        let changes =
+            bulk_edit(State(app_state.clone()), &selection, &replace, Some("anna")).unwrap();
+        assert_eq!(changes, preview);
+        assert_eq!(remark(1).as_deref(), Some("see ISO-611"));
+        let history = get_term_history(State(app_state.clone()), 2).unwrap();
+        assert_eq!(history[0].changed_by.as_deref(), Some("anna"));
+
+        let by_id = BulkSelection {
+            term_ids: vec![2, 3],
+            ..Default::default()
+        };
+        let set = BulkAction::Set {
+            field: "status".to_string(),
+            value: Some("Deprecated".to_string()),
+        };
+        bulk_edit(State(app_state.clone()), &by_id, &set, Some("anna")).unwrap();
+        let status = get_term_by_id(State(app_state.clone()), 3).unwrap().unwrap();
+        assert_eq!(status.term_language_set.status, Some(TermStatus::Deprecated));
+        // A second edit by the same editor is still credited to them.
+        let history = get_term_history(State(app_state.clone()), 2).unwrap();
+        assert_eq!(history[0].changed_by.as_deref(), Some("anna"));
+        assert_eq!(history[1].changed_by.as_deref(), Some("anna"));
+
+        let tags = BulkAction::AddTags {
+            tags: vec!["Legacy".to_string(), "reviewed".to_string()],
+        };
+        let changes = bulk_edit(State(app_state.clone()), &by_id, &tags, None).unwrap();
+        assert_eq!(changes[0].changes[0].new.as_deref(), Some("legacy; reviewed"));
+        // Tags already present are not added twice.
+        assert!(preview_bulk_edit(State(app_state.clone()), &by_id, &tags)
+            .unwrap()
+            .is_empty());
+
+        // Values go through picklists, whichever action writes them.
+        add_picklist_value(State(app_state.clone()), "source", "Automotive").unwrap();
+        let set = BulkAction::Set {
+            field: "source".to_string(),
+            value: Some(" automotive".to_string()),
+        };
+        let changes = bulk_edit(State(app_state.clone()), &by_id, &set, None).unwrap();
+        assert_eq!(changes[0].changes[0].new.as_deref(), Some("Automotive"));
+        let rename = BulkAction::Replace(FindReplace {
+            fields: vec!["source".to_string()],
+            find: "Automotive".to_string(),
+            replace: "Cars".to_string(),
+            regex: false,
+            ignore_case: false,
+        });
+        assert!(matches!(
+            preview_bulk_edit(State(app_state.clone()), &by_id, &rename),
+            Err(BulkError::Picklist(PicklistError::NotAllowed { .. }))
+        ));
+        assert!(matches!(
+            bulk_edit(State(app_state.clone()), &by_id, &rename, None),
+            Err(BulkError::Picklist(PicklistError::NotAllowed { .. }))
+        ));
+        let source = get_term_by_id(State(app_state.clone()), 2).unwrap().unwrap();
+        assert_eq!(source.term_language_set.source.as_deref(), Some("Automotive"));
+        set_allow_new(State(app_state.clone()), "source", true).unwrap();
+        preview_bulk_edit(State(app_state.clone()), &by_id, &rename).unwrap();
+        let picklist = get_picklist(State(app_state.clone()), "source").unwrap();
+        assert_eq!(picklist.values, vec!["Automotive"]);
+        assert_eq!(bulk_edit(State(app_state.clone()), &by_id, &rename, None).unwrap().len(), 2);
+        let picklist = get_picklist(State(app_state.clone()), "source").unwrap();
+        assert_eq!(picklist.values, vec!["Automotive", "Cars"]);
+
+        // Replacing a whole term with nothing is rejected.
+        let erase = BulkAction::Replace(FindReplace {
+            fields: vec!["term".to_string()],
+            find: "wheel".to_string(),
+            replace: String::new(),
+            regex: false,
+            ignore_case: false,
+        });
+        assert!(matches!(
+            bulk_edit(State(app_state.clone()), &by_id, &erase, None),
+            Err(BulkError::EmptyTerm(3))
+        ));
+        let wheel = get_term_by_id(State(app_state.clone()), 3).unwrap().unwrap();
+        assert_eq!(wheel.term_language_set.term.as_deref(), Some("wheel"));
+
+        assert!(matches!(
+            preview_bulk_edit(
+                State(app_state.clone()),
+                &by_id,
+                &BulkAction::Set { field: "term".to_string(), value: None },
+            ),
+            Err(BulkError::UnknownField(_))
+        ));
+        assert!(matches!(
+            preview_bulk_edit(
+                State(app_state.clone()),
+                &BulkSelection { term_ids: vec![9], ..Default::default() },
+                &BulkAction::Delete,
+            ),
+            Err(BulkError::UnknownTerm(9))
+        ));
+
+        bulk_edit(State(app_state.clone()), &selection, &BulkAction::Delete, Some("ben")).unwrap();
+        assert_eq!(count_terms(State(app_state.clone())).unwrap(), 1);
+        assert!(matches!(
+            preview_bulk_edit(State(app_state.clone()), &selection, &BulkAction::Delete),
+            Err(BulkError::NoTerms)
+        ));
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_find_duplicates() {
+        let app_state = create_test_app_state("test_db_find_duplicates");
+        create_relations_table(State(app_state.clone())).unwrap();
+        add_term_wrapper(&app_state, &test_term("brake pad", "en")).unwrap();
+        add_term_to_term_set(State(app_state.clone()), 1, &test_term("Bremsbelag", "de")).unwrap();
+        add_term_wrapper(&app_state, &test_term("brake pad", "en")).unwrap();
+        add_term_wrapper(&app_state, &test_term("Brake  Disc", "en")).unwrap();
+        add_term_wrapper(&app_state, &test_term("brake disc", "EN")).unwrap();
+        add_term_wrapper(&app_state, &test_term("accelerator", "en")).unwrap();
+        add_term_wrapper(&app_state, &test_term("accelerater", "en")).unwrap();
+        add_term_wrapper(&app_state, &test_term("brake pad", "de")).unwrap();
+        // Synonyms in one term set are not duplicates.
+        add_term_wrapper(&app_state, &test_term("clutch", "en")).unwrap();
+        add_term_to_term_set(State(app_state.clone()), 8, &test_term("Clutch", "en")).unwrap();
+        let summary = |options: &DuplicateOptions| -> Vec<(DuplicateKind, Vec<i32>)> {
+            find_duplicates(State(app_state.clone()), options)
+                .unwrap()
+                .into_iter()
+                .map(|group| {
+                    let term_set_ids =
+                        group.term_sets.iter().map(|set| set.term_set_id).collect();
+                    (group.kind, term_set_ids)
+                })
+                .collect()
+        };
+
+        let fuzzy = DuplicateOptions {
+            fuzzy: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            summary(&fuzzy),
+            vec![
+                (DuplicateKind::Exact, vec![1, 2]),
+                (DuplicateKind::Variant, vec![3, 4]),
+                (DuplicateKind::Fuzzy, vec![5, 6]),
+            ]
+        );
+        // Near-duplicates are only looked for on request.
+        let exact_only = DuplicateOptions::default();
+        assert_eq!(summary(&exact_only).len(), 2);
+        let german = DuplicateOptions {
+            language: "de".to_string(),
+            ..fuzzy.clone()
+        };
+        assert!(summary(&german).is_empty());
+
+        let groups = find_duplicates(State(app_state.clone()), &exact_only).unwrap();
+        assert_eq!(groups[0].term_ids, vec![1, 3]);
+        assert_eq!(groups[0].term_sets[0].terms.len(), 2);
+
+        // Only the duplicate is discarded, not the other German term.
+        add_term_to_term_set(State(app_state.clone()), 2, &test_term("Bremsklotz", "de")).unwrap();
+        assert!(matches!(
+            merge_term_sets_into(
+                State(app_state.clone()),
+                1,
+                &[2],
+                DuplicateLanguages::KeepBoth,
+                &[4],
+                None,
+            ),
+            Err(TermSetError::NotMerged(4))
+        ));
+        let result = merge_term_sets_into(
+            State(app_state.clone()),
+            1,
+            &[2],
+            DuplicateLanguages::KeepBoth,
+            &[3],
+            Some("anna"),
+        )
+        .unwrap();
+        assert_eq!(result, MergeResult { term_set_id: 1, moved: 1, discarded: 1 });
+        let mut merged: Vec<String> = search_terms_by_term_set_id(State(app_state.clone()), 1)
+            .unwrap()
+            .into_iter()
+            .filter_map(|entry| entry.term_language_set.term)
+            .collect();
+        merged.sort();
+        assert_eq!(merged, vec!["Bremsbelag", "Bremsklotz", "brake pad"]);
+        // Term set 3 merges, then 4 clashes with it, so neither merge stays.
+        assert!(matches!(
+            merge_term_sets_into(
+                State(app_state.clone()),
+                7,
+                &[3, 4],
+                DuplicateLanguages::Reject,
+                &[],
+                None,
+            ),
+            Err(TermSetError::DuplicateLanguages(_))
+        ));
+        assert_eq!(
+            summary(&fuzzy),
+            vec![(DuplicateKind::Variant, vec![3, 4]), (DuplicateKind::Fuzzy, vec![5, 6])]
+        );
+        // A merged set may lose all its live terms to discards.
+        let result = merge_term_sets_into(
+            State(app_state.clone()),
+            3,
+            &[4],
+            DuplicateLanguages::Reject,
+            &[5],
+            None,
+        )
+        .unwrap();
+        assert_eq!(result, MergeResult { term_set_id: 3, moved: 0, discarded: 1 });
+        assert_eq!(
+            summary(&fuzzy),
+            vec![(DuplicateKind::Fuzzy, vec![5, 6])]
+        );
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_facets_track_changes() {
+        let app_state = create_test_app_state("test_db_facets_track_changes");
+        let conn = connect_db(State(app_state.clone())).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE unique_subjects (id INTEGER PRIMARY KEY AUTOINCREMENT, subject TEXT UNIQUE);",
+        )
+        .unwrap();
+        let brakes = |term: &str, language: &str| TermLanguageSet {
+            subject: Some("brakes".to_string()),
+            ..test_term(term, language)
+        };
+        add_term_wrapper(&app_state, &brakes("brake pad", "en")).unwrap();
+
+        create_unique_values_tables(State(app_state.clone())).unwrap();
+        create_facet_index(State(app_state.clone())).unwrap();
+        let count = |facet: &[FacetCount], value: &str| {
+            facet.iter().find(|f| f.value == value).map(|f| f.count)
+        };
+        let facets = get_facets(State(app_state.clone()), DEFAULT_FACET_LIMIT).unwrap();
+        assert_eq!(count(&facets.subject, "brakes"), Some(1));
+
+        add_term_wrapper(&app_state, &brakes("Bremsbelag", "de")).unwrap();
+        add_term_wrapper(&app_state, &brakes("brake disc", "en")).unwrap();
+        let facets = get_facets(State(app_state.clone()), DEFAULT_FACET_LIMIT).unwrap();
+        assert_eq!(count(&facets.subject, "brakes"), Some(3));
+        assert_eq!(facets.language[0], FacetCount { value: "en".to_string(), count: 2 });
+
+        let disc_id = get_term_set_id(State(app_state.clone()), "brake disc", "en")
+            .unwrap()
+            .unwrap();
+        let update = TermLanguageSet {
+            subject: Some("wheels".to_string()),
+            ..Default::default()
+        };
+        update_term(State(app_state.clone()), disc_id, &update).unwrap();
+        let facets = get_facets(State(app_state.clone()), DEFAULT_FACET_LIMIT).unwrap();
+        assert_eq!(count(&facets.subject, "brakes"), Some(2));
+        assert_eq!(count(&facets.subject, "wheels"), Some(1));
+
+        delete_term(State(app_state.clone()), disc_id, None).unwrap();
+        let facets = get_facets(State(app_state.clone()), DEFAULT_FACET_LIMIT).unwrap();
+        assert_eq!(count(&facets.subject, "wheels"), None);
+
+        let english = search_terms(State(app_state.clone()), "", "en").unwrap();
+        let facets = facets_from_terms(english.iter(), DEFAULT_FACET_LIMIT);
+        assert_eq!(facets.language, vec![FacetCount { value: "en".to_string(), count: 1 }]);
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_sort_and_paginate_terms() {
+        let entry = |term_id: i32, term: Option<&str>| TermsList {
+            term_id,
+            term_set_id: term_id,
+            term_language_set: TermLanguageSet {
+                term: term.map(str::to_string),
+                ..Default::default()
+            },
+        };
+        let mut terms = vec![
+            entry(4, Some("b")),
+            entry(1, None),
+            entry(3, Some("a")),
+            entry(2, Some("b")),
+        ];
+        let ids = |terms: &[TermsList]| terms.iter().map(|t| t.term_id).collect::<Vec<_>>();
+
+        let root = collator_for_language("");
+        sort_by_field(&mut terms, |t| t, SortField::Term, SortOrder::Asc, &root);
+        assert_eq!(ids(&terms), vec![3, 2, 4, 1]);
+        sort_by_field(&mut terms, |t| t, SortField::Term, SortOrder::Desc, &root);
+        assert_eq!(ids(&terms), vec![2, 4, 3, 1]);
+
+        // Sorting for a page only sorts as far as the page goes.
+        let mut page = terms.clone();
+        sort_page_by_field(&mut page, |t| t, SortField::Term, SortOrder::Asc, &root, Some(2));
+        assert_eq!(ids(&page), vec![3, 2]);
+        let mut page = terms.clone();
+        sort_page_by_field(&mut page, |t| t, SortField::Term, SortOrder::Asc, &root, Some(9));
+        assert_eq!(ids(&page), vec![3, 2, 4, 1]);
+
+        assert_eq!(ids(&paginate(terms.clone(), 1, Some(2))), vec![4, 3]);
+        assert_eq!(ids(&paginate(terms.clone(), 3, Some(2))), vec![1]);
+        assert!(paginate(terms, 10, Some(2)).is_empty());
+        assert_eq!(clamp_limit(Some(0)), Some(1));
+        assert_eq!(clamp_limit(Some(1_000_000)), Some(MAX_PAGE_SIZE));
+
+        let links = page_links("/search", Some("term=br%C3%BC&offset=2&limit=2"), 2, 2, 7);
+        assert!(links.starts_with("</search?term=br%C3%BC&offset=0&limit=2>; rel=\"first\""));
+        assert!(links.contains("</search?term=br%C3%BC&offset=0&limit=2>; rel=\"prev\""));
+        assert!(links.contains("</search?term=br%C3%BC&offset=4&limit=2>; rel=\"next\""));
+        assert!(links.ends_with("</search?term=br%C3%BC&offset=6&limit=2>; rel=\"last\""));
+        assert!(!page_links("/search", None, 6, 2, 7).contains("rel=\"next\""));
+    }
+
+    #[test]
+    fn test_collation_per_language() {
+        assert_eq!(primary_language("nld-nl").as_deref(), Some("nl"));
+        assert_eq!(primary_language("sv_SE").as_deref(), Some("sv"));
+        assert_eq!(primary_language(""), None);
+        assert_eq!(locale_for_language("deu-de").to_string(), "de-DE");
+        assert_eq!(locale_for_language("xx-yy-zz").to_string(), "xx-YY");
+        assert_eq!(locale_for_language("").to_string(), "und");
+        assert!(same_language("eng-gb", "en-US"));
+        assert!(!same_language("eng-gb", "nld-nl"));
+
+        let entries: Vec<TermsList> = ["Öl", "Zebra", "apa", "Apa", "Äpple"]
+            .iter()
+            .enumerate()
+            .map(|(index, term)| TermsList {
+                term_id: index as i32,
+                term_set_id: index as i32,
+                term_language_set: TermLanguageSet {
+                    term: Some(term.to_string()),
+                    ..Default::default()
+                },
+            })
+            .collect();
+        let sorted = |language: &str| {
+            let mut terms = entries.clone();
+            let collator = collator_for_language(language);
+            sort_by_field(&mut terms, |t| t, SortField::Term, SortOrder::Asc, &collator);
+            terms
+                .into_iter()
+                .map(|t| t.term_language_set.term.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted("swe-se"), vec!["apa", "Apa", "Zebra", "Äpple", "Öl"]);
+        assert_eq!(sorted("deu-de"), vec!["apa", "Apa", "Äpple", "Öl", "Zebra"]);
+        assert_eq!(sorted("unknown"), sorted(""));
+
+        // Without a language each language is sorted with its own collation.
+        let mut mixed: Vec<TermsList> = [("Zebra", "sv"), ("Öl", "de"), ("Äpple", "sv"), ("Zebra", "de")]
+            .iter()
+            .enumerate()
+            .map(|(index, (term, language))| TermsList {
+                term_id: index as i32,
+                term_set_id: index as i32,
+                term_language_set: test_term(term, language),
+            })
+            .collect();
+        sort_page_for_language(&mut mixed, |t| t, SortField::Term, SortOrder::Asc, "", None);
+        let ids: Vec<i32> = mixed.iter().map(|t| t.term_id).collect();
+        assert_eq!(ids, vec![1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn test_db_lookup_translations() {
+        let app_state = create_test_app_state("test_db_lookup_translations");
+        let term = |term: &str, language: &str, remark: Option<&str>| TermLanguageSet {
+            term_type: Some("full form".to_string()),
+            remark: remark.map(str::to_string),
+            ..test_term(term, language)
+        };
+        add_term_wrapper(&app_state, &term("brake pad", "eng-gb", None)).unwrap();
+        let pad_set = get_term_set_id(State(app_state.clone()), "brake pad", "eng-gb")
+            .unwrap()
+            .unwrap();
+        for dutch in [term("remvoering", "nld-nl", Some("older")), term("remblok", "nld-nl", None)] {
+            add_term_to_term_set(State(app_state.clone()), pad_set, &dutch).unwrap();
+        }
+        add_term_to_term_set(State(app_state.clone()), pad_set, &term("Bremsbelag", "deu-de", None))
+            .unwrap();
+        add_term_wrapper(&app_state, &term("brake pad wear", "eng-us", None)).unwrap();
+
+        let options = LookupOptions {
+            search: SearchOptions::default(),
+            mode: SearchMode::Substring,
+            min_score: DEFAULT_MIN_SCORE,
+            limit: DEFAULT_LOOKUP_LIMIT,
+        };
+        let lookup = |term: &str, from: &str, to: &str, options: &LookupOptions| {
+            lookup_terms(State(app_state.clone()), term, from, to, options).unwrap()
+        };
+
+        let results = lookup("Brake Pad", "eng-gb", "nld-nl", &options);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].term_set_id, pad_set);
+        assert_eq!(results[0].source[0].match_kind, MatchKind::Exact);
+        let targets: Vec<_> = results[0]
+            .targets
+            .iter()
+            .map(|t| t.term_language_set.term.as_deref().unwrap())
+            .collect();
+        assert_eq!(targets, vec!["remblok", "remvoering"]);
+        assert_eq!(results[0].targets[1].term_language_set.remark.as_deref(), Some("older"));
+
+        let results = lookup("brake pad", "en", "nl", &options);
+        assert_eq!(results.len(), 2);
+        assert!(results[1].targets.is_empty());
+
+        let fuzzy = LookupOptions { mode: SearchMode::Fuzzy, limit: 1, ..options };
+        let results = lookup("brak pad", "eng", "de", &fuzzy);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].targets[0].term_language_set.term.as_deref(), Some("Bremsbelag"));
+
+        assert!(language_matches("eng-gb", "en"));
+        assert!(!language_matches("eng-us", "eng-gb"));
+        assert!(language_matches("", ""));
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_batch_lookup() {
+        let app_state = create_test_app_state("test_db_batch_lookup");
+        add_term_wrapper(&app_state, &test_term("brake pad", "eng-gb")).unwrap();
+        let pad_set = get_term_set_id(State(app_state.clone()), "brake pad", "eng-gb")
+            .unwrap()
+            .unwrap();
+        add_term_to_term_set(State(app_state.clone()), pad_set, &test_term("remblok", "nld-nl")).unwrap();
+        add_term_to_term_set(State(app_state.clone()), pad_set, &test_term("Bremsbelag", "deu-de"))
+            .unwrap();
+        add_term_wrapper(&app_state, &test_term("Gearbox", "eng-gb")).unwrap();
+
+        let entry = |term: &str, to: Option<&str>| BatchLookupEntry {
+            term: term.to_string(),
+            from: None,
+            to: to.map(str::to_string),
+        };
+        let entries = vec![
+            entry("brake pad", None),
+            entry("gearbox", None),
+            entry("brak pad", Some("deu")),
+            entry("clutch", None),
+        ];
+        let options = BatchLookupOptions {
+            search: SearchOptions::default(),
+            fuzzy: true,
+            min_score: DEFAULT_MIN_SCORE,
+            max_results: DEFAULT_BATCH_MAX_RESULTS,
+        };
+        let results =
+            batch_lookup(State(app_state.clone()), &entries, "eng", "nld-nl", &options).unwrap();
+
+        let match_types: Vec<_> = results.iter().map(|item| item.match_type).collect();
+        assert_eq!(
+            match_types,
+            vec![
+                BatchMatchType::Exact,
+                BatchMatchType::CaseInsensitive,
+                BatchMatchType::Fuzzy,
+                BatchMatchType::None,
+            ]
+        );
+        assert_eq!(results[0].matches[0].term_set_id, pad_set);
+        assert_eq!(results[0].matches[0].targets[0].term_language_set.term.as_deref(), Some("remblok"));
+        assert!(results[1].matches[0].targets.is_empty());
+        assert_eq!(results[2].to, "deu");
+        assert_eq!(results[2].matches[0].targets[0].term_language_set.term.as_deref(), Some("Bremsbelag"));
+        assert!(results[3].matches.is_empty());
+
+        let exact_only = BatchLookupOptions { fuzzy: false, ..options };
+        let results =
+            batch_lookup(State(app_state.clone()), &entries[2..3], "eng", "nld-nl", &exact_only).unwrap();
+        assert_eq!(results[0].match_type, BatchMatchType::None);
+
+        // A match that needs more than ignoring case says so.
+        let case_sensitive = BatchLookupOptions {
+            search: SearchOptions { ignore_case: false, ..SearchOptions::default() },
+            ..options
+        };
+        for (options, term) in [(&options, "ＧＥＡＲＢＯＸ"), (&case_sensitive, "Ｇｅａｒｂｏｘ")] {
+            let results =
+                batch_lookup(State(app_state.clone()), &[entry(term, None)], "eng", "nld-nl", options).unwrap();
+            assert_eq!(results[0].match_type, BatchMatchType::Variant);
+        }
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_recognize_terms() {
+        let app_state = create_test_app_state("test_db_recognize_terms");
+        add_term_wrapper(&app_state, &test_term("brake pad", "eng-gb")).unwrap();
+        let pad_set = get_term_set_id(State(app_state.clone()), "brake pad", "eng-gb")
+            .unwrap()
+            .unwrap();
+        add_term_to_term_set(State(app_state.clone()), pad_set, &test_term("remblok", "nld-nl")).unwrap();
+        for source in ["brake", "pad", "café", "C++"] {
+            add_term_wrapper(&app_state, &test_term(source, "eng-gb")).unwrap();
+        }
+
+        let text = "Replace the BRAKE PAD, not the brakes. Cafe\u{301} near the launchpad uses C++.";
+        clear_recognizers();
+        let found = recognize_terms(
+            State(app_state.clone()),
+            text,
+            "eng",
+            "nld",
+            &SearchOptions::default(),
+        )
+        .unwrap();
+
+        let spans: Vec<_> = found.iter().map(|term| term.text.as_str()).collect();
+        assert_eq!(spans, vec!["BRAKE PAD", "Cafe\u{301}", "C++"]);
+        assert_eq!((found[0].start, found[0].end), (12, 21));
+        assert_eq!(found[0].concepts[0].term_set_id, pad_set);
+        assert_eq!(
+            found[0].concepts[0].targets[0].term_language_set.term.as_deref(),
+            Some("remblok")
+        );
+        assert!(found[1].concepts[0].targets.is_empty());
+
+        // Changed terms are picked up without dropping every automaton.
+        let recognized = |text: &str| -> Vec<String> {
+            recognize_terms(State(app_state.clone()), text, "eng", "", &SearchOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|term| term.text)
+                .collect()
+        };
+        add_term_to_term_set(State(app_state.clone()), pad_set, &test_term("remkussen", "nld-nl")).unwrap();
+        terms_changed();
+        assert_eq!(recognized("a brake and an axle"), vec!["brake"]);
+        add_term_wrapper(&app_state, &test_term("axle", "eng-gb")).unwrap();
+        terms_changed();
+        assert_eq!(recognized("a brake and an axle"), vec!["brake", "axle"]);
+        let axle = search_terms(State(app_state.clone()), "axle", "eng-gb").unwrap()[0].term_id;
+        let update = TermLanguageSet { term: Some("axles".to_string()), ..Default::default() };
+        update_term(State(app_state.clone()), axle, &update).unwrap();
+        terms_changed();
+        assert_eq!(recognized("two axles and C++"), vec!["axles", "C++"]);
+
+        let recognizer = Recognizer::new(
+            search_terms(State(app_state.clone()), "", "").unwrap(),
+            "deu",
+            SearchOptions::default(),
+        );
+        assert_eq!(recognizer.pattern_count(), 0);
+        assert!(recognizer.find(text).is_empty());
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_qa_segments() {
+        let app_state = create_test_app_state("test_db_qa_segments");
+        let term = |term: &str, language: &str, term_type: Option<&str>| TermLanguageSet {
+            term_type: term_type.map(str::to_string),
+            ..test_term(term, language)
+        };
+        add_term_wrapper(&app_state, &term("brake pad", "eng-gb", None)).unwrap();
+        let pad_set = get_term_set_id(State(app_state.clone()), "brake pad", "eng-gb")
+            .unwrap()
+            .unwrap();
+        for dutch in [
+            term("remblok", "nld-nl", None),
+            term("remvoering", "nld-nl", None),
+            term("remkussen", "nld-nl", Some("forbidden")),
+        ] {
+            add_term_to_term_set(State(app_state.clone()), pad_set, &dutch).unwrap();
+        }
+
+        let segment = |id: &str, source: &str, target: &str| Segment {
+            id: Some(id.to_string()),
+            source: source.to_string(),
+            target: target.to_string(),
+        };
+        let segments = vec![
+            segment("1", "Check the brake pad.", "Controleer het remblok."),
+            segment("2", "Replace the brake pad.", "Vervang het remblok."),
+            segment("3", "Clean the brake pad.", "Reinig de remvoering."),
+            segment("4", "Order a brake pad.", "Bestel een remkussen."),
+        ];
+        clear_recognizers();
+        let report = check_segments(
+            State(app_state.clone()),
+            &segments,
+            "eng",
+            "nld",
+            &SearchOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.checked_segments, 4);
+        let issues: Vec<_> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.segment.as_str(), issue.kind))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                ("4", QaIssueKind::Missing),
+                ("4", QaIssueKind::Forbidden),
+                ("3", QaIssueKind::Inconsistent),
+            ]
+        );
+        assert_eq!(report.issues[0].expected, vec!["remblok", "remvoering"]);
+        assert_eq!(report.issues[1].found, vec!["remkussen"]);
+        assert_eq!(report.issues[2].found, vec!["remvoering"]);
+        assert_eq!(report.issues[2].expected, vec!["remblok"]);
+        remove_test_db(&app_state);
+    }
+
+    #[test]
+    fn test_db_qa_xliff() {
+        let app_state = create_test_app_state("test_db_qa_xliff");
+        let term = |term: &str, language: &str, term_type: Option<&str>| TermLanguageSet {
+            term_type: term_type.map(str::to_string),
+            ..test_term(term, language)
+        };
+        add_term_wrapper(&app_state, &term("brake pad", "eng-gb", None)).unwrap();
+        let pad_set = get_term_set_id(State(app_state.clone()), "brake pad", "eng-gb")
+            .unwrap()
+            .unwrap();
+        add_term_to_term_set(State(app_state.clone()), pad_set, &term("remblok", "nld-nl", None))
+            .unwrap();
+        add_term_to_term_set(
+            State(app_state.clone()),
+            pad_set,
+            &term("remkussen", "nld-nl", Some("deprecated")),
+        )
+        .unwrap();
+
+        let xliff_1_2 = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">
+  <file original="manual.docx" source-language="en-US" target-language="nl-NL" datatype="plaintext">
+    <body>
+      <trans-unit id="t1">
+        <source>Check the <g id="1">brake pad</g>.</source>
+        <target>Controleer het <g id="1">remblok</g>.</target>
+      </trans-unit>
+      <group id="g1">
+        <trans-unit id="t2">
+          <source>Replace the brake<ph id="2">&lt;br/&gt;</ph> pad.</source>
+          <target>Vervang het remkussen.</target>
+        </trans-unit>
+      </group>
+      <trans-unit id="t3" translate="no">
+        <source>brake pad</source>
+      </trans-unit>
+    </body>
+  </file>
+</xliff>"#;
+        let document = read_xliff(xliff_1_2.as_bytes()).unwrap();
+        assert_eq!(document.version, "1.2");
+        assert_eq!(document.units.len(), 2);
+        assert_eq!(document.units[0].source, "Check the brake pad.");
+        assert_eq!(document.units[1].source, "Replace the brake pad.");
+        assert_eq!(document.units[1].target_language, "nl-NL");
+
+        clear_recognizers();
+        let report =
+            check_xliff(State(app_state.clone()), &document, &SearchOptions::default()).unwrap();
+        assert_eq!(report.checked_units, 2);
+        assert_eq!(report.units.len(), 1);
+        assert_eq!(report.units[0].id, "t2");
+        let kinds: Vec<_> = report.units[0].issues.iter().map(|issue| issue.kind).collect();
+        assert_eq!(kinds, vec![QaIssueKind::Missing, QaIssueKind::Forbidden]);
+        assert_eq!(report.units[0].issues[0].segment, "t2");
+        assert_eq!(report.units[0].issues[0].expected, vec!["remblok"]);
+        assert_eq!(report.units[0].issues[1].found, vec!["remkussen"]);
+
+        let xliff_2 = r#"<xliff xmlns="urn:oasis:names:tc:xliff:document:2.0" version="2.0" srcLang="en" trgLang="nl">
+  <file id="f1">
+    <unit id="u1">
+      <segment><source>The brake pad</source><target>Het remblok</target></segment>
+      <ignorable><source> </source><target> </target></ignorable>
+      <segment><source>is worn.</source><target>is versleten.</target></segment>
+    </unit>
+  </file>
+</xliff>"#;
+        let document = read_xliff(xliff_2.as_bytes()).unwrap();
+        assert_eq!(document.units[0].source, "The brake pad is worn.");
+        assert_eq!(document.units[0].file, "f1");
+        let report =
+            check_xliff(State(app_state.clone()), &document, &SearchOptions::default()).unwrap();
+        assert!(report.units.is_empty());
+
+        assert!(read_xliff(b"<martif/>").is_err());
+        remove_test_db(&app_state);
+    }
+
     #[test]
     fn test_db_delete_termset() {
         let app_state = create_test_app_state("test_db_delete_termset");
@@ -234,7 +2072,7 @@ mod tests {
         let term_set_id = get_term_set_id(State(app_state.clone()), "term_1", "en")
             .unwrap()
             .unwrap();
-        delete_term(State(app_state.clone()), term_set_id).unwrap();
+        delete_term(State(app_state.clone()), term_set_id, None).unwrap();
 
         let terms = search_terms(State(app_state.clone()), "term_1", "en").unwrap();
         assert_eq!(terms.len(), 0);